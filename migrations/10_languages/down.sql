@@ -0,0 +1,5 @@
+ALTER TABLE `invoices`
+    DROP COLUMN `language`;
+
+ALTER TABLE `contacts`
+    DROP COLUMN `language`;
@@ -0,0 +1,5 @@
+ALTER TABLE `contacts`
+    ADD COLUMN `language` VARCHAR(2) NOT NULL DEFAULT 'cs';
+
+ALTER TABLE `invoices`
+    ADD COLUMN `language` VARCHAR(2) NULL;
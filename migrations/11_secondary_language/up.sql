@@ -0,0 +1,2 @@
+ALTER TABLE `contacts`
+    ADD COLUMN `secondary_language` VARCHAR(2) NULL;
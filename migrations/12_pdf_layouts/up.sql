@@ -0,0 +1,2 @@
+ALTER TABLE `accounts`
+    ADD COLUMN `pdf_layout` TEXT NULL;
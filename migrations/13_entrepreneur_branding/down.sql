@@ -0,0 +1,4 @@
+DROP TABLE `entrepreneur_logos`;
+
+ALTER TABLE `entrepreneurs`
+    DROP COLUMN `accent_color`;
@@ -0,0 +1,13 @@
+ALTER TABLE `entrepreneurs`
+    ADD COLUMN `accent_color` VARCHAR(7) NULL;
+
+CREATE TABLE `entrepreneur_logos`
+(
+    `entrepreneur_id` INT        NOT NULL,
+    `format`          VARCHAR(4) NOT NULL,
+    `data`            MEDIUMBLOB NOT NULL,
+    PRIMARY KEY (`entrepreneur_id`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `entrepreneur_logos`
+    ADD FOREIGN KEY (`entrepreneur_id`) REFERENCES `entrepreneurs` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,3 @@
+ALTER TABLE `invoices`
+    DROP COLUMN `exchange_rate`,
+    DROP COLUMN `currency_code`;
@@ -0,0 +1,11 @@
+-- the invoices made so far are in the currency of their entrepreneur
+ALTER TABLE `invoices`
+    ADD COLUMN `currency_code` VARCHAR(10) NOT NULL DEFAULT '' AFTER `language`,
+    ADD COLUMN `exchange_rate` DECIMAL(15, 6) NOT NULL DEFAULT 1 AFTER `currency_code`;
+
+UPDATE `invoices`
+    JOIN `entrepreneurs` ON `entrepreneurs`.`id` = `invoices`.`entrepreneur_id`
+SET `invoices`.`currency_code` = `entrepreneurs`.`currency_code`;
+
+ALTER TABLE `invoices`
+    ALTER COLUMN `currency_code` DROP DEFAULT;
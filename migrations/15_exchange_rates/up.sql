@@ -0,0 +1,7 @@
+CREATE TABLE `exchange_rates`
+(
+    `currency_code` VARCHAR(10)    NOT NULL,
+    `valid_from`    DATE           NOT NULL,
+    `rate`          DECIMAL(15, 6) NOT NULL,
+    PRIMARY KEY (`currency_code`, `valid_from`)
+) ENGINE = InnoDB;
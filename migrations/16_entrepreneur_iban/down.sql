@@ -0,0 +1,3 @@
+ALTER TABLE `entrepreneurs`
+    DROP COLUMN `bic`,
+    DROP COLUMN `iban`;
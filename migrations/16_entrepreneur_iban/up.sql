@@ -0,0 +1,4 @@
+-- IBAN of the account for the countries without Czech-like account numbers, it takes precedence over the account number
+ALTER TABLE `entrepreneurs`
+    ADD COLUMN `iban` VARCHAR(34) NULL AFTER `account_bank_code`,
+    ADD COLUMN `bic`  VARCHAR(11) NULL AFTER `iban`;
@@ -0,0 +1,55 @@
+ALTER TABLE `invoices`
+    DROP FOREIGN KEY `fk_invoices_bank_account`;
+
+ALTER TABLE `invoices`
+    DROP COLUMN `bank_account_id`;
+
+ALTER TABLE `entrepreneurs`
+    ADD COLUMN `account_number_country_code` VARCHAR(2)  NOT NULL DEFAULT 'CZ' AFTER `vat`,
+    ADD COLUMN `account_number_prefix`       SMALLINT    NULL AFTER `account_number_country_code`,
+    ADD COLUMN `account_number`              BIGINT      NOT NULL DEFAULT 0 AFTER `account_number_prefix`,
+    ADD COLUMN `account_bank_code`           SMALLINT    NOT NULL DEFAULT 0 AFTER `account_number`,
+    ADD COLUMN `iban`                        VARCHAR(34) NULL AFTER `account_bank_code`,
+    ADD COLUMN `bic`                         VARCHAR(11) NULL AFTER `iban`;
+
+-- the default accounts in the currencies of the entrepreneurs, by their IBAN
+UPDATE `entrepreneurs`
+    JOIN `bank_accounts` ON `bank_accounts`.`entrepreneur_id` = `entrepreneurs`.`id`
+        AND `bank_accounts`.`currency_code` = `entrepreneurs`.`currency_code`
+        AND `bank_accounts`.`is_default`
+SET `entrepreneurs`.`iban` = `bank_accounts`.`iban`,
+    `entrepreneurs`.`bic`  = `bank_accounts`.`bic`;
+
+-- the account numbers that couldn't be converted, unless an account was entered again
+UPDATE `entrepreneurs`
+    JOIN `unconverted_bank_accounts` ON `unconverted_bank_accounts`.`entrepreneur_id` = `entrepreneurs`.`id`
+SET `entrepreneurs`.`account_number_country_code` = `unconverted_bank_accounts`.`account_number_country_code`,
+    `entrepreneurs`.`account_number_prefix`       = `unconverted_bank_accounts`.`account_number_prefix`,
+    `entrepreneurs`.`account_number`              = `unconverted_bank_accounts`.`account_number`,
+    `entrepreneurs`.`account_bank_code`           = `unconverted_bank_accounts`.`account_bank_code`,
+    `entrepreneurs`.`bic`                         = `unconverted_bank_accounts`.`bic`
+WHERE `entrepreneurs`.`iban` IS NULL;
+
+DROP TABLE `unconverted_bank_accounts`;
+
+-- an entrepreneur has a single account there, the other ones are set aside rather than lost
+CREATE TABLE IF NOT EXISTS `reverted_bank_accounts`
+(
+    `entrepreneur_id` INT         NOT NULL,
+    `currency_code`   VARCHAR(10) NOT NULL,
+    `iban`            VARCHAR(34) NOT NULL,
+    `bic`             VARCHAR(11) NULL,
+    `is_default`      BOOLEAN     NOT NULL
+) ENGINE = InnoDB;
+
+INSERT INTO `reverted_bank_accounts` (`entrepreneur_id`, `currency_code`, `iban`, `bic`, `is_default`)
+SELECT `bank_accounts`.`entrepreneur_id`,
+       `bank_accounts`.`currency_code`,
+       `bank_accounts`.`iban`,
+       `bank_accounts`.`bic`,
+       `bank_accounts`.`is_default`
+FROM `bank_accounts`
+    JOIN `entrepreneurs` ON `entrepreneurs`.`id` = `bank_accounts`.`entrepreneur_id`
+WHERE NOT (`bank_accounts`.`is_default` AND `bank_accounts`.`currency_code` = `entrepreneurs`.`currency_code`);
+
+DROP TABLE `bank_accounts`;
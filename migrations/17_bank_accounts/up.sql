@@ -0,0 +1,81 @@
+CREATE TABLE `bank_accounts`
+(
+    `id`              INT         NOT NULL AUTO_INCREMENT,
+    `entrepreneur_id` INT         NOT NULL,
+    `currency_code`   VARCHAR(10) NOT NULL,
+    `iban`            VARCHAR(34) NOT NULL,
+    `bic`             VARCHAR(11) NULL,
+    `is_default`      BOOLEAN     NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `bank_accounts`
+    ADD FOREIGN KEY (`entrepreneur_id`) REFERENCES `entrepreneurs` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- the accounts of the entrepreneurs become their default ones in their currency
+INSERT INTO `bank_accounts` (`entrepreneur_id`, `currency_code`, `iban`, `bic`, `is_default`)
+SELECT `id`, `currency_code`, `iban`, `bic`, TRUE
+FROM `entrepreneurs`
+WHERE `iban` IS NOT NULL;
+
+-- IBANs of the Czech and Slovak account numbers: the check digits are computed of the BBAN followed by the country
+-- (CZ is 1235 and SK is 2820 in base 36) and 00
+INSERT INTO `bank_accounts` (`entrepreneur_id`, `currency_code`, `iban`, `bic`, `is_default`)
+SELECT `id`,
+       `currency_code`,
+       CONCAT(`country`,
+              LPAD(98 - MOD(CAST(CONCAT(`bban`, IF(`country` = 'CZ', '1235', '2820'), '00') AS DECIMAL(30)), 97), 2, '0'),
+              `bban`),
+       `bic`,
+       TRUE
+FROM (SELECT `id`,
+             `currency_code`,
+             `bic`,
+             `account_number_country_code` AS `country`,
+             CONCAT(LPAD(`account_bank_code`, 4, '0'),
+                    LPAD(IFNULL(`account_number_prefix`, 0), 6, '0'),
+                    LPAD(`account_number`, 10, '0')) AS `bban`
+      FROM `entrepreneurs`
+      WHERE `iban` IS NULL
+        AND `account_number` <> 0
+        AND `account_number_country_code` IN ('CZ', 'SK')) AS `domestic`;
+
+-- the account numbers of the other countries can't be converted without their IBAN, they're kept aside to be entered
+-- again rather than lost
+CREATE TABLE `unconverted_bank_accounts`
+(
+    `entrepreneur_id`             INT         NOT NULL,
+    `account_number_country_code` VARCHAR(2)  NOT NULL,
+    `account_number_prefix`       SMALLINT    NULL,
+    `account_number`              BIGINT      NOT NULL,
+    `account_bank_code`           SMALLINT    NOT NULL,
+    `bic`                         VARCHAR(11) NULL,
+    PRIMARY KEY (`entrepreneur_id`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `unconverted_bank_accounts`
+    ADD FOREIGN KEY (`entrepreneur_id`) REFERENCES `entrepreneurs` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
+
+INSERT INTO `unconverted_bank_accounts` (`entrepreneur_id`, `account_number_country_code`, `account_number_prefix`,
+                                         `account_number`, `account_bank_code`, `bic`)
+SELECT `id`, `account_number_country_code`, `account_number_prefix`, `account_number`, `account_bank_code`, `bic`
+FROM `entrepreneurs`
+WHERE `iban` IS NULL
+  AND `account_number` <> 0
+  AND `account_number_country_code` NOT IN ('CZ', 'SK');
+
+ALTER TABLE `entrepreneurs`
+    DROP COLUMN `bic`,
+    DROP COLUMN `iban`,
+    DROP COLUMN `account_bank_code`,
+    DROP COLUMN `account_number`,
+    DROP COLUMN `account_number_prefix`,
+    DROP COLUMN `account_number_country_code`;
+
+-- none is the default account in the currency of the invoice
+ALTER TABLE `invoices`
+    ADD COLUMN `bank_account_id` INT NULL AFTER `exchange_rate`;
+
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `fk_invoices_bank_account`
+        FOREIGN KEY (`bank_account_id`) REFERENCES `bank_accounts` (`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
@@ -0,0 +1,2 @@
+ALTER TABLE `invoice_rows`
+    DROP COLUMN `vat_rate`;
@@ -0,0 +1,2 @@
+ALTER TABLE `invoice_rows`
+    ADD COLUMN `vat_rate` SMALLINT UNSIGNED NULL AFTER `item_count`;
@@ -0,0 +1,2 @@
+ALTER TABLE `invoice_rows`
+    MODIFY COLUMN `item_price` FLOAT UNSIGNED NOT NULL;
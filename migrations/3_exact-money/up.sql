@@ -0,0 +1,10 @@
+-- FLOAT -> DECIMAL; the float is first cast to a precise DECIMAL so the ROUND rounds half away from zero
+ALTER TABLE `invoice_rows`
+    ADD COLUMN `item_price_exact` DECIMAL(15, 2) NOT NULL DEFAULT 0 AFTER `item_price`;
+
+UPDATE `invoice_rows`
+SET `item_price_exact` = ROUND(CAST(`item_price` AS DECIMAL(20, 6)), 2);
+
+ALTER TABLE `invoice_rows`
+    DROP COLUMN `item_price`,
+    CHANGE COLUMN `item_price_exact` `item_price` DECIMAL(15, 2) NOT NULL;
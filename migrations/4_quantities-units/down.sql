@@ -0,0 +1,3 @@
+ALTER TABLE `invoice_rows`
+    MODIFY COLUMN `item_count` SMALLINT UNSIGNED NOT NULL,
+    DROP COLUMN `item_unit`;
@@ -0,0 +1,3 @@
+ALTER TABLE `invoice_rows`
+    MODIFY COLUMN `item_count` DECIMAL(12, 3) NOT NULL,
+    ADD COLUMN `item_unit` VARCHAR(20) NULL AFTER `item_count`;
@@ -0,0 +1,7 @@
+ALTER TABLE `invoice_rows`
+    DROP COLUMN `discount_percent`,
+    DROP COLUMN `discount_amount`;
+
+ALTER TABLE `invoices`
+    DROP COLUMN `discount_percent`,
+    DROP COLUMN `discount_amount`;
@@ -0,0 +1,7 @@
+ALTER TABLE `invoice_rows`
+    ADD COLUMN `discount_percent` DECIMAL(5, 2) NULL AFTER `vat_rate`,
+    ADD COLUMN `discount_amount`  DECIMAL(15, 2) NULL AFTER `discount_percent`;
+
+ALTER TABLE `invoices`
+    ADD COLUMN `discount_percent` DECIMAL(5, 2) NULL AFTER `payed`,
+    ADD COLUMN `discount_amount`  DECIMAL(15, 2) NULL AFTER `discount_percent`;
@@ -0,0 +1,13 @@
+-- credit notes are tax documents, the migration fails instead of dropping them
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `chk_invoices_no_credit_notes` CHECK (`kind` = 'invoice');
+
+ALTER TABLE `invoices`
+    DROP CONSTRAINT `chk_invoices_no_credit_notes`;
+
+ALTER TABLE `invoices`
+    DROP FOREIGN KEY `fk_invoices_original_invoice`;
+
+ALTER TABLE `invoices`
+    DROP COLUMN `original_invoice_id`,
+    DROP COLUMN `kind`;
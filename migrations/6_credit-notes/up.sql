@@ -0,0 +1,8 @@
+ALTER TABLE `invoices`
+    ADD COLUMN `kind`                VARCHAR(20) NOT NULL DEFAULT 'invoice' AFTER `code`,
+    ADD COLUMN `original_invoice_id` INT         NULL AFTER `kind`;
+
+-- credit notes are tax documents on their own, they mustn't be deleted with their invoice
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `fk_invoices_original_invoice`
+        FOREIGN KEY (`original_invoice_id`) REFERENCES `invoices` (`id`) ON DELETE RESTRICT ON UPDATE CASCADE;
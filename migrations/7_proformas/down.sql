@@ -0,0 +1,13 @@
+-- the migration fails instead of dropping the proformas
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `chk_invoices_no_proformas` CHECK (`kind` <> 'proforma');
+
+ALTER TABLE `invoices`
+    DROP CONSTRAINT `chk_invoices_no_proformas`;
+
+ALTER TABLE `invoices`
+    DROP FOREIGN KEY `fk_invoices_proforma`;
+
+ALTER TABLE `invoices`
+    DROP INDEX `uq_invoices_proforma`,
+    DROP COLUMN `proforma_id`;
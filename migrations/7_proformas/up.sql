@@ -0,0 +1,10 @@
+ALTER TABLE `invoices`
+    ADD COLUMN `proforma_id` INT NULL AFTER `original_invoice_id`;
+
+-- a proforma is finalized just once
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `uq_invoices_proforma` UNIQUE (`proforma_id`);
+
+ALTER TABLE `invoices`
+    ADD CONSTRAINT `fk_invoices_proforma`
+        FOREIGN KEY (`proforma_id`) REFERENCES `invoices` (`id`) ON DELETE SET NULL ON UPDATE CASCADE;
@@ -0,0 +1,15 @@
+CREATE TABLE `invoice_recurrences`
+(
+    `id`           INT         NOT NULL AUTO_INCREMENT,
+    `invoice_id`   INT         NOT NULL,
+    `period`       VARCHAR(20) NOT NULL,
+    `day_of_month` SMALLINT    NOT NULL,
+    `end_date`     DATE        NULL,
+    `next_run`     DATE        NOT NULL,
+    `paused`       BOOLEAN     NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (`id`),
+    UNIQUE (`invoice_id`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `invoice_recurrences`
+    ADD FOREIGN KEY (`invoice_id`) REFERENCES `invoices` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,2 @@
+DROP TABLE `invoice_numbers`;
+DROP TABLE `invoice_counters`;
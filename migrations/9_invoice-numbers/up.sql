@@ -0,0 +1,26 @@
+CREATE TABLE `invoice_counters`
+(
+    `entrepreneur_id` INT         NOT NULL,
+    `kind`            VARCHAR(20) NOT NULL,
+    `period`          VARCHAR(10) NOT NULL,
+    `initial_value`   INT         NOT NULL,
+    `value`           INT         NOT NULL,
+    PRIMARY KEY (`entrepreneur_id`, `kind`, `period`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `invoice_counters`
+    ADD FOREIGN KEY (`entrepreneur_id`) REFERENCES `entrepreneurs` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE TABLE `invoice_numbers`
+(
+    `invoice_id`      INT         NOT NULL,
+    `entrepreneur_id` INT         NOT NULL,
+    `kind`            VARCHAR(20) NOT NULL,
+    `period`          VARCHAR(10) NOT NULL,
+    `number`          INT         NOT NULL,
+    PRIMARY KEY (`invoice_id`),
+    UNIQUE (`entrepreneur_id`, `kind`, `period`, `number`)
+) ENGINE = InnoDB;
+
+ALTER TABLE `invoice_numbers`
+    ADD FOREIGN KEY (`invoice_id`) REFERENCES `invoices` (`id`) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -28,11 +28,31 @@ pub struct AccountsConfig {
     pub login_salt: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct SchedulerConfig {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExchangeRatesConfig {
+    /// Directory with the files of the ČNB rates, `None` when they're only uploaded.
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+    /// IDs of the accounts allowed to upload the rates (shared by all the accounts), none by default.
+    #[serde(default)]
+    pub upload_accounts: Vec<u32>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub http: HttpConfig,
     pub database: DbConfig,
     pub accounts: AccountsConfig,
+    pub scheduler: SchedulerConfig,
+    pub exchange_rates: ExchangeRatesConfig,
 }
 
 impl AppConfig {
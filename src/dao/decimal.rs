@@ -0,0 +1,442 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use diesel::deserialize::{self, FromSql, FromSqlRow, Queryable};
+use diesel::expression::bound::Bound;
+use diesel::expression::AsExpression;
+use diesel::mysql::Mysql;
+use diesel::row::Row;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{Binary, Nullable, Numeric};
+use err_context::AnyError;
+use serde::de::{Error as DeError, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Amount of money, in hundredths of the currency unit.
+pub type Money = Decimal<2>;
+
+/// Quantity of an invoiced item (e.g. 1.5 hours), with up to 3 decimal places.
+pub type Quantity = Decimal<3>;
+
+/// Percentage (e.g. a discount of 12.5 %), with up to 2 decimal places.
+pub type Percent = Decimal<2>;
+
+/// Exchange rate - price of one unit of a currency in another one, with up to 6 decimal places.
+pub type Rate = Decimal<6>;
+
+/// Fixed-point decimal number with `SCALE` decimal places, stored as an integer count of `10^-SCALE` units.
+///
+/// Rounding policy: whenever a value has to be rounded (parsing a more precise input, multiplication, percentage),
+/// it's rounded half away from zero - the same way MySQL's `ROUND` works for `DECIMAL` values, so amounts computed
+/// in the DB and here always match.
+///
+/// In the DB it's a `DECIMAL` column, in JSON it's a plain number (or a string, when deserializing). The JSON number
+/// goes through `f64` but that's lossless for any value with up to 15 significant digits, which is plenty.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal<const SCALE: u32>(i64);
+
+impl<const SCALE: u32> Decimal<SCALE> {
+    pub const ZERO: Self = Decimal(0);
+
+    pub const fn from_units(units: i64) -> Self {
+        Decimal(units)
+    }
+
+    /// Panics when the value is out of range, use `checked_from_int` for values from the outside.
+    pub fn from_int(value: i64) -> Self {
+        Self::checked_from_int(value).expect("Decimal number out of range")
+    }
+
+    pub fn checked_from_int(value: i64) -> Option<Self> {
+        value.checked_mul(Self::factor() as i64).map(Decimal)
+    }
+
+    pub fn units(self) -> i64 {
+        self.0
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.0 % Self::factor() as i64 == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn abs(self) -> Self {
+        Decimal(self.0.abs())
+    }
+
+    /// Integer part of the number (truncated towards zero).
+    pub fn trunc(self) -> i64 {
+        self.0 / Self::factor() as i64
+    }
+
+    /// Multiplies by another decimal, rounding the result to this scale.
+    pub fn times<const T: u32>(self, other: Decimal<T>) -> Self {
+        Decimal(round_div(self.0 as i128 * other.0 as i128, Decimal::<T>::factor()) as i64)
+    }
+
+    /// Computes `percent` % of this value, rounded to this scale.
+    pub fn percent<const T: u32>(self, percent: Decimal<T>) -> Self {
+        Decimal(round_div(self.0 as i128 * percent.0 as i128, 100 * Decimal::<T>::factor()) as i64)
+    }
+
+    /// Computes `part / whole` of this value (e.g. a proportional share of a discount), rounded to this scale. `None`
+    /// when the `whole` is zero.
+    pub fn share(self, part: Self, whole: Self) -> Option<Self> {
+        if whole.0 == 0 {
+            return None;
+        }
+
+        Some(Decimal(round_div(self.0 as i128 * part.0 as i128, whole.0 as i128) as i64))
+    }
+
+    /// Converts to another scale, rounding when the target scale is smaller.
+    pub fn rescale<const T: u32>(self) -> Decimal<T> {
+        if T >= SCALE {
+            Decimal(self.0 * 10i64.pow(T - SCALE))
+        } else {
+            Decimal(round_div(self.0 as i128, 10i128.pow(SCALE - T)) as i64)
+        }
+    }
+
+    /// Lossy conversion, use only where exactness doesn't matter (e.g. serialization to JSON).
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::factor() as f64
+    }
+
+    /// `None` when the value is out of range (or not a number at all).
+    fn from_f64(value: f64) -> Option<Self> {
+        let units = (value * Self::factor() as f64).round();
+
+        // i64::MAX isn't exactly representable, it rounds up to 2^63
+        (units >= i64::MIN as f64 && units < i64::MAX as f64).then_some(Decimal(units as i64))
+    }
+
+    const fn factor() -> i128 {
+        10i128.pow(SCALE)
+    }
+}
+
+/// Integer division rounding half away from zero.
+fn round_div(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    let r = n % d;
+
+    if 2 * r.abs() >= d.abs() {
+        q + n.signum() * d.signum()
+    } else {
+        q
+    }
+}
+
+impl<const SCALE: u32> FromStr for Decimal<SCALE> {
+    type Err = AnyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+
+        let (int_part, frac_part) = match digits.split_once('.') {
+            Some((i, f)) => (i, f),
+            None => (digits, ""),
+        };
+
+        let all_digits = |p: &str| p.chars().all(|c| c.is_ascii_digit());
+
+        if (int_part.is_empty() && frac_part.is_empty()) || !all_digits(int_part) || !all_digits(frac_part) {
+            return Err(AnyError::from(format!("Invalid decimal number: {}", s)));
+        }
+
+        let invalid = || AnyError::from(format!("Decimal number out of range: {}", s));
+
+        let int_value: i128 = if int_part.is_empty() { 0 } else { int_part.parse().map_err(|_| invalid())? };
+
+        // all digits of the fractional part, the rounding happens at the end
+        let frac_value: i128 = if frac_part.is_empty() { 0 } else { frac_part.parse().map_err(|_| invalid())? };
+        let frac_factor = 10i128
+            .checked_pow(frac_part.len() as u32)
+            .ok_or_else(|| AnyError::from(format!("Too many decimal places: {}", s)))?;
+
+        let value = int_value
+            .checked_mul(frac_factor)
+            .and_then(|v| v.checked_add(frac_value))
+            .and_then(|v| v.checked_mul(Self::factor()))
+            .ok_or_else(invalid)?;
+        let value = round_div(value, frac_factor);
+        let value = if negative { -value } else { value };
+
+        i64::try_from(value).map(Decimal).map_err(|_| invalid())
+    }
+}
+
+impl<const SCALE: u32> Display for Decimal<SCALE> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let factor = Self::factor() as u64;
+        let abs = self.0.unsigned_abs();
+
+        if SCALE == 0 {
+            write!(f, "{}{}", sign, abs)
+        } else {
+            write!(f, "{}{}.{:0width$}", sign, abs / factor, abs % factor, width = SCALE as usize)
+        }
+    }
+}
+
+impl<const SCALE: u32> Add for Decimal<SCALE> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Decimal(self.0 + rhs.0)
+    }
+}
+
+impl<const SCALE: u32> AddAssign for Decimal<SCALE> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0
+    }
+}
+
+impl<const SCALE: u32> Sub for Decimal<SCALE> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Decimal(self.0 - rhs.0)
+    }
+}
+
+impl<const SCALE: u32> SubAssign for Decimal<SCALE> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0
+    }
+}
+
+impl<const SCALE: u32> Neg for Decimal<SCALE> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Decimal(-self.0)
+    }
+}
+
+impl<const SCALE: u32> Mul<i64> for Decimal<SCALE> {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        Decimal(self.0.checked_mul(rhs).expect("Decimal number out of range"))
+    }
+}
+
+impl<const SCALE: u32> Sum for Decimal<SCALE> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<'a, const SCALE: u32> Sum<&'a Decimal<SCALE>> for Decimal<SCALE> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+// Diesel's derives can't handle const generics, so these are what `FromSqlRow` and `AsExpression` would generate.
+
+impl<const SCALE: u32> FromSqlRow<Numeric, Mysql> for Decimal<SCALE> {
+    fn build_from_row<R: Row<Mysql>>(row: &mut R) -> deserialize::Result<Self> {
+        FromSql::<Numeric, Mysql>::from_sql(row.take())
+    }
+}
+
+impl<const SCALE: u32> Queryable<Numeric, Mysql> for Decimal<SCALE> {
+    type Row = Self;
+
+    fn build(row: Self::Row) -> Self {
+        row
+    }
+}
+
+impl<const SCALE: u32> AsExpression<Numeric> for Decimal<SCALE> {
+    type Expression = Bound<Numeric, Self>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<const SCALE: u32> AsExpression<Numeric> for &Decimal<SCALE> {
+    type Expression = Bound<Numeric, Self>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<const SCALE: u32> AsExpression<Nullable<Numeric>> for Decimal<SCALE> {
+    type Expression = Bound<Nullable<Numeric>, Self>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<const SCALE: u32> AsExpression<Nullable<Numeric>> for &Decimal<SCALE> {
+    type Expression = Bound<Nullable<Numeric>, Self>;
+
+    fn as_expression(self) -> Self::Expression {
+        Bound::new(self)
+    }
+}
+
+impl<const SCALE: u32> ToSql<Nullable<Numeric>, Mysql> for Decimal<SCALE> {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> serialize::Result {
+        ToSql::<Numeric, Mysql>::to_sql(self, out)
+    }
+}
+
+impl<const SCALE: u32> FromSql<Numeric, Mysql> for Decimal<SCALE> {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        // MySQL sends DECIMAL values as plain ASCII strings
+        let bytes = <Vec<u8> as FromSql<Binary, Mysql>>::from_sql(bytes)?;
+        let str = String::from_utf8(bytes)?;
+
+        str.parse()
+    }
+}
+
+impl<const SCALE: u32> ToSql<Numeric, Mysql> for Decimal<SCALE> {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> serialize::Result {
+        write!(out, "{}", self).map(|_| IsNull::No).map_err(|e| e.into())
+    }
+}
+
+impl<const SCALE: u32> Serialize for Decimal<SCALE> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de, const SCALE: u32> Deserialize<'de> for Decimal<SCALE> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecimalVisitor<const SCALE: u32>;
+
+        impl<'de, const SCALE: u32> Visitor<'de> for DecimalVisitor<SCALE> {
+            type Value = Decimal<SCALE>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a decimal number or a string with a decimal number")
+            }
+
+            fn visit_i64<E: DeError>(self, v: i64) -> Result<Self::Value, E> {
+                Decimal::checked_from_int(v).ok_or_else(|| out_of_range(v))
+            }
+
+            fn visit_u64<E: DeError>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .ok()
+                    .and_then(Decimal::checked_from_int)
+                    .ok_or_else(|| out_of_range(v))
+            }
+
+            fn visit_f64<E: DeError>(self, v: f64) -> Result<Self::Value, E> {
+                Decimal::from_f64(v).ok_or_else(|| out_of_range(v))
+            }
+
+            fn visit_str<E: DeError>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(|e| E::custom(format!("{}", e)))
+            }
+        }
+
+        fn out_of_range<E: DeError>(v: impl Display) -> E {
+            E::custom(format!("Decimal number out of range: {}", v))
+        }
+
+        deserializer.deserialize_any(DecimalVisitor::<SCALE>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::money;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(money("1"), Money::from_units(100));
+        assert_eq!(money("1.5"), Money::from_units(150));
+        assert_eq!(money("-1.05"), Money::from_units(-105));
+        assert_eq!(money(".5"), Money::from_units(50));
+        assert_eq!(money("1234567.89"), Money::from_units(123456789));
+        assert_eq!(money("0.005"), Money::from_units(1));
+        assert_eq!(money("0.00499999"), Money::from_units(0));
+        assert_eq!(money("-0.005"), Money::from_units(-1));
+        assert_eq!(money("2.12500000"), Money::from_units(213));
+
+        assert!("".parse::<Money>().is_err());
+        assert!("1,5".parse::<Money>().is_err());
+        assert!("abc".parse::<Money>().is_err());
+        assert!("1.2.3".parse::<Money>().is_err());
+        assert!("92233720368547758.08".parse::<Money>().is_err());
+        assert!("170141183460469231731687303715884105727.5".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(money("1").to_string(), "1.00");
+        assert_eq!(money("-0.5").to_string(), "-0.50");
+        assert_eq!(money("1234.56").to_string(), "1234.56");
+        assert_eq!(Decimal::<0>::from_int(21).to_string(), "21");
+        assert_eq!(Decimal::<3>::from_units(1500).to_string(), "1.500");
+    }
+
+    #[test]
+    fn test_arithmetics() {
+        assert_eq!(money("0.10") + money("0.20"), money("0.30"));
+        assert_eq!(money("0.10") - money("0.20"), money("-0.10"));
+        assert_eq!(money("33.33") * 3, money("99.99"));
+        assert_eq!([money("0.1"); 10].iter().sum::<Money>(), money("1"));
+
+        assert_eq!(money("10.05").percent(Decimal::<0>::from_int(21)), money("2.11")); // 2.1105
+        assert_eq!(money("0.50").percent(Decimal::<0>::from_int(21)), money("0.11")); // 0.105
+        assert_eq!(money("-0.50").percent(Decimal::<0>::from_int(21)), money("-0.11"));
+        assert_eq!(money("100").percent(Decimal::<2>::from_units(1250)), money("12.50"));
+
+        assert_eq!(money("10.00").times(Decimal::<3>::from_units(1_333)), money("13.33"));
+        assert_eq!(money("0.15").times(Decimal::<1>::from_units(5)), money("0.08")); // 0.075
+
+        assert_eq!(money("100").share(money("1"), money("3")), Some(money("33.33")));
+        assert_eq!(money("100").share(money("2"), money("3")), Some(money("66.67")));
+        assert_eq!(money("100").share(money("2"), Money::ZERO), None);
+
+        assert_eq!(money("1.235").rescale::<1>(), Decimal::<1>::from_units(12));
+        assert_eq!(money("1.25").rescale::<1>(), Decimal::<1>::from_units(13));
+        assert_eq!(money("1.25").rescale::<3>(), Decimal::<3>::from_units(1250));
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(serde_json::to_string(&money("12.30")).unwrap(), "12.3");
+        assert_eq!(serde_json::from_str::<Money>("12.3").unwrap(), money("12.30"));
+        assert_eq!(serde_json::from_str::<Money>("0.1").unwrap(), money("0.10"));
+        assert_eq!(serde_json::from_str::<Money>("\"1.005\"").unwrap(), money("1.01"));
+        assert_eq!(serde_json::from_str::<Money>("12").unwrap(), money("12"));
+        assert_eq!(serde_json::from_str::<Money>("-12").unwrap(), money("-12"));
+
+        // f64 can't represent these exactly, but the rounding recovers the right value
+        assert_eq!(serde_json::from_str::<Money>("1234567.89").unwrap(), money("1234567.89"));
+        assert_eq!(serde_json::from_str::<Money>("0.29").unwrap(), money("0.29"));
+
+        assert!(serde_json::from_str::<Money>("92233720368547759").is_err());
+        assert!(serde_json::from_str::<Money>("-92233720368547759").is_err());
+        assert!(serde_json::from_str::<Money>("18446744073709551615").is_err());
+        assert!(serde_json::from_str::<Money>("1e300").is_err());
+    }
+}
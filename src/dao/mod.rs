@@ -1,9 +1,10 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::future::Future;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 
-use chrono::{Duration, NaiveDateTime as DateTime};
+use chrono::NaiveDate;
 use diesel::backend::Backend;
 use diesel::deserialize::FromSql;
 use diesel::expression::dsl::count;
@@ -14,7 +15,7 @@ use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_query;
 use diesel::sql_types::VarChar;
-use diesel::{delete, deserialize, insert_into, select, serialize};
+use diesel::{delete, deserialize, insert_into, insert_or_ignore_into, replace_into, select, serialize};
 use diesel::{sql_types, update};
 use diesel_logger::LoggingConnection;
 use err_context::AnyError;
@@ -23,8 +24,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::DbConfig;
 use crate::dao::models::NewInvoice;
-pub use crate::dao::models::{Account, Contact, Entrepreneur, Invoice, InvoiceRow, LoginSession, MonthlyMoney};
+pub use crate::dao::decimal::{Decimal, Money, Percent, Quantity, Rate};
+pub use crate::dao::models::{
+    Account, BankAccount, Contact, Entrepreneur, EntrepreneurLogo, ExchangeRate, Invoice, InvoiceCounter, InvoiceNumber, InvoiceRecurrence,
+    InvoiceRow, LoginSession, NewBankAccount, NewInvoiceRecurrence, NewInvoiceRow,
+};
 
+mod decimal;
 mod models;
 mod schema;
 
@@ -33,7 +39,8 @@ embed_migrations!("migrations");
 // TODO metrics
 
 pub type DaoResult<A> = Result<A, AnyError>;
-pub type InvoiceWithAllInfo = (Invoice, f64, String);
+/// Invoice with the contact name.
+pub type InvoiceWithContactName = (Invoice, String);
 
 type MysqlConnectionManager = ConnectionManager<LoggingConnection<MysqlConnection>>;
 type MysqlPool = Pool<MysqlConnectionManager>;
@@ -67,6 +74,204 @@ where
     }
 }
 
+/// Type of the document - a regular invoice, a credit note ("opravný daňový doklad") correcting one, or a proforma
+/// ("zálohová faktura") asking for an advance payment, which is not a tax document.
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, PartialEq, Eq, Hash, Copy, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "VarChar"]
+pub enum InvoiceKind {
+    #[default]
+    Invoice,
+    CreditNote,
+    Proforma,
+}
+
+impl InvoiceKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceKind::Invoice => "invoice",
+            InvoiceKind::CreditNote => "credit_note",
+            InvoiceKind::Proforma => "proforma",
+        }
+    }
+}
+
+impl<DB> FromSql<VarChar, DB> for InvoiceKind
+where
+    DB: Backend,
+    String: FromSql<VarChar, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "invoice" => Ok(InvoiceKind::Invoice),
+            "credit_note" => Ok(InvoiceKind::CreditNote),
+            "proforma" => Ok(InvoiceKind::Proforma),
+            other => Err(format!("Unknown invoice kind: {}", other).into()),
+        }
+    }
+}
+
+impl<DB> ToSql<VarChar, DB> for InvoiceKind
+where
+    DB: Backend,
+    str: ToSql<VarChar, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.as_str().to_sql(out)
+    }
+}
+
+/// How often a recurring invoice is issued.
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, PartialEq, Eq, Copy, Clone)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "VarChar"]
+pub enum RecurrencePeriod {
+    Monthly,
+    Quarterly,
+}
+
+impl RecurrencePeriod {
+    pub fn months(&self) -> u32 {
+        match self {
+            RecurrencePeriod::Monthly => 1,
+            RecurrencePeriod::Quarterly => 3,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            RecurrencePeriod::Monthly => "monthly",
+            RecurrencePeriod::Quarterly => "quarterly",
+        }
+    }
+}
+
+impl<DB> FromSql<VarChar, DB> for RecurrencePeriod
+where
+    DB: Backend,
+    String: FromSql<VarChar, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "monthly" => Ok(RecurrencePeriod::Monthly),
+            "quarterly" => Ok(RecurrencePeriod::Quarterly),
+            other => Err(format!("Unknown recurrence period: {}", other).into()),
+        }
+    }
+}
+
+impl<DB> ToSql<VarChar, DB> for RecurrencePeriod
+where
+    DB: Backend,
+    str: ToSql<VarChar, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.as_str().to_sql(out)
+    }
+}
+
+/// Language of the documents for a contact.
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, PartialEq, Eq, Hash, Copy, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "VarChar"]
+pub enum Language {
+    #[default]
+    Cs,
+    En,
+    De,
+    Sk,
+}
+
+impl Language {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Language::Cs => "cs",
+            Language::En => "en",
+            Language::De => "de",
+            Language::Sk => "sk",
+        }
+    }
+}
+
+impl<DB> FromSql<VarChar, DB> for Language
+where
+    DB: Backend,
+    String: FromSql<VarChar, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "cs" => Ok(Language::Cs),
+            "en" => Ok(Language::En),
+            "de" => Ok(Language::De),
+            "sk" => Ok(Language::Sk),
+            other => Err(format!("Unknown language: {}", other).into()),
+        }
+    }
+}
+
+impl<DB> ToSql<VarChar, DB> for Language
+where
+    DB: Backend,
+    str: ToSql<VarChar, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.as_str().to_sql(out)
+    }
+}
+
+/// Image format of a logo.
+#[derive(Debug, Serialize, Deserialize, FromSqlRow, AsExpression, PartialEq, Eq, Copy, Clone)]
+#[serde(rename_all = "camelCase")]
+#[sql_type = "VarChar"]
+pub enum LogoFormat {
+    Png,
+    Jpeg,
+    Svg,
+}
+
+impl LogoFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LogoFormat::Png => "png",
+            LogoFormat::Jpeg => "jpeg",
+            LogoFormat::Svg => "svg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            LogoFormat::Png => "image/png",
+            LogoFormat::Jpeg => "image/jpeg",
+            LogoFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+impl<DB> FromSql<VarChar, DB> for LogoFormat
+where
+    DB: Backend,
+    String: FromSql<VarChar, DB>,
+{
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match String::from_sql(bytes)?.as_str() {
+            "png" => Ok(LogoFormat::Png),
+            "jpeg" => Ok(LogoFormat::Jpeg),
+            "svg" => Ok(LogoFormat::Svg),
+            other => Err(format!("Unknown logo format: {}", other).into()),
+        }
+    }
+}
+
+impl<DB> ToSql<VarChar, DB> for LogoFormat
+where
+    DB: Backend,
+    str: ToSql<VarChar, DB>,
+{
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        self.as_str().to_sql(out)
+    }
+}
+
 #[derive(Clone)]
 pub struct Dao {
     pool: Arc<Mutex<MysqlPool>>,
@@ -178,16 +383,51 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
-    pub async fn get_invoice(&self, id: u32) -> DaoResult<Option<InvoiceWithAllInfo>> {
+    pub async fn get_entrepreneur_logo(&self, entrepreneur_id: u32) -> DaoResult<Option<EntrepreneurLogo>> {
+        use schema::entrepreneur_logos::dsl as table;
+
+        self.with_connection(|conn| {
+            table::entrepreneur_logos
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// The rate of the currency valid on the date - the last one published on or before it.
+    pub async fn get_exchange_rate(&self, currency_code: &str, date: NaiveDate) -> DaoResult<Option<ExchangeRate>> {
+        use schema::exchange_rates::dsl as table;
+
+        self.with_connection(|conn| {
+            table::exchange_rates
+                .filter(table::currency_code.eq(currency_code))
+                .filter(table::valid_from.le(date))
+                .order(table::valid_from.desc())
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// All the rates valid from the days of the period (both days included).
+    pub async fn get_exchange_rates(&self, from: NaiveDate, to: NaiveDate) -> DaoResult<Vec<ExchangeRate>> {
+        use schema::exchange_rates::dsl as table;
+
+        self.with_connection(|conn| table::exchange_rates.filter(table::valid_from.between(from, to)).load(conn))
+            .await
+            .map_err(Self::map_db_error)
+    }
+
+    pub async fn get_invoice(&self, id: u32) -> DaoResult<Option<InvoiceWithContactName>> {
         use schema::*;
 
         self.with_connection(|conn| {
             invoices::table
                 .select((
                     invoices::all_columns,
-                    diesel::dsl::sql::<diesel::sql_types::Double>(
-                        "ifnull((select sum(invoice_rows.item_price) from invoice_rows where invoice_rows.invoice_id=invoices.id), 0)",
-                    ),
                     diesel::dsl::sql::<diesel::sql_types::VarChar>(
                         "(select contacts.name from contacts where contacts.id=invoices.contact_id)",
                     ),
@@ -200,6 +440,58 @@ impl Dao {
         .map_err(Self::map_db_error)
     }
 
+    /// The final invoice issued for the given (paid) proforma, if any.
+    pub async fn find_invoice_of_proforma(&self, proforma_id: u32) -> DaoResult<Option<Invoice>> {
+        use schema::invoices::dsl as table;
+
+        self.with_connection(|conn| {
+            table::invoices
+                .filter(table::proforma_id.eq(proforma_id as i32))
+                .first(conn)
+                .optional()
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// Number of the invoices paid to the account - chosen for them, or the default one in their currency.
+    pub async fn count_invoices_of_bank_account(&self, account: &BankAccount) -> DaoResult<i64> {
+        use schema::invoices::dsl as table;
+
+        self.with_connection(|conn| {
+            let mut query = table::invoices
+                .select(count(table::id))
+                .filter(table::bank_account_id.eq(account.id))
+                .into_boxed();
+
+            if account.is_default {
+                query = query.or_filter(
+                    table::bank_account_id
+                        .is_null()
+                        .and(table::entrepreneur_id.eq(account.entrepreneur_id))
+                        .and(table::currency_code.eq(&account.currency_code)),
+                );
+            }
+
+            query.first::<i64>(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// Credit notes issued for the given invoice.
+    pub async fn find_credit_notes(&self, invoice_id: u32) -> DaoResult<Vec<Invoice>> {
+        use schema::invoices::dsl as table;
+
+        self.with_connection(|conn| {
+            table::invoices
+                .filter(table::original_invoice_id.eq(invoice_id as i32))
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
     pub async fn get_invoice_with_rows(&self, id: u32) -> DaoResult<Option<(Invoice, Vec<InvoiceRow>)>> {
         use schema::*;
 
@@ -237,6 +529,14 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
+    pub async fn get_recurrence(&self, id: u32) -> DaoResult<Option<InvoiceRecurrence>> {
+        use schema::invoice_recurrences::dsl as table;
+
+        self.with_connection(|conn| table::invoice_recurrences.filter(table::id.eq(id as i32)).first(conn).optional())
+            .await
+            .map_err(Self::map_db_error)
+    }
+
     pub async fn get_contact(&self, id: u32) -> DaoResult<Option<Contact>> {
         use schema::contacts::dsl as table;
 
@@ -245,6 +545,14 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
+    pub async fn get_bank_account(&self, id: u32) -> DaoResult<Option<BankAccount>> {
+        use schema::bank_accounts::dsl as table;
+
+        self.with_connection(|conn| table::bank_accounts.filter(table::id.eq(id as i32)).first(conn).optional())
+            .await
+            .map_err(Self::map_db_error)
+    }
+
     pub async fn get_session(&self, id: &str) -> DaoResult<Option<LoginSession>> {
         use schema::login_sessions::dsl as table;
 
@@ -263,6 +571,19 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
+    pub async fn get_bank_accounts(&self, entrepreneur_id: u32) -> DaoResult<Vec<BankAccount>> {
+        use schema::bank_accounts::dsl as table;
+
+        self.with_connection(|conn| {
+            table::bank_accounts
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .order((table::currency_code, table::id))
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
     pub async fn get_contacts(&self, entrepreneur_id: u32, limit: Option<u16>, last_months: Option<u8>) -> DaoResult<Vec<Contact>> {
         // Here I'm not patient enough to convince Diesel to construct the right query :-( Sorryfor that.
 
@@ -283,7 +604,7 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
-    pub async fn get_invoices(&self, entrepreneur_id: u32, limit: Option<u16>) -> DaoResult<Vec<InvoiceWithAllInfo>> {
+    pub async fn get_invoices(&self, entrepreneur_id: u32, limit: Option<u16>) -> DaoResult<Vec<InvoiceWithContactName>> {
         use schema::*;
 
         self.with_connection(|conn| {
@@ -291,7 +612,6 @@ impl Dao {
                 .select((
                     invoices::all_columns,
                     // This is not exactly nice and type-safe piece of code. However, I'm unable to convince Diesel to create it by his own - I just don't know how.
-                    diesel::dsl::sql::<diesel::sql_types::Double>("ifnull((select sum(invoice_rows.item_price * invoice_rows.item_count) from invoice_rows where invoice_rows.invoice_id=invoices.id), 0)"),
                     diesel::dsl::sql::<diesel::sql_types::VarChar>("(select contacts.name from contacts where contacts.id=invoices.contact_id)"),
                 ))
                 .filter(invoices::entrepreneur_id.eq(entrepreneur_id as i32))
@@ -315,6 +635,91 @@ impl Dao {
             .map_err(Self::map_db_error)
     }
 
+    /// Rows of all the given invoices, by invoice ID (invoices without rows are missing).
+    pub async fn get_rows_of_invoices(&self, invoice_ids: &[i32]) -> DaoResult<HashMap<i32, Vec<InvoiceRow>>> {
+        use schema::invoice_rows::dsl as table;
+
+        let rows: Vec<InvoiceRow> = self
+            .with_connection(|conn| table::invoice_rows.filter(table::invoice_id.eq_any(invoice_ids)).load(conn))
+            .await
+            .map_err(Self::map_db_error)?;
+
+        let mut by_invoice: HashMap<i32, Vec<InvoiceRow>> = HashMap::new();
+
+        rows.into_iter()
+            .for_each(|row| by_invoice.entry(row.invoice_id).or_default().push(row));
+
+        Ok(by_invoice)
+    }
+
+    /// Invoices created in the given year, together with all credit notes of them (even those created later).
+    pub async fn get_yearly_invoices(&self, entrepreneur_id: u32, year: u16) -> DaoResult<Vec<Invoice>> {
+        use schema::invoices::dsl as table;
+
+        self.with_connection(|conn| {
+            table::invoices
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .filter(sql(&format!(
+                    "YEAR(ifnull((select originals.created from invoices originals where originals.id = invoices.original_invoice_id), invoices.created)) = {}",
+                    year
+                )))
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// Documents of the entrepreneur created in the given period (both days included), with their contacts.
+    pub async fn get_invoices_with_contacts(
+        &self,
+        entrepreneur_id: u32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> DaoResult<Vec<(Invoice, Contact)>> {
+        use schema::*;
+
+        self.with_connection(|conn| {
+            invoices::table
+                .inner_join(contacts::table)
+                .filter(invoices::entrepreneur_id.eq(entrepreneur_id as i32))
+                .filter(invoices::created.between(from, to))
+                .order((invoices::created, invoices::code))
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// Recurrences of invoices of the entrepreneur, with the invoices they're copying.
+    pub async fn get_recurrences(&self, entrepreneur_id: u32) -> DaoResult<Vec<(InvoiceRecurrence, Invoice)>> {
+        use schema::*;
+
+        self.with_connection(|conn| {
+            invoice_recurrences::table
+                .inner_join(invoices::table)
+                .filter(invoices::entrepreneur_id.eq(entrepreneur_id as i32))
+                .order(invoice_recurrences::next_run.asc())
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
+    /// Active recurrences which should have been run until the given date (incl.).
+    pub async fn get_due_recurrences(&self, date: NaiveDate) -> DaoResult<Vec<InvoiceRecurrence>> {
+        use schema::invoice_recurrences::dsl as table;
+
+        self.with_connection(|conn| {
+            table::invoice_recurrences
+                .filter(table::paused.eq(false))
+                .filter(table::next_run.le(date))
+                .filter(table::end_date.is_null().or(table::end_date.ge(table::next_run.nullable())))
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
+    }
+
     // *** INSERT:
 
     pub async fn insert_entrepreneur(&self, code: &str, name: &str, addr: &str) -> DaoResult<Entrepreneur> {
@@ -336,7 +741,17 @@ impl Dao {
             .expect("Must find newly inserted entrepreneur!"))
     }
 
-    pub async fn insert_contact(&self, ent_id: u32, code: &Option<String>, name: &str, addr: &str, vat: &Vat) -> DaoResult<Contact> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert_contact(
+        &self,
+        ent_id: u32,
+        code: &Option<String>,
+        name: &str,
+        addr: &str,
+        vat: &Vat,
+        language: Language,
+        secondary_language: Option<Language>,
+    ) -> DaoResult<Contact> {
         let id = self
             .with_connection(|conn| {
                 use schema::contacts::dsl as table;
@@ -349,6 +764,8 @@ impl Dao {
                         table::name.eq(name),
                         table::address.eq(addr),
                         table::vat.eq(vat),
+                        table::language.eq(language),
+                        table::secondary_language.eq(secondary_language),
                     ))
                     .execute(conn)
                     .map_err(Self::map_db_error)
@@ -359,70 +776,199 @@ impl Dao {
         Ok(self.get_contact(id as u32).await?.expect("Must find newly inserted contact!"))
     }
 
-    pub async fn insert_invoice(
+    /// Inserts the `invoice` (but its id and code) with the next number of its kind in the numbering period starting at
+    /// `period_start` (never ending if none), together with its `rows`; its code is made of the number. The
+    /// `number_of` (a code of a document created on a date, none if not in the period) lets a new counter continue the
+    /// documents numbered before it. The `recurrence` creating the invoice (if any) is updated too. It's all one
+    /// transaction, so the number is never given out twice, nor skipped when the insert fails, the invoice never misses
+    /// any of the rows and a recurrence never creates it twice.
+    pub async fn insert_invoice<F, N>(
         &self,
-        code: &str,
-        entrepreneur_id: u32,
-        contact_id: u32,
-        due_length: &Duration,
-    ) -> DaoResult<InvoiceWithAllInfo> {
+        period_start: Option<NaiveDate>,
+        code: F,
+        number_of: N,
+        invoice: &Invoice,
+        rows: &[NewInvoiceRow],
+        recurrence: Option<&InvoiceRecurrence>,
+    ) -> DaoResult<(Invoice, Vec<InvoiceRow>)>
+    where
+        F: FnOnce(u32) -> String,
+        N: Fn(&str, NaiveDate) -> Option<u32>,
+    {
         let id = self
             .with_connection(|conn| {
-                use schema::invoices::dsl as table;
-
-                let now: DateTime = select(diesel::dsl::now).get_result::<DateTime>(conn)?;
-
-                let pay_until = now + *due_length;
-                let pay_until = pay_until.date();
+                conn.transaction::<_, AnyError, _>(|| {
+                    use schema::invoices::dsl as table;
+
+                    let period = period_start.map(|d| d.to_string()).unwrap_or_default();
+                    let number = Self::next_number(conn, invoice.entrepreneur_id, invoice.kind, &period, period_start, number_of)?;
+                    let code = code(number as u32);
+
+                    let new_invoice = NewInvoice {
+                        entrepreneur_id: invoice.entrepreneur_id,
+                        contact_id: invoice.contact_id,
+                        code: &code,
+                        kind: invoice.kind,
+                        original_invoice_id: invoice.original_invoice_id,
+                        proforma_id: invoice.proforma_id,
+                        created: invoice.created,
+                        pay_until: invoice.pay_until,
+                        payed: invoice.payed,
+                        discount_percent: invoice.discount_percent,
+                        discount_amount: invoice.discount_amount,
+                        language: invoice.language,
+                        currency_code: &invoice.currency_code,
+                        exchange_rate: invoice.exchange_rate,
+                        bank_account_id: invoice.bank_account_id,
+                    };
+
+                    debug!("Inserting new invoice: {:?}", new_invoice);
+
+                    let id = insert_into(table::invoices)
+                        .values(new_invoice)
+                        .execute(conn)
+                        .map_err(Self::map_db_error)
+                        .and_then(|r| Self::get_new_id(conn, r))?;
+
+                    insert_into(schema::invoice_numbers::table)
+                        .values(InvoiceNumber {
+                            invoice_id: id,
+                            entrepreneur_id: invoice.entrepreneur_id,
+                            kind: invoice.kind,
+                            period,
+                            number,
+                        })
+                        .execute(conn)
+                        .map_err(Self::map_db_error)?;
+
+                    for row in rows {
+                        insert_into(schema::invoice_rows::table)
+                            .values(NewInvoiceRow { invoice_id: id, ..row.clone() })
+                            .execute(conn)
+                            .map_err(Self::map_db_error)?;
+                    }
+
+                    if let Some(recurrence) = recurrence {
+                        update(schema::invoice_recurrences::table)
+                            .set(recurrence)
+                            .filter(schema::invoice_recurrences::id.eq(recurrence.id))
+                            .execute(conn)
+                            .map_err(Self::map_db_error)?;
+                    }
+
+                    Ok(id)
+                })
+            })
+            .await?; // it's already mapped to DB error
 
-                let invoice = NewInvoice {
-                    entrepreneur_id: entrepreneur_id as i32,
-                    contact_id: contact_id as i32,
-                    code,
-                    created: None,
-                    pay_until,
-                    payed: None,
-                };
+        Ok(self
+            .get_invoice_with_rows(id as u32)
+            .await?
+            .expect("Must find newly inserted invoice!"))
+    }
 
-                debug!("Inserting new invoice: {:?}", invoice);
+    pub async fn insert_invoice_row(&self, row: &NewInvoiceRow) -> DaoResult<InvoiceRow> {
+        let id = self
+            .with_connection(|conn| {
+                use schema::invoice_rows::dsl as table;
 
-                insert_into(table::invoices)
-                    .values(invoice)
+                insert_into(table::invoice_rows)
+                    .values(row)
                     .execute(conn)
                     .map_err(Self::map_db_error)
                     .and_then(|r| Self::get_new_id(conn, r))
             })
             .await?; // it's already mapped to DB error
 
-        Ok(self.get_invoice(id as u32).await?.expect("Must find newly inserted invoice!"))
+        Ok(self
+            .get_invoice_row(id as u32)
+            .await?
+            .expect("Must find newly inserted invoice row!"))
     }
 
-    pub async fn insert_invoice_row(&self, invoice_id: u32, name: &str, price: f32, count: u16) -> DaoResult<InvoiceRow> {
+    pub async fn insert_recurrence(&self, recurrence: &NewInvoiceRecurrence) -> DaoResult<InvoiceRecurrence> {
         let id = self
             .with_connection(|conn| {
-                use schema::invoice_rows::dsl as table;
+                use schema::invoice_recurrences::dsl as table;
 
-                insert_into(table::invoice_rows)
-                    .values((
-                        table::invoice_id.eq(invoice_id as i32),
-                        table::item_name.eq(name),
-                        table::item_price.eq(price),
-                        table::item_count.eq(count as i16),
-                    ))
+                insert_into(table::invoice_recurrences)
+                    .values(recurrence)
                     .execute(conn)
                     .map_err(Self::map_db_error)
                     .and_then(|r| Self::get_new_id(conn, r))
             })
             .await?; // it's already mapped to DB error
 
+        Ok(self.get_recurrence(id as u32).await?.expect("Must find newly inserted recurrence!"))
+    }
+
+    /// A new default account replaces the default one of its currency.
+    pub async fn insert_bank_account(&self, account: &NewBankAccount<'_>) -> DaoResult<BankAccount> {
+        let id = self
+            .with_connection(|conn| {
+                conn.transaction::<_, AnyError, _>(|| {
+                    use schema::bank_accounts::dsl as table;
+
+                    if account.is_default {
+                        Self::clear_default_account(conn, account.entrepreneur_id, account.currency_code, None)?;
+                    }
+
+                    insert_into(table::bank_accounts)
+                        .values(account)
+                        .execute(conn)
+                        .map_err(Self::map_db_error)
+                        .and_then(|r| Self::get_new_id(conn, r))
+                })
+            })
+            .await?; // it's already mapped to DB error
+
         Ok(self
-            .get_invoice_row(id as u32)
+            .get_bank_account(id as u32)
             .await?
-            .expect("Must find newly inserted invoice row!"))
+            .expect("Must find newly inserted bank account!"))
+    }
+
+    /// Inserts the new rates, the ones known already (of the same currencies and dates) are kept as they are. Returns
+    /// the number of the inserted rates.
+    pub async fn insert_exchange_rates(&self, rates: &[ExchangeRate]) -> DaoResult<usize> {
+        self.with_connection(|conn| {
+            conn.transaction::<_, AnyError, _>(|| {
+                use schema::exchange_rates::dsl as table;
+
+                let mut count = 0;
+
+                // yearly files have thousands of rates, too many for one statement
+                for chunk in rates.chunks(1000) {
+                    count += insert_or_ignore_into(table::exchange_rates)
+                        .values(chunk)
+                        .execute(conn)
+                        .map_err(Self::map_db_error)?;
+                }
+
+                Ok(count)
+            })
+        })
+        .await
     }
 
     // *** UPDATE:
 
+    /// Sets the layout template of the account's PDFs, `None` restores the default one.
+    pub async fn update_pdf_layout(&self, account_id: u32, layout: Option<&str>) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            use schema::accounts::dsl as table;
+
+            update(table::accounts)
+                .set(table::pdf_layout.eq(layout))
+                .filter(table::id.eq(account_id as i32))
+                .execute(conn)
+                .map_err(Self::map_db_error)
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
     pub async fn update_entrepreneur(&self, ent: &Entrepreneur) -> DaoResult<()> {
         self.with_connection(|conn| {
             use schema::entrepreneurs::dsl as table;
@@ -438,6 +984,43 @@ impl Dao {
         Ok(())
     }
 
+    /// Sets the logo of the entrepreneur, replacing the one it had.
+    pub async fn update_entrepreneur_logo(&self, logo: &EntrepreneurLogo) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            use schema::entrepreneur_logos::dsl as table;
+
+            replace_into(table::entrepreneur_logos)
+                .values(logo)
+                .execute(conn)
+                .map_err(Self::map_db_error)
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
+    /// The account becoming the default one replaces the default one of its currency.
+    pub async fn update_bank_account(&self, account: &BankAccount) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            conn.transaction::<_, AnyError, _>(|| {
+                use schema::bank_accounts::dsl as table;
+
+                if account.is_default {
+                    Self::clear_default_account(conn, account.entrepreneur_id, &account.currency_code, Some(account.id))?;
+                }
+
+                update(table::bank_accounts)
+                    .set(account)
+                    .filter(table::id.eq(account.id))
+                    .execute(conn)
+                    .map_err(Self::map_db_error)
+            })
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
     pub async fn update_contact(&self, contact: &Contact) -> DaoResult<()> {
         self.with_connection(|conn| {
             use schema::contacts::dsl as table;
@@ -448,6 +1031,8 @@ impl Dao {
                     table::name.eq(&contact.name),
                     table::address.eq(&contact.address),
                     table::vat.eq(&contact.vat),
+                    table::language.eq(contact.language),
+                    table::secondary_language.eq(contact.secondary_language),
                 ))
                 .filter(table::id.eq(contact.id))
                 .execute(conn)
@@ -488,6 +1073,21 @@ impl Dao {
         Ok(())
     }
 
+    pub async fn update_recurrence(&self, recurrence: &InvoiceRecurrence) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            use schema::invoice_recurrences::dsl as table;
+
+            update(table::invoice_recurrences)
+                .set(recurrence)
+                .filter(table::id.eq(recurrence.id))
+                .execute(conn)
+                .map_err(Self::map_db_error)
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
     // *** DELETE:
 
     pub async fn delete_entrepreneur(&self, id: u32) -> DaoResult<()> {
@@ -504,6 +1104,35 @@ impl Dao {
         Ok(())
     }
 
+    pub async fn delete_entrepreneur_logo(&self, entrepreneur_id: u32) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            use schema::entrepreneur_logos::dsl as table;
+
+            delete(table::entrepreneur_logos)
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .execute(conn)
+                .map_err(Self::map_db_error)
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
+    /// The invoices paid to the account are paid to the default one of their currency then.
+    pub async fn delete_bank_account(&self, id: u32) -> DaoResult<()> {
+        self.with_connection(|conn| {
+            use schema::bank_accounts::dsl as table;
+
+            delete(table::bank_accounts)
+                .filter(table::id.eq(id as i32))
+                .execute(conn)
+                .map_err(Self::map_db_error)
+        })
+        .await?; // it's already mapped to DB error
+
+        Ok(())
+    }
+
     pub async fn delete_contact(&self, id: u32) -> DaoResult<()> {
         self.with_connection(|conn| {
             use schema::contacts::dsl as table;
@@ -548,41 +1177,31 @@ impl Dao {
 
     // *** OTHERS:
 
-    pub async fn get_invoices_count(&self, entrepreneur: &Entrepreneur) -> DaoResult<u64> {
+    /// Counters of all numbering periods and kinds of documents of the entrepreneur.
+    pub async fn get_invoice_counters(&self, entrepreneur_id: u32) -> DaoResult<Vec<InvoiceCounter>> {
         self.with_connection(|conn| {
-            use schema::invoices::dsl as table;
+            use schema::invoice_counters::dsl as table;
 
-            table::invoices
-                .select(count(table::id))
-                .filter(table::entrepreneur_id.eq(entrepreneur.id))
-                .filter(sql("YEAR(invoices.created) = YEAR(NOW())"))
-                .first::<i64>(conn)
-                .map(|c| c as u64)
-                .map_err(Self::map_db_error)
+            table::invoice_counters
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .load(conn)
         })
         .await
+        .map_err(Self::map_db_error)
     }
 
-    pub async fn get_yearly_stats(&self, entrepreneur_id: u32, year: u16) -> DaoResult<(Vec<MonthlyMoney>, Vec<MonthlyMoney>)> {
-        let paid = self.with_connection(|conn| {
-            let query = format!(
-                "SELECT sum(item_price * item_count) as money, month(invoices.created) as month FROM `invoice_rows` join invoices on invoices.id = invoice_rows.invoice_id where invoices.entrepreneur_id = {} and invoices.created > '{}-01-01' and invoices.payed is not null group by month(invoices.created)",
-                entrepreneur_id, year
-            );
-
-            sql_query(query).load::<MonthlyMoney>(conn).map_err(Self::map_db_error)
-        }).await?;
-
-        let unpaid = self.with_connection(|conn| {
-            let query = format!(
-                "SELECT sum(item_price * item_count) as money, month(invoices.created) as month FROM `invoice_rows` join invoices on invoices.id = invoice_rows.invoice_id where invoices.entrepreneur_id = {} and invoices.created > '{}-01-01' and invoices.payed is null group by month(invoices.created)",
-                entrepreneur_id, year
-            );
-
-            sql_query(query).load::<MonthlyMoney>(conn).map_err(Self::map_db_error)
-        }).await?;
+    /// Numbers of existing documents of the entrepreneur.
+    pub async fn get_invoice_numbers(&self, entrepreneur_id: u32) -> DaoResult<Vec<InvoiceNumber>> {
+        self.with_connection(|conn| {
+            use schema::invoice_numbers::dsl as table;
 
-        Ok((paid, unpaid))
+            table::invoice_numbers
+                .filter(table::entrepreneur_id.eq(entrepreneur_id as i32))
+                .order(table::number.asc())
+                .load(conn)
+        })
+        .await
+        .map_err(Self::map_db_error)
     }
 
     // *** HELPER METHODS:
@@ -603,6 +1222,113 @@ impl Dao {
         })
     }
 
+    /// Takes the next number of the counter, locking it till the end of the transaction. A new counter starts at the
+    /// highest number of the documents created in its period before (by `number_of` their codes), so it never repeats
+    /// a number, even if some of the documents were deleted.
+    fn next_number<N>(
+        conn: &MysqlConnection,
+        entrepreneur_id: i32,
+        kind: InvoiceKind,
+        period: &str,
+        period_start: Option<NaiveDate>,
+        number_of: N,
+    ) -> Result<i32, AnyError>
+    where
+        N: Fn(&str, NaiveDate) -> Option<u32>,
+    {
+        use schema::invoice_counters::dsl as table;
+
+        let counter = table::invoice_counters
+            .filter(table::entrepreneur_id.eq(entrepreneur_id))
+            .filter(table::kind.eq(kind))
+            .filter(table::period.eq(period));
+
+        let current = counter
+            .select(table::value)
+            .for_update()
+            .first::<i32>(conn)
+            .optional()
+            .map_err(Self::map_db_error)?;
+
+        let current = match current {
+            Some(value) => value,
+            None => {
+                let existing = Self::highest_number(conn, entrepreneur_id, kind, period_start, number_of)?;
+
+                // a concurrent transaction may have created it meanwhile
+                insert_or_ignore_into(table::invoice_counters)
+                    .values(InvoiceCounter {
+                        entrepreneur_id,
+                        kind,
+                        period: period.to_string(),
+                        initial_value: existing,
+                        value: existing,
+                    })
+                    .execute(conn)
+                    .map_err(Self::map_db_error)?;
+
+                counter
+                    .select(table::value)
+                    .for_update()
+                    .first::<i32>(conn)
+                    .map_err(Self::map_db_error)?
+            }
+        };
+
+        update(counter)
+            .set(table::value.eq(current + 1))
+            .execute(conn)
+            .map_err(Self::map_db_error)?;
+
+        Ok(current + 1)
+    }
+
+    fn highest_number<N>(
+        conn: &MysqlConnection,
+        entrepreneur_id: i32,
+        kind: InvoiceKind,
+        since: Option<NaiveDate>,
+        number_of: N,
+    ) -> Result<i32, AnyError>
+    where
+        N: Fn(&str, NaiveDate) -> Option<u32>,
+    {
+        use schema::invoices::dsl as table;
+
+        let mut query = table::invoices
+            .select((table::code, table::created))
+            .filter(table::entrepreneur_id.eq(entrepreneur_id))
+            .filter(table::kind.eq(kind))
+            .into_boxed();
+
+        if let Some(since) = since {
+            query = query.filter(table::created.ge(since));
+        }
+
+        let codes = query.load::<(String, NaiveDate)>(conn).map_err(Self::map_db_error)?;
+
+        Ok(codes
+            .iter()
+            .filter_map(|(code, created)| number_of(code, *created))
+            .max()
+            .map_or(0, |number| number as i32))
+    }
+
+    /// Unsets the default account of the entrepreneur in the currency, but the `kept` one.
+    fn clear_default_account(conn: &MysqlConnection, entrepreneur_id: i32, currency_code: &str, kept: Option<i32>) -> DaoResult<()> {
+        use schema::bank_accounts::dsl as table;
+
+        update(table::bank_accounts)
+            .set(table::is_default.eq(false))
+            .filter(table::entrepreneur_id.eq(entrepreneur_id))
+            .filter(table::currency_code.eq(currency_code))
+            .filter(table::id.ne(kept.unwrap_or_default()))
+            .execute(conn)
+            .map_err(Self::map_db_error)?;
+
+        Ok(())
+    }
+
     fn get_new_id(conn: &MysqlConnection, r: usize) -> Result<i32, AnyError> {
         if r == 1 {
             Self::last_inserted_id(conn)
@@ -1,8 +1,7 @@
 use chrono::NaiveDate as Date;
-use diesel::sql_types::{Double, Integer};
 use frunk::{Generic, LabelledGeneric};
 
-use crate::dao::Vat;
+use crate::dao::{InvoiceKind, Language, LogoFormat, Money, Percent, Quantity, Rate, RecurrencePeriod, Vat};
 
 use super::schema::*;
 
@@ -14,6 +13,8 @@ pub struct Account {
     pub salt: String,
     pub password: String,
     pub settings: String,
+    /// Layout template of the PDFs (JSON), the default one when none.
+    pub pdf_layout: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
@@ -35,13 +36,45 @@ pub struct Entrepreneur {
     pub name: String,
     pub address: String,
     pub vat: Vat,
-    pub account_number_country_code: String,
-    pub account_number_prefix: Option<i16>,
-    pub account_number: i64,
-    pub account_bank_code: i16,
     pub email: Option<String>,
     pub phone: Option<String>,
     pub currency_code: String,
+    /// Colour of the title of the invoices (`#rrggbb`).
+    pub accent_color: Option<String>,
+}
+
+/// Logo of the entrepreneur in the header of the invoices.
+#[derive(Queryable, Insertable, PartialEq, Debug, Clone)]
+#[table_name = "entrepreneur_logos"]
+pub struct EntrepreneurLogo {
+    pub entrepreneur_id: i32,
+    pub format: LogoFormat,
+    pub data: Vec<u8>,
+}
+
+/// Bank account of the entrepreneur, the default ones (one per currency) are used by the invoices in their currency.
+#[derive(Identifiable, Queryable, Associations, AsChangeset, LabelledGeneric, PartialEq, Debug, Clone)]
+#[belongs_to(Entrepreneur)]
+#[table_name = "bank_accounts"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct BankAccount {
+    pub id: i32,
+    pub entrepreneur_id: i32,
+    pub currency_code: String,
+    /// IBAN in the electronic format.
+    pub iban: String,
+    pub bic: Option<String>,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "bank_accounts"]
+pub struct NewBankAccount<'a> {
+    pub entrepreneur_id: i32,
+    pub currency_code: &'a str,
+    pub iban: &'a str,
+    pub bic: Option<&'a str>,
+    pub is_default: bool,
 }
 
 #[derive(Debug, Insertable)]
@@ -51,15 +84,21 @@ pub struct NewEntrepreneur<'a> {
     pub name: &'a str,
     pub address: &'a str,
     pub vat: Vat,
-    pub account_number_country_code: &'a str,
-    pub account_number_prefix: Option<i16>,
-    pub account_number: i64,
-    pub account_bank_code: i16,
     pub email: Option<String>,
     pub phone: Option<String>,
     pub currency_code: String,
 }
 
+/// Exchange rate of the Czech National Bank - price of one unit of the currency in CZK, valid from the date (until the
+/// next one).
+#[derive(Queryable, Insertable, LabelledGeneric, PartialEq, Debug, Clone)]
+#[table_name = "exchange_rates"]
+pub struct ExchangeRate {
+    pub currency_code: String,
+    pub valid_from: Date,
+    pub rate: Rate,
+}
+
 #[derive(Identifiable, Queryable, QueryableByName, Associations, AsChangeset, LabelledGeneric, PartialEq, Debug, Clone)]
 #[belongs_to(Entrepreneur)]
 #[table_name = "contacts"]
@@ -71,6 +110,9 @@ pub struct Contact {
     pub name: String,
     pub address: String,
     pub vat: Vat,
+    pub language: Language,
+    /// Language of the bilingual documents, next to the `language`.
+    pub secondary_language: Option<Language>,
 }
 
 #[derive(Debug, Insertable)]
@@ -92,9 +134,21 @@ pub struct Invoice {
     pub entrepreneur_id: i32,
     pub contact_id: i32,
     pub code: String,
+    pub kind: InvoiceKind,
+    pub original_invoice_id: Option<i32>,
+    pub proforma_id: Option<i32>,
     pub created: Date,
     pub pay_until: Date,
     pub payed: Option<Date>,
+    pub discount_percent: Option<Percent>,
+    pub discount_amount: Option<Money>,
+    /// Overrides the language of the contact.
+    pub language: Option<Language>,
+    pub currency_code: String,
+    /// Price of one unit of the `currency_code` in the currency of the entrepreneur.
+    pub exchange_rate: Rate,
+    /// The account the invoice is paid to, none for the default one in its currency.
+    pub bank_account_id: Option<i32>,
 }
 
 #[derive(Debug, Insertable)]
@@ -103,9 +157,18 @@ pub struct NewInvoice<'a> {
     pub entrepreneur_id: i32,
     pub contact_id: i32,
     pub code: &'a str,
-    pub created: Option<Date>,
+    pub kind: InvoiceKind,
+    pub original_invoice_id: Option<i32>,
+    pub proforma_id: Option<i32>,
+    pub created: Date,
     pub pay_until: Date,
     pub payed: Option<Date>,
+    pub discount_percent: Option<Percent>,
+    pub discount_amount: Option<Money>,
+    pub language: Option<Language>,
+    pub currency_code: &'a str,
+    pub exchange_rate: Rate,
+    pub bank_account_id: Option<i32>,
 }
 
 #[derive(Identifiable, Queryable, QueryableByName, Associations, AsChangeset, LabelledGeneric, PartialEq, Debug, Clone)]
@@ -116,17 +179,71 @@ pub struct InvoiceRow {
     pub id: i32,
     pub invoice_id: i32,
     pub item_name: String,
-    pub item_price: f32,
-    pub item_count: i16,
+    pub item_price: Money,
+    pub item_count: Quantity,
+    pub item_unit: Option<String>,
+    pub vat_rate: Option<i16>,
+    pub discount_percent: Option<Percent>,
+    pub discount_amount: Option<Money>,
 }
 
-#[derive(Debug, Insertable)]
+#[derive(Debug, Clone, Insertable)]
 #[table_name = "invoice_rows"]
 pub struct NewInvoiceRow {
     pub invoice_id: i32,
     pub item_name: String,
-    pub item_price: f32,
-    pub item_count: i16,
+    pub item_price: Money,
+    pub item_count: Quantity,
+    pub item_unit: Option<String>,
+    pub vat_rate: Option<i16>,
+    pub discount_percent: Option<Percent>,
+    pub discount_amount: Option<Money>,
+}
+
+#[derive(Identifiable, Queryable, QueryableByName, Associations, AsChangeset, LabelledGeneric, PartialEq, Debug, Clone)]
+#[belongs_to(Invoice)]
+#[table_name = "invoice_recurrences"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct InvoiceRecurrence {
+    pub id: i32,
+    pub invoice_id: i32,
+    pub period: RecurrencePeriod,
+    pub day_of_month: i16,
+    pub end_date: Option<Date>,
+    pub next_run: Date,
+    pub paused: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "invoice_recurrences"]
+pub struct NewInvoiceRecurrence {
+    pub invoice_id: i32,
+    pub period: RecurrencePeriod,
+    pub day_of_month: i16,
+    pub end_date: Option<Date>,
+    pub next_run: Date,
+}
+
+/// Counter of numbers of documents of the kind, in a numbering period (keyed by its first day, empty if it never ends).
+/// Numbers up to `initial_value` were given out before the counter existed.
+#[derive(Queryable, Insertable, PartialEq, Debug, Clone)]
+#[table_name = "invoice_counters"]
+pub struct InvoiceCounter {
+    pub entrepreneur_id: i32,
+    pub kind: InvoiceKind,
+    pub period: String,
+    pub initial_value: i32,
+    pub value: i32,
+}
+
+#[derive(Queryable, Insertable, PartialEq, Debug, Clone)]
+#[table_name = "invoice_numbers"]
+pub struct InvoiceNumber {
+    pub invoice_id: i32,
+    pub entrepreneur_id: i32,
+    pub kind: InvoiceKind,
+    pub period: String,
+    pub number: i32,
 }
 
 #[derive(Identifiable, Queryable, QueryableByName, Associations, AsChangeset, LabelledGeneric, PartialEq, Debug, Clone)]
@@ -144,11 +261,3 @@ pub struct NewSession<'a> {
     pub id: &'a str,
     pub account_id: i32,
 }
-
-#[derive(Queryable, QueryableByName, PartialEq, Debug, Clone)]
-pub struct MonthlyMoney {
-    #[sql_type = "Double"]
-    pub money: f64,
-    #[sql_type = "Integer"]
-    pub month: i32,
-}
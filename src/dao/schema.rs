@@ -5,6 +5,18 @@ table! {
         salt -> Varchar,
         password -> Varchar,
         settings -> Text,
+        pdf_layout -> Nullable<Text>,
+    }
+}
+
+table! {
+    bank_accounts (id) {
+        id -> Integer,
+        entrepreneur_id -> Integer,
+        currency_code -> Varchar,
+        iban -> Varchar,
+        bic -> Nullable<Varchar>,
+        is_default -> Bool,
     }
 }
 
@@ -16,6 +28,8 @@ table! {
         name -> Varchar,
         address -> Varchar,
         vat -> Varchar,
+        language -> Varchar,
+        secondary_language -> Nullable<Varchar>,
     }
 }
 
@@ -27,13 +41,26 @@ table! {
         name -> Varchar,
         address -> Varchar,
         vat -> Varchar,
-        account_number_country_code -> Varchar,
-        account_number_prefix -> Nullable<Smallint>,
-        account_number -> Bigint,
-        account_bank_code -> Smallint,
         email -> Nullable<Varchar>,
         phone -> Nullable<Varchar>,
         currency_code -> Varchar,
+        accent_color -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    entrepreneur_logos (entrepreneur_id) {
+        entrepreneur_id -> Integer,
+        format -> Varchar,
+        data -> Blob,
+    }
+}
+
+table! {
+    exchange_rates (currency_code, valid_from) {
+        currency_code -> Varchar,
+        valid_from -> Date,
+        rate -> Numeric,
     }
 }
 
@@ -43,9 +70,18 @@ table! {
         entrepreneur_id -> Integer,
         contact_id -> Integer,
         code -> Varchar,
+        kind -> Varchar,
+        original_invoice_id -> Nullable<Integer>,
+        proforma_id -> Nullable<Integer>,
         created -> Date,
         pay_until -> Date,
         payed -> Nullable<Date>,
+        discount_percent -> Nullable<Numeric>,
+        discount_amount -> Nullable<Numeric>,
+        language -> Nullable<Varchar>,
+        currency_code -> Varchar,
+        exchange_rate -> Numeric,
+        bank_account_id -> Nullable<Integer>,
     }
 }
 
@@ -54,8 +90,44 @@ table! {
         id -> Integer,
         invoice_id -> Integer,
         item_name -> Varchar,
-        item_price -> Float,
-        item_count -> SmallInt,
+        item_price -> Numeric,
+        item_count -> Numeric,
+        item_unit -> Nullable<Varchar>,
+        vat_rate -> Nullable<SmallInt>,
+        discount_percent -> Nullable<Numeric>,
+        discount_amount -> Nullable<Numeric>,
+    }
+}
+
+table! {
+    invoice_counters (entrepreneur_id, kind, period) {
+        entrepreneur_id -> Integer,
+        kind -> Varchar,
+        period -> Varchar,
+        initial_value -> Integer,
+        value -> Integer,
+    }
+}
+
+table! {
+    invoice_numbers (invoice_id) {
+        invoice_id -> Integer,
+        entrepreneur_id -> Integer,
+        kind -> Varchar,
+        period -> Varchar,
+        number -> Integer,
+    }
+}
+
+table! {
+    invoice_recurrences (id) {
+        id -> Integer,
+        invoice_id -> Integer,
+        period -> Varchar,
+        day_of_month -> SmallInt,
+        end_date -> Nullable<Date>,
+        next_run -> Date,
+        paused -> Bool,
     }
 }
 
@@ -66,11 +138,29 @@ table! {
     }
 }
 
+joinable!(bank_accounts -> entrepreneurs (entrepreneur_id));
 joinable!(contacts -> entrepreneurs (entrepreneur_id));
+joinable!(entrepreneur_logos -> entrepreneurs (entrepreneur_id));
 joinable!(entrepreneurs -> accounts (account_id));
+joinable!(invoice_counters -> entrepreneurs (entrepreneur_id));
+joinable!(invoice_numbers -> invoices (invoice_id));
+joinable!(invoice_recurrences -> invoices (invoice_id));
 joinable!(invoice_rows -> invoices (invoice_id));
+joinable!(invoices -> bank_accounts (bank_account_id));
 joinable!(invoices -> contacts (contact_id));
 joinable!(invoices -> entrepreneurs (entrepreneur_id));
 joinable!(login_sessions -> accounts (account_id));
 
-allow_tables_to_appear_in_same_query!(accounts, contacts, entrepreneurs, invoices, invoice_rows,);
+allow_tables_to_appear_in_same_query!(
+    accounts,
+    bank_accounts,
+    contacts,
+    entrepreneurs,
+    entrepreneur_logos,
+    exchange_rates,
+    invoices,
+    invoice_counters,
+    invoice_numbers,
+    invoice_recurrences,
+    invoice_rows,
+);
@@ -0,0 +1,132 @@
+//! Values the tests are made of.
+
+use chrono::NaiveDate;
+
+use crate::dao::{Contact, Entrepreneur, Invoice, InvoiceKind, InvoiceRow, Language, Money, Percent, Quantity, Rate, Vat};
+use crate::logic::bank_account::BankAccount;
+use crate::logic::pdf::layout::Layout;
+use crate::logic::settings::AccountSettings;
+use crate::logic::{Deposit, InvoiceDocument};
+
+pub fn money(s: &str) -> Money {
+    s.parse().unwrap()
+}
+
+/// Document of the `kind` in CZK, issued on 2022-03-15 and due in two weeks, without any discount.
+pub fn invoice(id: i32, kind: InvoiceKind) -> Invoice {
+    Invoice {
+        id,
+        entrepreneur_id: 1,
+        contact_id: 1,
+        code: format!("2022-{:04}", id),
+        kind,
+        original_invoice_id: None,
+        proforma_id: None,
+        created: NaiveDate::from_ymd(2022, 3, 15),
+        pay_until: NaiveDate::from_ymd(2022, 3, 29),
+        payed: None,
+        discount_percent: None,
+        discount_amount: None,
+        language: None,
+        currency_code: "CZK".to_string(),
+        exchange_rate: Rate::from_int(1),
+        bank_account_id: None,
+    }
+}
+
+/// Row without a name, a unit and a discount.
+pub fn row(invoice_id: i32, price: &str, count: &str, vat_rate: Option<i16>) -> InvoiceRow {
+    InvoiceRow {
+        id: 0,
+        invoice_id,
+        item_name: String::new(),
+        item_price: money(price),
+        item_count: count.parse::<Quantity>().unwrap(),
+        item_unit: None,
+        vat_rate,
+        discount_percent: None,
+        discount_amount: None,
+    }
+}
+
+/// Invoice (or a credit note of it) with two rows at different VAT rates and a 10 % discount.
+pub fn document(kind: InvoiceKind) -> InvoiceDocument {
+    let row = |name: &str, price, count, rate| InvoiceRow {
+        item_name: name.to_string(),
+        item_unit: Some("ks".to_string()),
+        ..row(2, price, count, rate)
+    };
+
+    InvoiceDocument {
+        settings: AccountSettings::default(),
+        layout: Layout::default(),
+        entrepreneur: Entrepreneur {
+            id: 1,
+            account_id: 1,
+            code: "12345678".to_string(),
+            name: "Jan Novák & syn".to_string(),
+            address: "Hlavní 123\r\n436 01 Litvínov".to_string(),
+            vat: Vat::Code("CZ12345678".to_string()),
+            email: Some("jan@novak.cz".to_string()),
+            phone: None,
+            currency_code: "CZK".to_string(),
+            accent_color: None,
+        },
+        logo: None,
+        bank_account: Some(BankAccount {
+            iban: "CZ6508000000192000145399".parse().unwrap(),
+            bic: None,
+        }),
+        contact: Contact {
+            id: 1,
+            entrepreneur_id: 1,
+            code: Some("87654321".to_string()),
+            name: "Odběratel s.r.o.".to_string(),
+            address: "Dlouhá 1\r\n110 00 Praha".to_string(),
+            vat: Vat::NotTaxPayer,
+            language: Language::Cs,
+            secondary_language: None,
+        },
+        invoice: Invoice {
+            discount_percent: Some(Percent::from_int(10)),
+            ..invoice(2, kind)
+        },
+        original: match kind {
+            InvoiceKind::CreditNote => Some(Invoice {
+                discount_percent: Some(Percent::from_int(10)),
+                ..invoice(1, InvoiceKind::Invoice)
+            }),
+            _ => None,
+        },
+        deposit: None,
+        rows: vec![row("Práce", "1000", "2", Some(21)), row("Kniha", "300", "1", Some(12))],
+    }
+}
+
+/// Final invoice of a proforma paid in advance of 1000.
+pub fn final_invoice() -> InvoiceDocument {
+    let mut document = document(InvoiceKind::Invoice);
+    document.deposit = Some(Deposit {
+        proforma: Invoice {
+            id: 1,
+            code: "2022-0001".to_string(),
+            kind: InvoiceKind::Proforma,
+            ..document.invoice.clone()
+        },
+        amount: Money::from_int(1000),
+    });
+    document.invoice.proforma_id = Some(1);
+
+    document
+}
+
+/// Invoice in euros to a German customer, at the rate of 24.5 CZK.
+pub fn foreign_invoice() -> InvoiceDocument {
+    let mut document = document(InvoiceKind::Invoice);
+    document.contact.address = "Hauptstraße 5\r\n10115 Berlin".to_string();
+    document.contact.vat = Vat::Code("DE123456789".to_string());
+    document.invoice.currency_code = "EUR".to_string();
+    document.invoice.exchange_rate = "24.5".parse().unwrap();
+
+    document
+}
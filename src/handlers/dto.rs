@@ -4,8 +4,8 @@ use chrono::NaiveDate as Date;
 use frunk::*;
 use serde::{Deserialize, Serialize};
 
-use crate::dao::MonthlyMoney;
-use crate::dao::Vat;
+use crate::dao::{InvoiceKind, Language, Money, Percent, Quantity, Rate, RecurrencePeriod, Vat};
+use crate::logic::stats::MonthlyMoney;
 
 #[derive(Serialize, Deserialize, LabelledGeneric, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -25,6 +25,10 @@ pub struct Contact {
     pub name: String,
     pub address: String,
     pub vat: Vat,
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_language: Option<Language>,
 }
 
 #[derive(Serialize, Deserialize, LabelledGeneric, Debug, Clone)]
@@ -36,16 +40,26 @@ pub struct Entrepreneur {
     pub name: String,
     pub address: String,
     pub vat: Vat,
-    pub account_number_country_code: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub account_number_prefix: Option<i16>,
-    pub account_number: i64,
-    pub account_bank_code: i16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub phone: Option<String>,
     pub currency_code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, LabelledGeneric, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BankAccount {
+    pub id: i32,
+    pub entrepreneur_id: i32,
+    pub currency_code: String,
+    pub iban: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bic: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
 }
 
 #[derive(Serialize, Deserialize, LabelledGeneric, Generic, Debug, Clone)]
@@ -55,10 +69,26 @@ pub struct Invoice {
     pub entrepreneur_id: i32,
     pub contact_id: i32,
     pub code: String,
+    #[serde(default)]
+    pub kind: InvoiceKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_invoice_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proforma_id: Option<i32>,
     pub created: Date,
     pub pay_until: Date,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub payed: Option<Date>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_percent: Option<Percent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_amount: Option<Money>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    pub currency_code: String,
+    pub exchange_rate: Rate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bank_account_id: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, LabelledGeneric, Generic, Debug, Clone)]
@@ -68,11 +98,28 @@ pub struct InvoiceWithAllInfo {
     pub entrepreneur_id: i32,
     pub contact_id: i32,
     pub code: String,
+    #[serde(default)]
+    pub kind: InvoiceKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_invoice_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proforma_id: Option<i32>,
     pub created: Date,
     pub pay_until: Date,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub payed: Option<Date>,
-    pub price_sum: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_percent: Option<Percent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_amount: Option<Money>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    pub currency_code: String,
+    pub exchange_rate: Rate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bank_account_id: Option<i32>,
+    pub price_sum: Money,
+    pub price_sum_gross: Money,
     pub contact_name: String,
 }
 
@@ -82,8 +129,16 @@ pub struct InvoiceRow {
     pub id: i32,
     pub invoice_id: i32,
     pub item_name: String,
-    pub item_price: f32,
-    pub item_count: i16,
+    pub item_price: Money,
+    pub item_count: Quantity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vat_rate: Option<i16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_percent: Option<Percent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discount_amount: Option<Money>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -93,6 +148,43 @@ pub struct InvoiceWithRows {
     pub rows: Vec<InvoiceRow>,
 }
 
+#[derive(Serialize, Deserialize, LabelledGeneric, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceRecurrence {
+    pub id: i32,
+    pub invoice_id: i32,
+    pub period: RecurrencePeriod,
+    pub day_of_month: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<Date>,
+    pub next_run: Date,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpcomingRecurrence {
+    pub recurrence: InvoiceRecurrence,
+    pub invoice_code: String,
+    pub upcoming_runs: Vec<Date>,
+}
+
+#[derive(Serialize, LabelledGeneric, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NumberingGap {
+    pub kind: InvoiceKind,
+    pub period_start: Option<Date>,
+    pub missing: Vec<u32>,
+}
+
+#[derive(Serialize, LabelledGeneric, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeRate {
+    pub currency_code: String,
+    pub valid_from: Date,
+    pub rate: Rate,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginSession {
@@ -120,11 +212,26 @@ pub struct ContactsListParams {
     pub last_months: Option<u8>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrencesListParams {
+    pub runs: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportParams {
+    pub from: Date,
+    pub to: Date,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct MonthlyStat {
-    pub paid: f64,
-    pub unpaid: f64,
+    pub paid: Money,
+    pub unpaid: Money,
+    pub paid_gross: Money,
+    pub unpaid_gross: Money,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -141,6 +248,10 @@ pub struct NewContact {
     pub name: String,
     pub address: String,
     pub vat: Vat,
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default)]
+    pub secondary_language: Option<Language>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -151,12 +262,41 @@ pub struct NewEntrepreneur {
     pub address: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NewBankAccount {
+    pub entrepreneur_id: u32,
+    pub currency_code: String,
+    /// Either the IBAN or the domestic account to derive it from.
+    #[serde(default)]
+    pub iban: Option<String>,
+    #[serde(default)]
+    pub domestic_account: Option<DomesticAccount>,
+    #[serde(default)]
+    pub bic: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// Czech or Slovak account number.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DomesticAccount {
+    pub country_code: String,
+    #[serde(default)]
+    pub prefix: Option<u32>,
+    pub number: u64,
+    pub bank_code: u16,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct NewInvoice {
     pub account_id: u32,
     pub entrepreneur_id: u32,
     pub contact_id: u32,
+    #[serde(default)]
+    pub kind: InvoiceKind,
 }
 
 #[derive(Deserialize, Debug)]
@@ -164,8 +304,26 @@ pub struct NewInvoice {
 pub struct NewInvoiceRow {
     pub invoice_id: u32,
     pub item_name: String,
-    pub item_price: f32,
-    pub item_count: u16,
+    pub item_price: Money,
+    pub item_count: Quantity,
+    #[serde(default)]
+    pub item_unit: Option<String>,
+    #[serde(default)]
+    pub vat_rate: Option<u16>,
+    #[serde(default)]
+    pub discount_percent: Option<Percent>,
+    #[serde(default)]
+    pub discount_amount: Option<Money>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NewInvoiceRecurrence {
+    pub invoice_id: u32,
+    pub period: RecurrencePeriod,
+    pub day_of_month: u8,
+    #[serde(default)]
+    pub end_date: Option<Date>,
 }
 
 // ******
@@ -184,17 +342,23 @@ impl From<crate::dao::Contact> for Contact {
     }
 }
 
+impl From<crate::dao::BankAccount> for BankAccount {
+    fn from(a: crate::dao::BankAccount) -> Self {
+        frunk::labelled_convert_from(a)
+    }
+}
+
 impl From<crate::dao::Invoice> for Invoice {
     fn from(i: crate::dao::Invoice) -> Self {
         frunk::labelled_convert_from(i)
     }
 }
 
-impl From<crate::dao::InvoiceWithAllInfo> for InvoiceWithAllInfo {
-    fn from(i: crate::dao::InvoiceWithAllInfo) -> Self {
-        let (invoice, price_sum, contact_name) = i;
+impl From<crate::logic::InvoiceWithAllInfo> for InvoiceWithAllInfo {
+    fn from(i: crate::logic::InvoiceWithAllInfo) -> Self {
+        let (invoice, price_sum, price_sum_gross, contact_name) = i;
         let inv_repr = frunk::into_generic(invoice);
-        let inv_repr = inv_repr + hlist![price_sum, contact_name];
+        let inv_repr = inv_repr + hlist![price_sum, price_sum_gross, contact_name];
         frunk::from_generic(inv_repr)
     }
 }
@@ -205,6 +369,24 @@ impl From<crate::dao::InvoiceRow> for InvoiceRow {
     }
 }
 
+impl From<crate::dao::InvoiceRecurrence> for InvoiceRecurrence {
+    fn from(r: crate::dao::InvoiceRecurrence) -> Self {
+        frunk::labelled_convert_from(r)
+    }
+}
+
+impl From<crate::logic::invoices::NumberingGap> for NumberingGap {
+    fn from(g: crate::logic::invoices::NumberingGap) -> Self {
+        frunk::labelled_convert_from(g)
+    }
+}
+
+impl From<crate::dao::ExchangeRate> for ExchangeRate {
+    fn from(r: crate::dao::ExchangeRate) -> Self {
+        frunk::labelled_convert_from(r)
+    }
+}
+
 impl From<crate::dao::LoginSession> for LoginSession {
     fn from(s: crate::dao::LoginSession) -> Self {
         LoginSession {
@@ -226,6 +408,12 @@ impl From<Contact> for crate::dao::Contact {
     }
 }
 
+impl From<BankAccount> for crate::dao::BankAccount {
+    fn from(a: BankAccount) -> Self {
+        frunk::labelled_convert_from(a)
+    }
+}
+
 impl From<Invoice> for crate::dao::Invoice {
     fn from(i: Invoice) -> Self {
         frunk::labelled_convert_from(i)
@@ -238,6 +426,21 @@ impl From<InvoiceRow> for crate::dao::InvoiceRow {
     }
 }
 
+impl From<NewInvoiceRow> for crate::dao::NewInvoiceRow {
+    fn from(r: NewInvoiceRow) -> Self {
+        crate::dao::NewInvoiceRow {
+            invoice_id: r.invoice_id as i32,
+            item_name: r.item_name,
+            item_price: r.item_price,
+            item_count: r.item_count,
+            item_unit: r.item_unit,
+            vat_rate: r.vat_rate.map(|r| r as i16),
+            discount_percent: r.discount_percent,
+            discount_amount: r.discount_amount,
+        }
+    }
+}
+
 impl From<(Vec<MonthlyMoney>, Vec<MonthlyMoney>)> for YearlyStats {
     fn from(d: (Vec<MonthlyMoney>, Vec<MonthlyMoney>)) -> Self {
         let (paid, unpaid) = d;
@@ -245,20 +448,28 @@ impl From<(Vec<MonthlyMoney>, Vec<MonthlyMoney>)> for YearlyStats {
         // paid.sort_by(|a, b| Ord::cmp(&a.month, &b.month));
         // unpaid.sort_by(|a, b| Ord::cmp(&a.month, &b.month));
 
-        let paid = paid.into_iter().map(|s| (s.month as u8, s.money)).collect::<HashMap<u8, f64>>();
-        let unpaid = unpaid.into_iter().map(|s| (s.month as u8, s.money)).collect::<HashMap<u8, f64>>();
+        let paid = paid
+            .into_iter()
+            .map(|s| (s.month, (s.money, s.money_gross)))
+            .collect::<HashMap<u8, (Money, Money)>>();
+        let unpaid = unpaid
+            .into_iter()
+            .map(|s| (s.month, (s.money, s.money_gross)))
+            .collect::<HashMap<u8, (Money, Money)>>();
 
         let mut stats = HashMap::new();
 
         for month in 1..=12u8 {
-            let paid_m = paid.get(&month).copied().unwrap_or(0f64);
-            let unpaid_m = unpaid.get(&month).copied().unwrap_or(0f64);
+            let (paid_m, paid_gross_m) = paid.get(&month).copied().unwrap_or((Money::ZERO, Money::ZERO));
+            let (unpaid_m, unpaid_gross_m) = unpaid.get(&month).copied().unwrap_or((Money::ZERO, Money::ZERO));
 
             stats.insert(
                 month,
                 MonthlyStat {
                     paid: paid_m,
                     unpaid: unpaid_m,
+                    paid_gross: paid_gross_m,
+                    unpaid_gross: unpaid_gross_m,
                 },
             );
         }
@@ -7,20 +7,33 @@ use std::pin::Pin;
 use actix_web::body::BodyStream;
 use actix_web::web::Data;
 use actix_web::{get, post, web, FromRequest, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
 use err_context::AnyError;
 use futures::future::{err, ok};
 use futures::FutureExt;
+use itertools::Itertools;
 use log::{debug, trace, warn};
 use serde::Deserialize;
 
-use crate::dao::DaoResult;
+use crate::dao::{self, DaoResult, EntrepreneurLogo, InvoiceKind};
 pub use crate::handlers::dto::LoginSession;
 use crate::handlers::dto::{
-    Contact, ContactsListParams, Entrepreneur, Invoice, InvoiceRow, InvoiceWithRows, InvoicesListParams, LoginSessionCreated, NewContact,
-    NewEntrepreneur, NewInvoice, NewInvoiceRow, YearlyStats,
+    BankAccount, Contact, ContactsListParams, Entrepreneur, ExportParams, Invoice, InvoiceRow, InvoiceWithRows, InvoicesListParams,
+    LoginSessionCreated, NewBankAccount, NewContact, NewEntrepreneur, NewInvoice, NewInvoiceRecurrence, NewInvoiceRow,
+    RecurrencesListParams, UpcomingRecurrence, YearlyStats,
 };
 use crate::logic;
 use crate::logic::auth::Auth;
+use crate::logic::currency;
+use crate::logic::discount::Discount;
+use crate::logic::exchange_rates;
+use crate::logic::iban::{self, Iban};
+use crate::logic::invoices;
+use crate::logic::pdf::layout::{Color, Layout};
+use crate::logic::pdf::logo;
+use crate::logic::recurrence;
+use crate::logic::stats;
+use crate::logic::vat;
 use crate::RequestContext;
 
 pub mod dto;
@@ -48,6 +61,338 @@ pub async fn download_invoice(id: web::Path<u32>, session: LoginSession, ctx: we
     }
 }
 
+#[post("/download-isdoc/{id}")]
+pub async fn download_isdoc(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    if !(session.is_valid_for_invoice(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    match logic::download_isdoc(&ctx.dao, *id).await {
+        Ok((invoice, xml)) => {
+            let hvalue = format!("attachment; filename=\"invoice_{}.isdoc\"", invoice.code);
+
+            HttpResponse::Ok()
+                .insert_header(("Content-Type", "application/xml"))
+                .insert_header(("Content-Disposition", hvalue))
+                .body(xml)
+        }
+        Err(err) => {
+            warn!("Error while downloading invoice ISDOC: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/download-isdocx/{id}")]
+pub async fn download_isdocx(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    if !(session.is_valid_for_invoice(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    match logic::download_isdocx(&ctx.dao, &ctx.pdf_manager, *id).await {
+        Ok((invoice, package)) => {
+            let hvalue = format!("attachment; filename=\"invoice_{}.isdocx\"", invoice.code);
+
+            HttpResponse::Ok()
+                .insert_header(("Content-Type", "application/zip"))
+                .insert_header(("Content-Disposition", hvalue))
+                .body(package)
+        }
+        Err(err) => {
+            warn!("Error while downloading invoice ISDOCX: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/download-ubl/{id}")]
+pub async fn download_ubl(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    if !(session.is_valid_for_invoice(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    match logic::download_ubl(&ctx.dao, *id).await {
+        Ok((invoice, xml)) => {
+            let hvalue = format!("attachment; filename=\"invoice_{}.xml\"", invoice.code);
+
+            HttpResponse::Ok()
+                .insert_header(("Content-Type", "application/xml"))
+                .insert_header(("Content-Disposition", hvalue))
+                .body(xml)
+        }
+        Err(err) => {
+            warn!("Error while downloading invoice UBL: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/export-pohoda/{entrepreneur_id}")]
+pub async fn export_pohoda(
+    entrepreneur_id: web::Path<u32>,
+    params: web::Json<ExportParams>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    if params.from > params.to {
+        return HttpResponse::BadRequest().body("Invalid period");
+    }
+
+    match logic::export_pohoda(&ctx.dao, *entrepreneur_id, params.from, params.to).await {
+        Ok(xml) => {
+            let hvalue = format!("attachment; filename=\"pohoda_{}_{}.xml\"", params.from, params.to);
+
+            HttpResponse::Ok()
+                .insert_header(("Content-Type", "application/xml"))
+                .insert_header(("Content-Disposition", hvalue))
+                .body(xml)
+        }
+        Err(err) => {
+            warn!("Error while exporting invoices to Pohoda: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/preview-pdf-layout/{id}")]
+pub async fn preview_pdf_layout(
+    id: web::Path<u32>,
+    layout: web::Json<Layout>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    if !(session.is_valid_for_invoice(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    if let Err(err) = ctx.pdf_manager.check_layout(&layout) {
+        return HttpResponse::BadRequest().body(err.to_string());
+    }
+
+    match logic::preview_pdf_layout(&ctx.dao, &ctx.pdf_manager, *id, layout.into_inner()).await {
+        Ok((invoice, stream)) => {
+            let hvalue = format!("inline; filename=\"invoice_{}.pdf\"", invoice.code);
+
+            HttpResponse::Ok()
+                .insert_header(("Content-Type", "application/pdf"))
+                .insert_header(("Content-Disposition", hvalue))
+                .body(BodyStream::new(stream))
+        }
+        Err(err) => {
+            warn!("Error while previewing invoice PDF: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[post("/data-get/pdf-layout")]
+pub async fn get_pdf_layout(session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Getting PDF layout for account ID {}", session.account_id);
+
+    // no access rights check
+
+    with_ok(logic::get_pdf_layout(&ctx.dao, session.account_id), |layout| async move {
+        HttpResponse::Ok().json(layout)
+    })
+    .await
+}
+
+#[post("/data-update/pdf-layout")]
+pub async fn update_pdf_layout(layout: web::Json<Layout>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Updating PDF layout for account ID {}", session.account_id);
+
+    // no access rights check
+
+    if let Err(err) = ctx.pdf_manager.check_layout(&layout) {
+        return HttpResponse::BadRequest().body(err.to_string());
+    }
+
+    let json = serde_json::to_string(&layout.into_inner()).expect("Layout must be serializable");
+
+    with_ok(ctx.dao.update_pdf_layout(session.account_id, Some(&json)), |_| async {
+        HttpResponse::Ok().body("{\"success\":true}")
+    })
+    .await
+}
+
+#[post("/data-delete/pdf-layout")]
+pub async fn delete_pdf_layout(session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Restoring the default PDF layout for account ID {}", session.account_id);
+
+    // no access rights check
+
+    with_ok(ctx.dao.update_pdf_layout(session.account_id, None), |_| async {
+        HttpResponse::Ok().body("{\"success\":true}")
+    })
+    .await
+}
+
+#[post("/data-get/entrepreneur-logo/{id}")]
+pub async fn get_entrepreneur_logo(
+    entrepreneur_id: web::Path<u32>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Getting logo of entrepreneur ID {}", *entrepreneur_id);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_entrepreneur_logo(*entrepreneur_id), |logo| async move {
+        HttpResponse::Ok()
+            .insert_header(("Content-Type", logo.format.mime_type()))
+            .body(logo.data)
+    })
+    .await
+}
+
+/// The body is the image itself - a PNG, JPEG or SVG.
+#[post("/data-update/entrepreneur-logo/{id}")]
+pub async fn update_entrepreneur_logo(
+    entrepreneur_id: web::Path<u32>,
+    data: web::Bytes,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Updating logo of entrepreneur ID {}, {} bytes", *entrepreneur_id, data.len());
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let format = match logo::detect_format(&data) {
+        Some(format) => format,
+        None => return HttpResponse::BadRequest().body("Invalid logo, expected a PNG, JPEG or SVG image"),
+    };
+    if let Err(err) = logo::decode(format, &data) {
+        return HttpResponse::BadRequest().body(format!("Invalid logo: {}", err));
+    }
+
+    let logo = EntrepreneurLogo {
+        entrepreneur_id: *entrepreneur_id as i32,
+        format,
+        data: data.to_vec(),
+    };
+
+    with_ok(ctx.dao.update_entrepreneur_logo(&logo), |_| async {
+        HttpResponse::Ok().body("{\"success\":true}")
+    })
+    .await
+}
+
+#[post("/data-delete/entrepreneur-logo/{id}")]
+pub async fn delete_entrepreneur_logo(
+    entrepreneur_id: web::Path<u32>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Deleting logo of entrepreneur ID {}", *entrepreneur_id);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_ok(ctx.dao.delete_entrepreneur_logo(*entrepreneur_id), |_| async {
+        HttpResponse::Ok().body("{\"success\":true}")
+    })
+    .await
+}
+
+/// The body is a file of the ČNB rates, a daily or a yearly one. The rates are shared by all the accounts, so only the
+/// configured ones may upload them, and only the new rates are imported - the known ones can't be overwritten.
+#[post("/import-exchange-rates")]
+pub async fn import_exchange_rates(data: web::Bytes, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!(
+        "Importing exchange rates for account ID {}, {} bytes",
+        session.account_id,
+        data.len()
+    );
+
+    if !ctx.exchange_rates_config.upload_accounts.contains(&session.account_id) {
+        debug!("Session {:?} is forbidden to upload exchange rates", session);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let rates = match std::str::from_utf8(&data).map_err(AnyError::from).and_then(exchange_rates::parse) {
+        Ok(rates) => rates,
+        Err(err) => return HttpResponse::BadRequest().body(format!("Invalid exchange rates: {}", err)),
+    };
+
+    match exchange_rates::find_conflicts(&ctx.dao, &rates).await {
+        Ok(conflicts) if conflicts.is_empty() => {}
+        Ok(conflicts) => {
+            let rates = conflicts.iter().map(|r| format!("{} {}", r.currency_code, r.valid_from)).join(", ");
+            return HttpResponse::Conflict().body(format!("Exchange rates known already with other values: {}", rates));
+        }
+        Err(e) => {
+            warn!("Error while querying DB: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    with_ok(ctx.dao.insert_exchange_rates(&rates), |count| async move {
+        HttpResponse::Ok().body(format!("{{\"success\":true,\"imported\":{}}}", count))
+    })
+    .await
+}
+
+#[post("/data-get/exchange-rate/{currency}/{date}")]
+pub async fn get_exchange_rate(
+    params: web::Path<(String, NaiveDate)>,
+    _session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    let (currency, date) = params.into_inner();
+
+    debug!("Getting exchange rate of {} for {}", currency, date);
+
+    // any account can read the rates, they're the same for all the accounts
+
+    with_found(ctx.dao.get_exchange_rate(&currency, date), |rate| async move {
+        HttpResponse::Ok().json(Into::<dto::ExchangeRate>::into(rate))
+    })
+    .await
+}
+
+/// Fills in the rate valid on the date of the invoice.
+#[post("/data-update/invoice-exchange-rate/{id}")]
+pub async fn fill_invoice_exchange_rate(
+    invoice_id: web::Path<u32>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Filling exchange rate of invoice ID {}", *invoice_id);
+
+    if !(session.is_valid_for_invoice(&ctx.dao, *invoice_id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *invoice_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_invoice(*invoice_id), |(invoice, _)| async {
+        with_ok(exchange_rates::fill_invoice_rate(&ctx.dao, invoice), |invoice| async {
+            match invoice {
+                Some(invoice) => HttpResponse::Ok().json(Into::<dto::Invoice>::into(invoice)),
+                None => HttpResponse::BadRequest().body("No exchange rate for the date of the invoice"),
+            }
+        })
+        .await
+    })
+    .await
+}
+
 // TODO security: https://docs.rs/csrf/0.4.0/csrf/
 
 #[derive(Deserialize)]
@@ -134,7 +479,7 @@ pub async fn get_invoice(id: web::Path<u32>, session: LoginSession, ctx: web::Da
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
-    with_found(ctx.dao.get_invoice(*id), |i| async {
+    with_found(logic::get_invoice(&ctx.dao, *id), |i| async {
         HttpResponse::Ok().json(Into::<dto::InvoiceWithAllInfo>::into(i))
     })
     .await
@@ -175,6 +520,21 @@ pub async fn get_contact(id: web::Path<u32>, session: LoginSession, ctx: web::Da
     .await
 }
 
+#[post("/data-get/bank-account/{id}")]
+pub async fn get_bank_account(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Getting bank account data, ID {}", *id);
+
+    if !(session.is_valid_for_bank_account(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access bank account id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_bank_account(*id), |a| async {
+        HttpResponse::Ok().json(Into::<dto::BankAccount>::into(a))
+    })
+    .await
+}
+
 #[post("/data-get/entrepreneurs")]
 pub async fn list_entrepreneurs(session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
     debug!("Getting entrepreneurs list for account ID {}", session.account_id);
@@ -187,6 +547,21 @@ pub async fn list_entrepreneurs(session: LoginSession, ctx: web::Data<RequestCon
     .await
 }
 
+#[post("/data-get/bank-accounts/{id}")]
+pub async fn list_bank_accounts(entrepreneur_id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Getting bank accounts list for entrepreneur ID {:?}", entrepreneur_id);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_ok(ctx.dao.get_bank_accounts(*entrepreneur_id), |rows| async {
+        HttpResponse::Ok().json(rows.into_iter().map(|r| r.into()).collect::<Vec<dto::BankAccount>>())
+    })
+    .await
+}
+
 #[post("/data-get/contacts/{id}")]
 pub async fn list_contacts(
     entrepreneur_id: web::Path<u32>,
@@ -226,7 +601,7 @@ pub async fn list_invoices(
 
     let limit = params.and_then(|p| p.last);
 
-    with_ok(ctx.dao.get_invoices(*entrepreneur_id, limit), |rows| async {
+    with_ok(logic::list_invoices(&ctx.dao, *entrepreneur_id, limit), |rows| async {
         HttpResponse::Ok().json(rows.into_iter().map(|r| r.into()).collect::<Vec<dto::InvoiceWithAllInfo>>())
     })
     .await
@@ -258,12 +633,60 @@ pub async fn get_yearly_stats(params: web::Path<(u16, u32)>, session: LoginSessi
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
-    with_ok(ctx.dao.get_yearly_stats(entrepreneur_id, year), |data| async move {
+    with_ok(stats::get_yearly_stats(&ctx.dao, entrepreneur_id, year), |data| async move {
         HttpResponse::Ok().json(Into::<YearlyStats>::into(data))
     })
     .await
 }
 
+#[post("/data-get/numbering-gaps/{id}")]
+pub async fn get_numbering_gaps(entrepreneur_id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Getting numbering gaps for entrepreneur ID {}", *entrepreneur_id);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_ok(invoices::get_numbering_gaps(&ctx.dao, *entrepreneur_id), |gaps| async move {
+        let gaps = gaps.into_iter().map(Into::into).collect::<Vec<dto::NumberingGap>>();
+
+        HttpResponse::Ok().json(gaps)
+    })
+    .await
+}
+
+#[post("/data-get/recurrences/{id}")]
+pub async fn list_recurrences(
+    entrepreneur_id: web::Path<u32>,
+    session: LoginSession,
+    params: Option<web::Json<RecurrencesListParams>>,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Getting recurrences list for entrepreneur ID {:?}", entrepreneur_id);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, *entrepreneur_id).await) {
+        debug!("Session {:?} is forbidden to access entrepreneur id {}", session, *entrepreneur_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let runs = params.and_then(|p| p.runs).unwrap_or(3) as usize;
+
+    with_ok(ctx.dao.get_recurrences(*entrepreneur_id), |rows| async move {
+        let upcoming = rows
+            .into_iter()
+            .map(|(recurrence, invoice)| UpcomingRecurrence {
+                upcoming_runs: recurrence::upcoming_runs(&recurrence, runs),
+                recurrence: recurrence.into(),
+                invoice_code: invoice.code,
+            })
+            .collect::<Vec<_>>();
+
+        HttpResponse::Ok().json(upcoming)
+    })
+    .await
+}
+
 #[post("/data-insert/entrepreneur")]
 pub async fn insert_entrepreneur(
     entrepreneur: web::Json<NewEntrepreneur>,
@@ -300,12 +723,64 @@ pub async fn insert_contact(contact: web::Json<NewContact>, session: LoginSessio
             &contact.name,
             &contact.address,
             &contact.vat,
+            contact.language,
+            contact.secondary_language,
         ),
         |i| async { HttpResponse::Ok().json(Into::<dto::Contact>::into(i)) },
     )
     .await
 }
 
+#[post("/data-insert/bank-account")]
+pub async fn insert_bank_account(
+    account: web::Json<NewBankAccount>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Inserting new bank account: {:?}", account);
+
+    if !(session.is_valid_for_entrepreneur(&ctx.dao, account.entrepreneur_id).await) {
+        debug!(
+            "Session {:?} is forbidden to insert bank account for entrepreneur id {}",
+            session, account.entrepreneur_id
+        );
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let iban = match (&account.iban, &account.domestic_account) {
+        (Some(iban), _) => iban.to_owned(),
+        (None, Some(domestic)) => {
+            let number = iban::DomesticAccount {
+                prefix: domestic.prefix,
+                number: domestic.number,
+                bank_code: domestic.bank_code,
+            };
+            match Iban::from_domestic(&domestic.country_code, &number) {
+                Ok(iban) => iban.as_str().to_owned(),
+                Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+            }
+        }
+        (None, None) => return HttpResponse::BadRequest().body("Missing IBAN"),
+    };
+    let iban = match valid_iban(&account.currency_code, &iban, account.bic.as_deref()) {
+        Ok(iban) => iban,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let new_account = dao::NewBankAccount {
+        entrepreneur_id: account.entrepreneur_id as i32,
+        currency_code: &account.currency_code,
+        iban: iban.as_str(),
+        bic: account.bic.as_deref(),
+        is_default: account.is_default,
+    };
+
+    with_ok(ctx.dao.insert_bank_account(&new_account), |a| async {
+        HttpResponse::Ok().json(Into::<dto::BankAccount>::into(a))
+    })
+    .await
+}
+
 #[post("/data-insert/invoice")]
 pub async fn insert_invoice(invoice: web::Json<NewInvoice>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
     debug!("Inserting new invoice: {:?}", invoice);
@@ -320,6 +795,11 @@ pub async fn insert_invoice(invoice: web::Json<NewInvoice>, session: LoginSessio
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
+    if invoice.kind == InvoiceKind::CreditNote {
+        debug!("Credit note can't be inserted directly");
+        return HttpResponse::BadRequest().body("Credit note can be issued only for an invoice");
+    }
+
     with_ok(logic::insert_invoice(&ctx.dao, &invoice), |i| async {
         HttpResponse::Ok().json(Into::<dto::InvoiceWithAllInfo>::into(i))
     })
@@ -335,7 +815,7 @@ pub async fn copy_invoice(invoice_id: web::Path<u32>, session: LoginSession, ctx
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
-    with_found(ctx.dao.get_invoice(*invoice_id), |(original, _, _)| async {
+    with_found(ctx.dao.get_invoice(*invoice_id), |(original, _)| async {
         with_ok(logic::copy_invoice(&ctx.dao, original), |i| async {
             HttpResponse::Ok().json(Into::<dto::Invoice>::into(i))
         })
@@ -344,6 +824,55 @@ pub async fn copy_invoice(invoice_id: web::Path<u32>, session: LoginSession, ctx
     .await
 }
 
+#[post("/data-insert/credit-note/{id}")]
+pub async fn insert_credit_note(invoice_id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Inserting credit note for invoice: {:?}", invoice_id);
+
+    if !(session.is_valid_for_invoice(&ctx.dao, *invoice_id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *invoice_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_invoice(*invoice_id), |(original, _)| async {
+        if original.kind != InvoiceKind::Invoice {
+            debug!(
+                "Invoice id {} is a {:?}, can't issue a credit note for it",
+                original.id, original.kind
+            );
+            return HttpResponse::BadRequest().body("Credit note can be issued only for an invoice");
+        }
+
+        with_ok(logic::insert_credit_note(&ctx.dao, original), |i| async {
+            HttpResponse::Ok().json(Into::<dto::Invoice>::into(i))
+        })
+        .await
+    })
+    .await
+}
+
+#[post("/data-finalize/proforma/{id}")]
+pub async fn finalize_proforma(proforma_id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Finalizing proforma: {:?}", proforma_id);
+
+    if !(session.is_valid_for_invoice(&ctx.dao, *proforma_id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, *proforma_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_invoice(*proforma_id), |(proforma, _)| async {
+        if proforma.kind != InvoiceKind::Proforma || proforma.payed.is_none() {
+            debug!("Invoice id {} is not a paid proforma", proforma.id);
+            return HttpResponse::BadRequest().body("Only a paid proforma can be finalized");
+        }
+
+        with_ok(logic::finalize_proforma(&ctx.dao, proforma), |i| async {
+            HttpResponse::Ok().json(Into::<dto::Invoice>::into(i))
+        })
+        .await
+    })
+    .await
+}
+
 #[post("/data-insert/invoice-row")]
 pub async fn insert_invoice_row(row: web::Json<NewInvoiceRow>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
     debug!("Inserting new invoice row: {:?}", row);
@@ -353,11 +882,82 @@ pub async fn insert_invoice_row(row: web::Json<NewInvoiceRow>, session: LoginSes
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
-    with_ok(
-        ctx.dao
-            .insert_invoice_row(row.invoice_id, &row.item_name, row.item_price, row.item_count),
-        |i| async { HttpResponse::Ok().json(Into::<dto::InvoiceRow>::into(i)) },
-    )
+    if !vat::is_valid_rate(row.vat_rate) {
+        debug!("Invalid VAT rate {:?}", row.vat_rate);
+        return HttpResponse::BadRequest().body("Invalid VAT rate");
+    }
+
+    if !Discount::new(row.discount_percent, row.discount_amount).is_valid_for(row.item_price.times(row.item_count)) {
+        debug!("Invalid discount {:?} / {:?}", row.discount_percent, row.discount_amount);
+        return HttpResponse::BadRequest().body("Invalid discount");
+    }
+
+    with_ok(ctx.dao.insert_invoice_row(&row.into_inner().into()), |i| async {
+        HttpResponse::Ok().json(Into::<dto::InvoiceRow>::into(i))
+    })
+    .await
+}
+
+#[post("/data-insert/recurrence")]
+pub async fn insert_recurrence(
+    recurrence: web::Json<NewInvoiceRecurrence>,
+    session: LoginSession,
+    ctx: web::Data<RequestContext>,
+) -> impl Responder {
+    debug!("Inserting new recurrence: {:?}", recurrence);
+
+    if !(session.is_valid_for_invoice(&ctx.dao, recurrence.invoice_id).await) {
+        debug!("Session {:?} is forbidden to access invoice id {}", session, recurrence.invoice_id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    if !(1..=31).contains(&recurrence.day_of_month) {
+        debug!("Invalid day of month {}", recurrence.day_of_month);
+        return HttpResponse::BadRequest().body("Invalid day of month");
+    }
+
+    let dao = ctx.dao.clone();
+
+    with_found(ctx.dao.get_invoice(recurrence.invoice_id), |(invoice, _)| async move {
+        if invoice.kind == InvoiceKind::CreditNote {
+            debug!("Invoice id {} is a credit note, it can't recur", invoice.id);
+            return HttpResponse::BadRequest().body("Credit note can't recur");
+        }
+
+        let inserted = recurrence::insert_recurrence(&dao, &invoice, recurrence.period, recurrence.day_of_month, recurrence.end_date);
+
+        with_ok(inserted, |r| async {
+            HttpResponse::Ok().json(Into::<dto::InvoiceRecurrence>::into(r))
+        })
+        .await
+    })
+    .await
+}
+
+#[post("/data-update/recurrence-pause/{id}")]
+pub async fn pause_recurrence(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Pausing recurrence ID {}", *id);
+    set_recurrence_paused(*id, true, session, ctx).await
+}
+
+#[post("/data-update/recurrence-resume/{id}")]
+pub async fn resume_recurrence(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Resuming recurrence ID {}", *id);
+    set_recurrence_paused(*id, false, session, ctx).await
+}
+
+async fn set_recurrence_paused(id: u32, paused: bool, session: LoginSession, ctx: web::Data<RequestContext>) -> HttpResponse {
+    if !(session.is_valid_for_recurrence(&ctx.dao, id).await) {
+        debug!("Session {:?} is forbidden to access recurrence id {}", session, id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    with_found(ctx.dao.get_recurrence(id), |r| async {
+        with_ok(recurrence::set_paused(&ctx.dao, r, paused), |r| async {
+            HttpResponse::Ok().json(Into::<dto::InvoiceRecurrence>::into(r))
+        })
+        .await
+    })
     .await
 }
 
@@ -374,12 +974,70 @@ pub async fn update_entrepreneur(
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
+    if let Some(accent_color) = &entrepreneur.accent_color {
+        if let Err(err) = Color::try_from(accent_color.clone()) {
+            return HttpResponse::BadRequest().body(err);
+        }
+    }
+
     with_ok(ctx.dao.update_entrepreneur(&entrepreneur.into_inner().into()), |_| async {
         HttpResponse::Ok().body("{\"success\":true}")
     })
     .await
 }
 
+#[post("/data-update/bank-account")]
+pub async fn update_bank_account(account: web::Json<BankAccount>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Updating bank account: {:?}", account);
+
+    if !(session.is_valid_for_bank_account(&ctx.dao, account.id as u32).await
+        && session.is_valid_for_entrepreneur(&ctx.dao, account.entrepreneur_id as u32).await)
+    {
+        debug!("Session {:?} is forbidden to access bank account id {}", session, account.id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let mut account = account.into_inner();
+    account.iban = match valid_iban(&account.currency_code, &account.iban, account.bic.as_deref()) {
+        Ok(iban) => iban.as_str().to_owned(),
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let stored = match ctx.dao.get_bank_account(account.id as u32).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            warn!("Error while querying DB: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    if stored.entrepreneur_id != account.entrepreneur_id {
+        debug!("Bank account id {} can't move to entrepreneur id {}", account.id, account.entrepreneur_id);
+        return HttpResponse::BadRequest().body("Bank account can't move to another entrepreneur");
+    }
+
+    // the issued invoices mustn't change the account they're paid to
+    if stored.iban != account.iban || stored.currency_code != account.currency_code {
+        match ctx.dao.count_invoices_of_bank_account(&stored).await {
+            Ok(0) => {}
+            Ok(count) => {
+                debug!("Bank account id {} is used by {} invoices, can't change it", account.id, count);
+                return HttpResponse::Conflict().body(format!("Bank account is used by {} invoices", count));
+            }
+            Err(e) => {
+                warn!("Error while querying DB: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    with_ok(ctx.dao.update_bank_account(&account.into()), |_| async {
+        HttpResponse::Ok().body("{\"success\":true}")
+    })
+    .await
+}
+
 #[post("/data-update/contact")]
 pub async fn update_contact(contact: web::Json<Contact>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
     debug!("Updating contact: {:?}", contact);
@@ -404,7 +1062,28 @@ pub async fn update_invoice(invoice: web::Json<Invoice>, session: LoginSession,
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
-    with_ok(ctx.dao.update_invoice(&invoice.into_inner().into()), |_| async {
+    if !Discount::new(invoice.discount_percent, invoice.discount_amount).is_valid() {
+        debug!("Invalid discount {:?} / {:?}", invoice.discount_percent, invoice.discount_amount);
+        return HttpResponse::BadRequest().body("Invalid discount");
+    }
+
+    if !currency::is_valid_code(&invoice.currency_code) || !currency::is_valid_rate(invoice.exchange_rate) {
+        debug!("Invalid currency {} / rate {}", invoice.currency_code, invoice.exchange_rate);
+        return HttpResponse::BadRequest().body("Invalid currency");
+    }
+
+    if let Some(bank_account_id) = invoice.bank_account_id {
+        match ctx.dao.get_bank_account(bank_account_id as u32).await {
+            Ok(Some(account)) if account.entrepreneur_id == invoice.entrepreneur_id => {}
+            Ok(_) => return HttpResponse::BadRequest().body("Invalid bank account"),
+            Err(e) => {
+                warn!("Error while querying DB: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    with_ok(logic::update_invoice(&ctx.dao, invoice.into_inner().into()), |_| async {
         HttpResponse::Ok().body("{\"success\":true}")
     })
     .await
@@ -419,6 +1098,16 @@ pub async fn update_invoice_row(row: web::Json<InvoiceRow>, session: LoginSessio
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
+    if !vat::is_valid_rate(row.vat_rate.map(|r| r as u16)) {
+        debug!("Invalid VAT rate {:?}", row.vat_rate);
+        return HttpResponse::BadRequest().body("Invalid VAT rate");
+    }
+
+    if !Discount::new(row.discount_percent, row.discount_amount).is_valid_for(row.item_price.times(row.item_count)) {
+        debug!("Invalid discount {:?} / {:?}", row.discount_percent, row.discount_amount);
+        return HttpResponse::BadRequest().body("Invalid discount");
+    }
+
     with_ok(ctx.dao.update_invoice_row(&row.into_inner().into()), |_| async {
         HttpResponse::Ok().body("{\"success\":true}")
     })
@@ -440,6 +1129,39 @@ pub async fn delete_entrepreneur(id: web::Path<u32>, session: LoginSession, ctx:
     .await
 }
 
+#[post("/data-delete/bank-account/{id}")]
+pub async fn delete_bank_account(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
+    debug!("Deleting bank account ID {:?}", id);
+
+    if !(session.is_valid_for_bank_account(&ctx.dao, *id).await) {
+        debug!("Session {:?} is forbidden to access bank account id {}", session, *id);
+        return HttpResponse::Forbidden().body("Invalid resource");
+    }
+
+    let dao = &ctx.dao;
+
+    with_found(dao.get_bank_account(*id), |account| async move {
+        // the issued invoices mustn't change the account they're paid to
+        match dao.count_invoices_of_bank_account(&account).await {
+            Ok(0) => {}
+            Ok(count) => {
+                debug!("Bank account id {} is used by {} invoices, can't delete it", account.id, count);
+                return HttpResponse::Conflict().body(format!("Bank account is used by {} invoices", count));
+            }
+            Err(e) => {
+                warn!("Error while querying DB: {}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        }
+
+        with_ok(dao.delete_bank_account(account.id as u32), |_| async {
+            HttpResponse::Ok().body("{\"success\":true}")
+        })
+        .await
+    })
+    .await
+}
+
 #[post("/data-delete/contact/{id}")]
 pub async fn delete_contact(id: web::Path<u32>, session: LoginSession, ctx: web::Data<RequestContext>) -> impl Responder {
     debug!("Deleting contact ID {:?}", id);
@@ -464,6 +1186,20 @@ pub async fn delete_invoice(id: web::Path<u32>, session: LoginSession, ctx: web:
         return HttpResponse::Forbidden().body("Invalid resource");
     }
 
+    // the credit notes are tax documents on their own, they can't lose their invoice
+    match ctx.dao.find_credit_notes(*id).await {
+        Ok(credit_notes) if credit_notes.is_empty() => {}
+        Ok(credit_notes) => {
+            let codes = credit_notes.iter().map(|c| c.code.as_str()).join(", ");
+            debug!("Invoice id {} has credit notes {}, can't delete it", *id, codes);
+            return HttpResponse::Conflict().body(format!("Invoice has credit notes: {}", codes));
+        }
+        Err(e) => {
+            warn!("Error while querying DB: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
     with_ok(ctx.dao.delete_invoice(*id), |_| async {
         HttpResponse::Ok().body("{\"success\":true}")
     })
@@ -496,6 +1232,21 @@ pub async fn status() -> impl Responder {
     HttpResponse::Ok().body("{\"status\":\"ok\"}")
 }
 
+/// The parsed IBAN of a bank account, the reason why it's invalid otherwise.
+fn valid_iban(currency_code: &str, iban: &str, bic: Option<&str>) -> Result<Iban, String> {
+    if !currency::is_valid_code(currency_code) {
+        return Err("Invalid currency".to_owned());
+    }
+
+    if let Some(bic) = bic {
+        if !iban::is_valid_bic(bic) {
+            return Err("Invalid BIC".to_owned());
+        }
+    }
+
+    iban.parse().map_err(|e: AnyError| e.to_string())
+}
+
 async fn with_ok<A, F, Fu>(req: impl Future<Output = DaoResult<A>>, f: F) -> HttpResponse
 where
     Fu: Future<Output = HttpResponse>,
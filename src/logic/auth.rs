@@ -11,6 +11,8 @@ pub trait Auth {
     async fn is_valid_for_invoice_row(&self, dao: &Dao, row_id: u32) -> bool;
     async fn is_valid_for_entrepreneur(&self, dao: &Dao, entrepreneur_id: u32) -> bool;
     async fn is_valid_for_contact(&self, dao: &Dao, contact_id: u32) -> bool;
+    async fn is_valid_for_bank_account(&self, dao: &Dao, bank_account_id: u32) -> bool;
+    async fn is_valid_for_recurrence(&self, dao: &Dao, recurrence_id: u32) -> bool;
 }
 
 /// This struct exists because Diesel doesn't allow to return tuples from raw queries:
@@ -65,6 +67,29 @@ impl Auth for LoginSession {
 
         is_valid_for(dao, sql).await
     }
+
+    async fn is_valid_for_bank_account(&self, dao: &Dao, bank_account_id: u32) -> bool {
+        let sql = format!(
+            r#"SELECT entrepreneurs.account_id = {} as result FROM bank_accounts
+                join entrepreneurs on entrepreneurs.id=bank_accounts.entrepreneur_id
+                where bank_accounts.id={}"#,
+            self.account_id, bank_account_id
+        );
+
+        is_valid_for(dao, sql).await
+    }
+
+    async fn is_valid_for_recurrence(&self, dao: &Dao, recurrence_id: u32) -> bool {
+        let sql = format!(
+            r#"SELECT entrepreneurs.account_id = {} as result FROM invoice_recurrences
+                join invoices on invoices.id=invoice_recurrences.invoice_id
+                join entrepreneurs on entrepreneurs.id=invoices.entrepreneur_id
+                where invoice_recurrences.id={}"#,
+            self.account_id, recurrence_id
+        );
+
+        is_valid_for(dao, sql).await
+    }
 }
 
 async fn is_valid_for(dao: &Dao, sql: String) -> bool {
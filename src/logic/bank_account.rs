@@ -0,0 +1,109 @@
+use std::fmt;
+
+use log::warn;
+
+use crate::dao::{self, Invoice};
+use crate::logic::iban::{DomesticAccount, Iban};
+
+/// Account the invoice is paid to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BankAccount {
+    pub iban: Iban,
+    pub bic: Option<String>,
+}
+
+impl BankAccount {
+    /// None when the stored IBAN isn't valid (anymore).
+    pub fn of(account: &dao::BankAccount) -> Option<Self> {
+        match account.iban.parse() {
+            Ok(iban) => Some(BankAccount {
+                iban,
+                bic: account.bic.clone(),
+            }),
+            Err(e) => {
+                warn!("Invalid bank account {}: {}", account.id, e);
+                None
+            }
+        }
+    }
+
+    /// The Czech or Slovak account, none for the other countries.
+    pub fn domestic(&self) -> Option<DomesticAccount> {
+        self.iban.domestic()
+    }
+}
+
+/// The domestic account number where there's one, the IBAN otherwise.
+impl fmt::Display for BankAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.domestic() {
+            Some(account) => account.fmt(f),
+            None => self.iban.fmt(f),
+        }
+    }
+}
+
+/// Account of the invoice among the ones of its entrepreneur - the chosen one, or the default one in the currency of
+/// the invoice. Without such, the default one in the `home_currency` of the entrepreneur.
+pub fn select<'a>(accounts: &'a [dao::BankAccount], invoice: &Invoice, home_currency: &str) -> Option<&'a dao::BankAccount> {
+    let default_in = |currency: &str| accounts.iter().find(|a| a.is_default && a.currency_code == currency);
+
+    match invoice.bank_account_id {
+        Some(id) => accounts.iter().find(|a| a.id == id),
+        None => default_in(&invoice.currency_code).or_else(|| default_in(home_currency)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dao::InvoiceKind;
+    use crate::fixtures::document;
+
+    fn account(id: i32, currency_code: &str, iban: &str, is_default: bool) -> dao::BankAccount {
+        dao::BankAccount {
+            id,
+            entrepreneur_id: 1,
+            currency_code: currency_code.to_owned(),
+            iban: iban.to_owned(),
+            bic: None,
+            is_default,
+        }
+    }
+
+    #[test]
+    fn test_of() {
+        let czk = BankAccount::of(&account(1, "CZK", "CZ6508000000192000145399", true)).unwrap();
+        assert_eq!(czk.to_string(), "19-2000145399/0800");
+
+        let eur = BankAccount::of(&account(2, "EUR", "DE89370400440532013000", true)).unwrap();
+        assert_eq!(eur.domestic(), None);
+        assert_eq!(eur.to_string(), "DE89 3704 0044 0532 0130 00");
+
+        assert_eq!(BankAccount::of(&account(3, "EUR", "DE89370400440532013001", true)), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let accounts = [
+            account(1, "CZK", "CZ6508000000192000145399", false),
+            account(2, "CZK", "CZ6230300000001559929018", true),
+            account(3, "EUR", "DE89370400440532013000", true),
+        ];
+        let mut invoice = document(InvoiceKind::Invoice).invoice;
+
+        assert_eq!(select(&accounts, &invoice, "CZK").map(|a| a.id), Some(2));
+
+        invoice.currency_code = "EUR".to_owned();
+        assert_eq!(select(&accounts, &invoice, "CZK").map(|a| a.id), Some(3));
+
+        // no account in the currency
+        invoice.currency_code = "USD".to_owned();
+        assert_eq!(select(&accounts, &invoice, "CZK").map(|a| a.id), Some(2));
+
+        invoice.bank_account_id = Some(1);
+        assert_eq!(select(&accounts, &invoice, "CZK").map(|a| a.id), Some(1));
+
+        assert_eq!(select(&[], &invoice, "CZK"), None);
+    }
+}
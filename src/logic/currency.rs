@@ -0,0 +1,51 @@
+use crate::dao::{Invoice, Money, Rate};
+
+/// Currencies with a symbol of their own, the others are printed with their code.
+const SYMBOLS: [(&str, &str); 6] = [
+    ("CZK", "Kč"),
+    ("EUR", "€"),
+    ("USD", "$"),
+    ("GBP", "£"),
+    ("PLN", "zł"),
+    ("HUF", "Ft"),
+];
+
+/// Symbol of the currency printed after the amounts, its code when it has none.
+pub fn symbol(code: &str) -> &str {
+    SYMBOLS.iter().find(|(c, _)| *c == code).map_or(code, |(_, symbol)| symbol)
+}
+
+/// The ISO 4217 code - three capital letters.
+pub fn is_valid_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+pub fn is_valid_rate(rate: Rate) -> bool {
+    rate > Rate::ZERO
+}
+
+/// The amount of the invoice in the currency of its entrepreneur.
+pub fn to_home(invoice: &Invoice, amount: Money) -> Money {
+    amount.times(invoice.exchange_rate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_symbol() {
+        assert_eq!(symbol("CZK"), "Kč");
+        assert_eq!(symbol("EUR"), "€");
+        assert_eq!(symbol("CHF"), "CHF");
+    }
+
+    #[test]
+    fn test_validity() {
+        assert!(is_valid_code("USD"));
+        assert!(!is_valid_code("usd"));
+        assert!(!is_valid_code("US"));
+        assert!(is_valid_rate("0.056123".parse().unwrap()));
+        assert!(!is_valid_rate(Rate::ZERO));
+    }
+}
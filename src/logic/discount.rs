@@ -0,0 +1,138 @@
+use crate::dao::{Invoice, InvoiceRow, Money, Percent};
+
+/// Discount given either as a percentage of the price or as an absolute amount, never both.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Discount {
+    pub percent: Option<Percent>,
+    pub amount: Option<Money>,
+}
+
+impl Discount {
+    pub fn new(percent: Option<Percent>, amount: Option<Money>) -> Self {
+        Discount { percent, amount }
+    }
+
+    /// Discount of the row; an absolute amount is a discount for the whole row, not for a single item.
+    pub fn of_row(row: &InvoiceRow) -> Self {
+        Discount::new(row.discount_percent, row.discount_amount)
+    }
+
+    /// Discount of the whole invoice, applied to the sum of rows (after their own discounts).
+    pub fn of_invoice(invoice: &Invoice) -> Self {
+        Discount::new(invoice.discount_percent, invoice.discount_amount)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let percent_valid = self
+            .percent
+            .map(|p| !p.is_negative() && p <= Percent::from_int(100))
+            .unwrap_or(true);
+        let amount_valid = self.amount.map(|a| !a.is_negative()).unwrap_or(true);
+
+        percent_valid && amount_valid && !(self.percent.is_some() && self.amount.is_some())
+    }
+
+    /// Whether the discount is valid and its amount doesn't exceed the (absolute value of the) `price`.
+    pub fn is_valid_for(&self, price: Money) -> bool {
+        self.is_valid() && self.amount.is_none_or(|a| a <= price.abs())
+    }
+
+    /// How much is taken off the `price`, rounded to cents.
+    ///
+    /// A discount always lowers the absolute value of the price, so for a negative price (e.g. on a credit note) the
+    /// result is negative as well. An amount larger than the price takes off just the whole price (e.g. when rows of an
+    /// invoice with a discount are removed later).
+    pub fn of(&self, price: Money) -> Money {
+        let from_percent = self.percent.map(|p| price.percent(p)).unwrap_or(Money::ZERO);
+        let amount = self.amount.unwrap_or(Money::ZERO).min(price.abs());
+
+        from_percent + if price.is_negative() { -amount } else { amount }
+    }
+
+    /// Splits the discount of the sum of `prices` among them, proportionally to the prices.
+    ///
+    /// The rounding difference goes to the last price, so the shares always sum up to exactly the whole discount.
+    pub fn split(&self, prices: &[Money]) -> Vec<Money> {
+        let total: Money = prices.iter().sum();
+        let whole = self.of(total);
+
+        let mut shares = Vec::with_capacity(prices.len());
+        let mut remaining = whole;
+
+        for (i, price) in prices.iter().enumerate() {
+            let share = if i + 1 == prices.len() {
+                remaining
+            } else {
+                whole.share(*price, total).unwrap_or(Money::ZERO)
+            };
+
+            remaining -= share;
+            shares.push(share);
+        }
+
+        shares
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::money;
+
+    #[test]
+    fn test_validity() {
+        assert!(Discount::default().is_valid());
+        assert!(Discount::new(Some(money("12.5")), None).is_valid());
+        assert!(Discount::new(Some(money("100")), None).is_valid());
+        assert!(Discount::new(None, Some(money("500"))).is_valid());
+
+        assert!(!Discount::new(Some(money("100.01")), None).is_valid());
+        assert!(!Discount::new(Some(money("-1")), None).is_valid());
+        assert!(!Discount::new(None, Some(money("-1"))).is_valid());
+        assert!(!Discount::new(Some(money("10")), Some(money("10"))).is_valid());
+    }
+
+    #[test]
+    fn test_validity_for_price() {
+        assert!(Discount::new(None, Some(money("500"))).is_valid_for(money("500")));
+        assert!(Discount::new(None, Some(money("500"))).is_valid_for(money("-500")));
+        assert!(Discount::new(Some(money("100")), None).is_valid_for(Money::ZERO));
+
+        assert!(!Discount::new(None, Some(money("500.01"))).is_valid_for(money("500")));
+        assert!(!Discount::new(None, Some(money("0.01"))).is_valid_for(Money::ZERO));
+        assert!(!Discount::new(Some(money("101")), None).is_valid_for(money("500")));
+    }
+
+    #[test]
+    fn test_amount_over_price() {
+        assert_eq!(Discount::new(None, Some(money("150"))).of(money("100")), money("100"));
+        assert_eq!(Discount::new(None, Some(money("150"))).of(money("-100")), money("-100"));
+    }
+
+    #[test]
+    fn test_negative_price() {
+        assert_eq!(Discount::new(Some(money("10")), None).of(money("-200")), money("-20"));
+        assert_eq!(Discount::new(None, Some(money("15"))).of(money("-200")), money("-15"));
+    }
+
+    #[test]
+    fn test_split() {
+        let prices = [money("200"), money("100")];
+
+        assert_eq!(Discount::default().split(&prices), vec![Money::ZERO, Money::ZERO]);
+        assert_eq!(
+            Discount::new(Some(money("10")), None).split(&prices),
+            vec![money("20"), money("10")]
+        );
+        assert_eq!(
+            Discount::new(None, Some(money("100"))).split(&prices),
+            vec![money("66.67"), money("33.33")]
+        );
+        assert_eq!(
+            Discount::new(None, Some(money("0.01"))).split(&[money("1"), money("1"), money("1")]),
+            vec![Money::ZERO, Money::ZERO, money("0.01")]
+        );
+        assert_eq!(Discount::new(None, Some(money("5"))).split(&[Money::ZERO]), vec![Money::ZERO]);
+        assert!(Discount::new(None, Some(money("5"))).split(&[]).is_empty());
+    }
+}
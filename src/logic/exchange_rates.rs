@@ -0,0 +1,286 @@
+//! Exchange rates of the Czech National Bank (ČNB), imported from its text files - the daily ones (`denni_kurz.txt`):
+//!
+//! ```text
+//! 17.10.2024 #202
+//! země|měna|množství|kód|kurz
+//! Austrálie|dolar|1|AUD|15,565
+//! ```
+//!
+//! and the yearly ones (`rok.txt`), with a line per day (the header repeats when the currencies change):
+//!
+//! ```text
+//! Datum|1 AUD|1 BGN|100 JPY
+//! 02.01.2024|15,234|12,386|15,823
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use err_context::AnyError;
+use itertools::Itertools;
+use log::{debug, info, warn};
+
+use crate::config::ExchangeRatesConfig;
+use crate::dao::{Dao, DaoResult, ExchangeRate, Invoice, Rate};
+
+/// The currency the ČNB rates are in.
+pub const RATES_CURRENCY: &str = "CZK";
+
+const DATE_FORMAT: &str = "%d.%m.%Y";
+
+pub fn start_import(dao: Dao, config: ExchangeRatesConfig) {
+    let directory = match config.directory {
+        Some(directory) => directory,
+        None => return,
+    };
+    let period = config.interval.to_std().expect("Exchange rates import interval must be positive");
+
+    info!("Starting import of exchange rates from {}, running every {:?}", directory, period);
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(period);
+
+        loop {
+            interval.tick().await;
+
+            match import_directory(&dao, Path::new(&directory)).await {
+                Ok(count) => info!("Imported {} exchange rate(s) from {}", count, directory),
+                Err(e) => warn!("Error while importing exchange rates from {}: {}", directory, e),
+            }
+        }
+    });
+}
+
+/// Imports all the `.txt` files of the directory, returns the number of the imported rates.
+pub async fn import_directory(dao: &Dao, directory: &Path) -> Result<usize, AnyError> {
+    let mut count = 0;
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+            continue;
+        }
+
+        debug!("Importing exchange rates from {:?}", path);
+        let text = fs::read_to_string(&path).map_err(|e| format!("Could not read {:?}: {}", path, e))?;
+        count += import(dao, &text).await.map_err(|e| format!("Invalid file {:?}: {}", path, e))?;
+    }
+
+    Ok(count)
+}
+
+/// Imports the new rates of a daily or a yearly file, returns their number. The rates known already are kept.
+pub async fn import(dao: &Dao, text: &str) -> Result<usize, AnyError> {
+    dao.insert_exchange_rates(&parse(text)?).await
+}
+
+/// The rates known already with other values. The published rates never change, such a file is wrong.
+pub async fn find_conflicts(dao: &Dao, rates: &[ExchangeRate]) -> DaoResult<Vec<ExchangeRate>> {
+    let (from, to) = match rates.iter().map(|r| r.valid_from).minmax().into_option() {
+        Some(period) => period,
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(conflicts(rates, &dao.get_exchange_rates(from, to).await?))
+}
+
+fn conflicts(rates: &[ExchangeRate], known: &[ExchangeRate]) -> Vec<ExchangeRate> {
+    let known: HashMap<_, _> = known.iter().map(|r| ((r.currency_code.as_str(), r.valid_from), r.rate)).collect();
+
+    rates
+        .iter()
+        .filter(|r| matches!(known.get(&(r.currency_code.as_str(), r.valid_from)), Some(rate) if *rate != r.rate))
+        .cloned()
+        .collect()
+}
+
+pub fn parse(text: &str) -> Result<Vec<ExchangeRate>, AnyError> {
+    let first = text.lines().next().unwrap_or_default();
+
+    if first.starts_with("Datum|") {
+        parse_yearly(text)
+    } else {
+        parse_daily(text)
+    }
+}
+
+fn parse_daily(text: &str) -> Result<Vec<ExchangeRate>, AnyError> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+
+    let header = lines.next().ok_or("Empty file")?;
+    let date = header.split(" #").next().unwrap_or_default();
+    let valid_from = NaiveDate::parse_from_str(date.trim(), DATE_FORMAT).map_err(|e| format!("Invalid date {}: {}", date, e))?;
+
+    // the names of the columns
+    lines.next().ok_or("Missing header")?;
+
+    lines
+        .map(|line| match line.split('|').collect::<Vec<_>>()[..] {
+            [_, _, amount, code, rate] => {
+                let amount = amount.parse().map_err(|_| format!("Invalid amount {}", amount))?;
+                Ok(ExchangeRate {
+                    currency_code: code.to_owned(),
+                    valid_from,
+                    rate: unit_rate(rate, amount)?,
+                })
+            }
+            _ => Err(format!("Invalid line {}", line).into()),
+        })
+        .collect()
+}
+
+fn parse_yearly(text: &str) -> Result<Vec<ExchangeRate>, AnyError> {
+    // amounts and codes of the columns
+    let mut currencies: Vec<(i64, &str)> = Vec::new();
+    let mut rates = Vec::new();
+
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let mut columns = line.split('|');
+        let first = columns.next().unwrap_or_default();
+
+        if first == "Datum" {
+            currencies = columns
+                .map(|column| match column.split_once(' ') {
+                    Some((amount, code)) => Ok((amount.parse().map_err(|_| format!("Invalid amount {}", amount))?, code)),
+                    None => Err(AnyError::from(format!("Invalid currency {}", column))),
+                })
+                .collect::<Result<_, _>>()?;
+            continue;
+        }
+
+        let valid_from = NaiveDate::parse_from_str(first, DATE_FORMAT).map_err(|e| format!("Invalid date {}: {}", first, e))?;
+
+        for ((amount, code), rate) in currencies.iter().zip(columns) {
+            // the currencies not quoted that day
+            if rate.trim().is_empty() {
+                continue;
+            }
+
+            rates.push(ExchangeRate {
+                currency_code: (*code).to_owned(),
+                valid_from,
+                rate: unit_rate(rate, *amount)?,
+            });
+        }
+    }
+
+    Ok(rates)
+}
+
+/// The rate of the `amount` of units (e.g. `15,823` for 100 JPY) for one unit.
+fn unit_rate(rate: &str, amount: i64) -> Result<Rate, AnyError> {
+    let rate: Rate = rate.replace(',', ".").parse()?;
+
+    Rate::checked_from_int(amount)
+        .filter(|amount| *amount > Rate::ZERO)
+        .and_then(|amount| rate.share(Rate::from_int(1), amount))
+        .ok_or_else(|| format!("Invalid amount {}", amount).into())
+}
+
+/// Price of one unit of the `currency` in the `home` one on the date, crossed over the ČNB rates when neither of the
+/// currencies is CZK. `None` when there's no (non-zero) rate of one of them.
+pub async fn rate_on(dao: &Dao, currency: &str, home: &str, date: NaiveDate) -> DaoResult<Option<Rate>> {
+    if currency == home {
+        return Ok(Some(Rate::from_int(1)));
+    }
+
+    match (czk_rate(dao, currency, date).await?, czk_rate(dao, home, date).await?) {
+        (Some(rate), Some(home_rate)) => Ok(rate.share(Rate::from_int(1), home_rate)),
+        _ => Ok(None),
+    }
+}
+
+async fn czk_rate(dao: &Dao, currency: &str, date: NaiveDate) -> DaoResult<Option<Rate>> {
+    match currency {
+        RATES_CURRENCY => Ok(Some(Rate::from_int(1))),
+        _ => Ok(dao.get_exchange_rate(currency, date).await?.map(|r| r.rate)),
+    }
+}
+
+/// Sets the rate of the invoice to the one valid on its taxable supply date (the date of the invoice) - the last one
+/// known for an invoice dated ahead. `None` when there's no rate for the date.
+pub async fn fill_invoice_rate(dao: &Dao, mut invoice: Invoice) -> DaoResult<Option<Invoice>> {
+    let entrepreneur = dao
+        .get_entrepreneur(invoice.entrepreneur_id as u32)
+        .await?
+        .expect("This value must exist!");
+
+    match rate_on(dao, &invoice.currency_code, &entrepreneur.currency_code, invoice.created).await? {
+        Some(rate) => {
+            invoice.exchange_rate = rate;
+            dao.update_invoice(&invoice).await?;
+            Ok(Some(invoice))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rate(code: &str, date: (i32, u32, u32), rate: &str) -> ExchangeRate {
+        ExchangeRate {
+            currency_code: code.to_owned(),
+            valid_from: NaiveDate::from_ymd(date.0, date.1, date.2),
+            rate: rate.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_daily() {
+        let text = "17.10.2024 #202\n\
+                    země|měna|množství|kód|kurz\n\
+                    Austrálie|dolar|1|AUD|15,565\n\
+                    Japonsko|jen|100|JPY|15,623\n";
+
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![rate("AUD", (2024, 10, 17), "15.565"), rate("JPY", (2024, 10, 17), "0.15623")]
+        );
+    }
+
+    #[test]
+    fn test_parse_yearly() {
+        let text = "Datum|1 AUD|100 JPY\n\
+                    02.01.2024|15,234|15,823\n\
+                    03.01.2024|15,3|\n\
+                    Datum|1 EUR\n\
+                    04.01.2024|24,725\n";
+
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                rate("AUD", (2024, 1, 2), "15.234"),
+                rate("JPY", (2024, 1, 2), "0.15823"),
+                rate("AUD", (2024, 1, 3), "15.3"),
+                rate("EUR", (2024, 1, 4), "24.725")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let known = [rate("AUD", (2024, 1, 2), "15.234"), rate("JPY", (2024, 1, 2), "0.15823")];
+
+        // the same file again
+        assert_eq!(conflicts(&known, &known), vec![]);
+
+        let rates = [
+            rate("AUD", (2024, 1, 2), "15.3"),
+            rate("JPY", (2024, 1, 2), "0.15823"),
+            rate("AUD", (2024, 1, 3), "15.3"),
+        ];
+        assert_eq!(conflicts(&rates, &known), vec![rate("AUD", (2024, 1, 2), "15.3")]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("17.10.2024 #202\nzemě|měna|množství|kód|kurz\nAustrálie|dolar|1|AUD\n").is_err());
+        assert!(parse("Datum|1 AUD\n2.1.2024x|15,234\n").is_err());
+        assert!(parse("Datum|0 AUD\n02.01.2024|15,234\n").is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Factur-X (ZUGFeRD) - invoice in the UN/CEFACT Cross Industry Invoice syntax, profile EN 16931
+//! (<https://fnfe-mpe.org/factur-x/>). It's embedded into the PDF of the invoice.
+
+use chrono::NaiveDate;
+
+use crate::dao::{Contact, Entrepreneur, InvoiceKind, Money, Vat};
+use crate::logic::bank_account::BankAccount;
+use crate::logic::export::xml::XmlWriter;
+use crate::logic::export::{catalog, country_code, exemption_reason, tax_category, type_code, unit_code, PostalAddress};
+use crate::logic::invoices::variable_symbol;
+use crate::logic::vat::{self, VatRecap};
+use crate::logic::InvoiceDocument;
+
+const RSM_NAMESPACE: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+const RAM_NAMESPACE: &str = "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+const QDT_NAMESPACE: &str = "urn:un:unece:uncefact:data:standard:QualifiedDataType:100";
+const UDT_NAMESPACE: &str = "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100";
+const GUIDELINE: &str = "urn:cen.eu:en16931:2017";
+
+/// Name of the attachment, required by the standard.
+pub const FILENAME: &str = "factur-x.xml";
+/// Profile of the document, as written to the XMP metadata of the PDF.
+pub const CONFORMANCE_LEVEL: &str = "EN 16931";
+
+pub fn create(document: &InvoiceDocument) -> String {
+    let InvoiceDocument {
+        entrepreneur,
+        bank_account,
+        contact,
+        invoice,
+        original,
+        deposit,
+        rows,
+        ..
+    } = document;
+
+    let vat_recap = VatRecap::new(invoice, rows);
+    let currency = invoice.currency_code.as_str();
+    let texts = catalog(document);
+
+    // rows of credit notes are negative, while the credit note itself states positive amounts
+    let sign = |amount: Money| match invoice.kind {
+        InvoiceKind::CreditNote => -amount,
+        _ => amount,
+    };
+    let category = |rate| tax_category(&entrepreneur.vat, rate);
+
+    let mut w = XmlWriter::default();
+
+    w.start(
+        "rsm:CrossIndustryInvoice",
+        &[
+            ("xmlns:rsm", RSM_NAMESPACE),
+            ("xmlns:ram", RAM_NAMESPACE),
+            ("xmlns:qdt", QDT_NAMESPACE),
+            ("xmlns:udt", UDT_NAMESPACE),
+        ],
+    );
+
+    w.start("rsm:ExchangedDocumentContext", &[]);
+    w.start("ram:GuidelineSpecifiedDocumentContextParameter", &[]);
+    w.text("ram:ID", GUIDELINE);
+    w.end();
+    w.end();
+
+    w.start("rsm:ExchangedDocument", &[]);
+    w.text("ram:ID", &invoice.code);
+    w.text("ram:TypeCode", type_code(invoice.kind));
+    w.start("ram:IssueDateTime", &[]);
+    date(&mut w, "udt:DateTimeString", invoice.created);
+    w.end();
+    w.end();
+
+    w.start("rsm:SupplyChainTradeTransaction", &[]);
+
+    for (i, row) in rows.iter().enumerate() {
+        let rate = row.vat_rate.map(|r| r as u16);
+        let discount = vat::row_discount(row);
+
+        w.start("ram:IncludedSupplyChainTradeLineItem", &[]);
+        w.start("ram:AssociatedDocumentLineDocument", &[]);
+        w.text("ram:LineID", i + 1);
+        w.end();
+        w.start("ram:SpecifiedTradeProduct", &[]);
+        w.text("ram:Name", &row.item_name);
+        w.end();
+        w.start("ram:SpecifiedLineTradeAgreement", &[]);
+        w.start("ram:NetPriceProductTradePrice", &[]);
+        w.text("ram:ChargeAmount", sign(row.item_price));
+        w.end();
+        w.end();
+        w.start("ram:SpecifiedLineTradeDelivery", &[]);
+        w.text_with(
+            "ram:BilledQuantity",
+            &[("unitCode", unit_code(row.item_unit.as_deref()))],
+            row.item_count,
+        );
+        w.end();
+        w.start("ram:SpecifiedLineTradeSettlement", &[]);
+        trade_tax(&mut w, category(rate), rate);
+        if discount != Money::ZERO {
+            w.start("ram:SpecifiedTradeAllowanceCharge", &[]);
+            w.start("ram:ChargeIndicator", &[]);
+            w.text("udt:Indicator", false);
+            w.end();
+            w.text("ram:ActualAmount", sign(discount));
+            w.text("ram:Reason", "Sleva"); // TODO hard code value
+            w.end();
+        }
+        w.start("ram:SpecifiedTradeSettlementLineMonetarySummation", &[]);
+        w.text("ram:LineTotalAmount", sign(vat::row_net_price(row)));
+        w.end();
+        w.end();
+        w.end();
+    }
+
+    w.start("ram:ApplicableHeaderTradeAgreement", &[]);
+    seller_party(&mut w, entrepreneur);
+    buyer_party(&mut w, contact);
+    w.end();
+
+    w.start("ram:ApplicableHeaderTradeDelivery", &[]);
+    if invoice.kind != InvoiceKind::Proforma {
+        w.start("ram:ActualDeliverySupplyChainEvent", &[]);
+        w.start("ram:OccurrenceDateTime", &[]);
+        date(&mut w, "udt:DateTimeString", invoice.created);
+        w.end();
+        w.end();
+    }
+    w.end();
+
+    w.start("ram:ApplicableHeaderTradeSettlement", &[]);
+    w.text("ram:PaymentReference", variable_symbol(invoice));
+    w.text("ram:InvoiceCurrencyCode", currency);
+    payment_means(&mut w, bank_account.as_ref());
+
+    for recap_line in &vat_recap.lines {
+        let category = category(recap_line.rate);
+
+        w.start("ram:ApplicableTradeTax", &[]);
+        w.text("ram:CalculatedAmount", sign(recap_line.vat));
+        w.text("ram:TypeCode", "VAT");
+        if let Some(reason) = exemption_reason(category, texts) {
+            w.text("ram:ExemptionReason", reason);
+        }
+        w.text("ram:BasisAmount", sign(recap_line.base));
+        w.text("ram:CategoryCode", category);
+        if category != "O" {
+            w.text("ram:RateApplicablePercent", recap_line.rate.unwrap_or_default());
+        }
+        w.end();
+    }
+
+    for recap_line in vat_recap.lines.iter().filter(|l| l.discount != Money::ZERO) {
+        w.start("ram:SpecifiedTradeAllowanceCharge", &[]);
+        w.start("ram:ChargeIndicator", &[]);
+        w.text("udt:Indicator", false);
+        w.end();
+        w.text("ram:ActualAmount", sign(recap_line.discount));
+        w.text("ram:Reason", "Sleva na fakturu"); // TODO hard code value
+        w.start("ram:CategoryTradeTax", &[]);
+        w.text("ram:TypeCode", "VAT");
+        w.text("ram:CategoryCode", category(recap_line.rate));
+        if category(recap_line.rate) != "O" {
+            w.text("ram:RateApplicablePercent", recap_line.rate.unwrap_or_default());
+        }
+        w.end();
+        w.end();
+    }
+
+    w.start("ram:SpecifiedTradePaymentTerms", &[]);
+    w.start("ram:DueDateDateTime", &[]);
+    date(&mut w, "udt:DateTimeString", invoice.pay_until);
+    w.end();
+    w.end();
+
+    w.start("ram:SpecifiedTradeSettlementHeaderMonetarySummation", &[]);
+    w.text("ram:LineTotalAmount", sign(vat_recap.subtotal));
+    w.text("ram:AllowanceTotalAmount", sign(vat_recap.discount));
+    w.text("ram:TaxBasisTotalAmount", sign(vat_recap.base));
+    w.text_with("ram:TaxTotalAmount", &[("currencyID", currency)], sign(vat_recap.vat));
+    w.text("ram:GrandTotalAmount", sign(vat_recap.gross));
+    if let Some(deposit) = deposit {
+        w.text("ram:TotalPrepaidAmount", deposit.amount);
+    }
+    w.text("ram:DuePayableAmount", sign(document.payable(&vat_recap)));
+    w.end();
+
+    if let Some(original) = original {
+        w.start("ram:InvoiceReferencedDocument", &[]);
+        w.text("ram:IssuerAssignedID", &original.code);
+        w.start("ram:FormattedIssueDateTime", &[]);
+        date(&mut w, "qdt:DateTimeString", original.created);
+        w.end();
+        w.end();
+    }
+
+    w.end();
+
+    w.end();
+
+    w.finish()
+}
+
+fn trade_tax(w: &mut XmlWriter, category: &str, rate: Option<u16>) {
+    w.start("ram:ApplicableTradeTax", &[]);
+    w.text("ram:TypeCode", "VAT");
+    w.text("ram:CategoryCode", category);
+    if category != "O" {
+        w.text("ram:RateApplicablePercent", rate.unwrap_or_default());
+    }
+    w.end();
+}
+
+/// Dates are written in the format 102 (`YYYYMMDD`).
+fn date(w: &mut XmlWriter, element: &str, date: NaiveDate) {
+    w.text_with(element, &[("format", "102")], date.format("%Y%m%d"));
+}
+
+fn seller_party(w: &mut XmlWriter, entrepreneur: &Entrepreneur) {
+    w.start("ram:SellerTradeParty", &[]);
+    w.text("ram:Name", &entrepreneur.name);
+    w.start("ram:SpecifiedLegalOrganization", &[]);
+    w.text("ram:ID", &entrepreneur.code);
+    w.end();
+    if entrepreneur.phone.is_some() || entrepreneur.email.is_some() {
+        w.start("ram:DefinedTradeContact", &[]);
+        if let Some(phone) = &entrepreneur.phone {
+            w.start("ram:TelephoneUniversalCommunication", &[]);
+            w.text("ram:CompleteNumber", phone);
+            w.end();
+        }
+        if let Some(email) = &entrepreneur.email {
+            w.start("ram:EmailURIUniversalCommunication", &[]);
+            w.text("ram:URIID", email);
+            w.end();
+        }
+        w.end();
+    }
+    postal_address(w, &entrepreneur.address, &entrepreneur.vat);
+    tax_registration(w, &entrepreneur.vat);
+    w.end();
+}
+
+fn buyer_party(w: &mut XmlWriter, contact: &Contact) {
+    w.start("ram:BuyerTradeParty", &[]);
+    w.text("ram:Name", &contact.name);
+    if let Some(code) = &contact.code {
+        w.start("ram:SpecifiedLegalOrganization", &[]);
+        w.text("ram:ID", code);
+        w.end();
+    }
+    postal_address(w, &contact.address, &contact.vat);
+    tax_registration(w, &contact.vat);
+    w.end();
+}
+
+fn postal_address(w: &mut XmlWriter, address: &str, vat: &Vat) {
+    let address = PostalAddress::parse(address);
+
+    w.start("ram:PostalTradeAddress", &[]);
+    w.text("ram:PostcodeCode", address.postal_zone);
+    w.text("ram:LineOne", format!("{} {}", address.street, address.building_number).trim());
+    w.text("ram:CityName", address.city);
+    w.text("ram:CountryID", country_code(vat));
+    w.end();
+}
+
+fn tax_registration(w: &mut XmlWriter, vat: &Vat) {
+    if let Vat::Code(code) = vat {
+        w.start("ram:SpecifiedTaxRegistration", &[]);
+        w.text_with("ram:ID", &[("schemeID", "VA")], code);
+        w.end();
+    }
+}
+
+fn payment_means(w: &mut XmlWriter, account: Option<&BankAccount>) {
+    let account = match account {
+        Some(account) => account,
+        None => return,
+    };
+
+    w.start("ram:SpecifiedTradeSettlementPaymentMeans", &[]);
+    w.text("ram:TypeCode", 30); // credit transfer
+    w.start("ram:PayeePartyCreditorFinancialAccount", &[]);
+    w.text("ram:IBANID", account.iban.as_str());
+    w.end();
+    if let Some(bic) = &account.bic {
+        w.start("ram:PayeeSpecifiedCreditorFinancialInstitution", &[]);
+        w.text("ram:BICID", bic);
+        w.end();
+    }
+    w.end();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::document;
+    use crate::logic::export::test::assert_in_order;
+
+    #[test]
+    fn test_invoice() {
+        let xml = create(&document(InvoiceKind::Invoice));
+
+        assert_in_order(
+            &xml,
+            &[
+                "rsm:ExchangedDocumentContext",
+                "rsm:ExchangedDocument",
+                "rsm:SupplyChainTradeTransaction",
+                "ram:IncludedSupplyChainTradeLineItem",
+                "ram:ApplicableHeaderTradeAgreement",
+                "ram:SellerTradeParty",
+                "ram:BuyerTradeParty",
+                "ram:ApplicableHeaderTradeDelivery",
+                "ram:ApplicableHeaderTradeSettlement",
+                "ram:PaymentReference",
+                "ram:InvoiceCurrencyCode",
+                "ram:SpecifiedTradeSettlementPaymentMeans",
+                "ram:ApplicableTradeTax",
+                "ram:SpecifiedTradeAllowanceCharge",
+                "ram:SpecifiedTradePaymentTerms",
+                "ram:SpecifiedTradeSettlementHeaderMonetarySummation",
+            ],
+        );
+
+        assert!(xml.contains("<ram:TypeCode>380</ram:TypeCode>"));
+        assert!(xml.contains("<udt:DateTimeString format=\"102\">20220315</udt:DateTimeString>"));
+        assert!(xml.contains("<ram:Name>Jan Novák &amp; syn</ram:Name>"));
+        assert!(xml.contains("<ram:BilledQuantity unitCode=\"C62\">2.000</ram:BilledQuantity>"));
+        assert!(xml.contains("<ram:ID schemeID=\"VA\">CZ12345678</ram:ID>"));
+        assert!(xml.contains("<ram:IBANID>CZ6508000000192000145399</ram:IBANID>"));
+        // invoice discount split into the VAT groups: 200 at 21 %, 30 at 12 %
+        assert!(xml.contains("<ram:ActualAmount>200.00</ram:ActualAmount>"));
+        assert!(xml.contains("<ram:ActualAmount>30.00</ram:ActualAmount>"));
+        assert!(xml.contains("<ram:BasisAmount>1800.00</ram:BasisAmount>"));
+        assert!(xml.contains("<ram:CalculatedAmount>378.00</ram:CalculatedAmount>"));
+        assert!(xml.contains("<ram:LineTotalAmount>2300.00</ram:LineTotalAmount>"));
+        assert!(xml.contains("<ram:TaxTotalAmount currencyID=\"CZK\">410.40</ram:TaxTotalAmount>"));
+        assert!(xml.contains("<ram:DuePayableAmount>2480.40</ram:DuePayableAmount>"));
+        assert!(!xml.contains("ram:InvoiceReferencedDocument"));
+    }
+
+    #[test]
+    fn test_credit_note() {
+        let mut document = document(InvoiceKind::CreditNote);
+        document.rows.iter_mut().for_each(|row| row.item_price = -row.item_price);
+
+        let xml = create(&document);
+
+        assert!(xml.contains("<ram:TypeCode>381</ram:TypeCode>"));
+        assert!(xml.contains("<ram:IssuerAssignedID>2022-0001</ram:IssuerAssignedID>"));
+        assert!(xml.contains("<ram:DuePayableAmount>2480.40</ram:DuePayableAmount>"));
+        assert!(!xml.contains(">-"));
+    }
+}
@@ -0,0 +1,541 @@
+//! ISDOC 6 - the Czech standard of electronic invoices (<https://isdoc.cz>).
+
+use std::io::{Cursor, Write};
+
+use err_context::AnyError;
+use sha1::{Digest, Sha1};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::dao::{Contact, Entrepreneur, Invoice, InvoiceKind, Money, Quantity, Vat};
+use crate::logic::bank_account::BankAccount;
+use crate::logic::currency;
+use crate::logic::export::xml::XmlWriter;
+use crate::logic::export::{catalog, country_code, document_uuid, PostalAddress};
+use crate::logic::invoices::variable_symbol;
+use crate::logic::pdf::i18n::Catalog;
+use crate::logic::vat::{self, VatRecap};
+use crate::logic::InvoiceDocument;
+
+const NAMESPACE: &str = "http://isdoc.cz/namespace/2013";
+const MANIFEST_NAMESPACE: &str = "http://isdoc.cz/namespace/2013/manifest";
+const VERSION: &str = "6.0.2";
+
+/// A file attached to the ISDOC document.
+pub struct Supplement<'a> {
+    pub filename: &'a str,
+    pub content: &'a [u8],
+}
+
+/// Package with the ISDOC document and its supplements (usually the PDF).
+pub fn create_package(document: &InvoiceDocument, supplements: &[Supplement]) -> Result<Vec<u8>, AnyError> {
+    let isdoc_filename = filename(&document.invoice, "isdoc");
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    zip.start_file(&isdoc_filename, FileOptions::default())?;
+    zip.write_all(create(document, supplements).as_bytes())?;
+
+    for supplement in supplements {
+        zip.start_file(supplement.filename, FileOptions::default())?;
+        zip.write_all(supplement.content)?;
+    }
+
+    let mut manifest = XmlWriter::default();
+    manifest.start("manifest", &[("xmlns", MANIFEST_NAMESPACE)]);
+    manifest.empty("maindocument", &[("filename", &isdoc_filename)]);
+
+    zip.start_file("manifest.xml", FileOptions::default())?;
+    zip.write_all(manifest.finish().as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn filename(invoice: &Invoice, extension: &str) -> String {
+    format!("invoice_{}.{}", invoice.code, extension)
+}
+
+pub fn create(document: &InvoiceDocument, supplements: &[Supplement]) -> String {
+    let InvoiceDocument {
+        entrepreneur,
+        bank_account,
+        contact,
+        invoice,
+        original,
+        deposit,
+        rows,
+        ..
+    } = document;
+
+    let vat_recap = VatRecap::new(invoice, rows);
+    let payable = document.payable(&vat_recap);
+    let texts = catalog(document);
+    let vat_applicable = matches!(entrepreneur.vat, Vat::Code(_));
+    let amounts = Amounts {
+        invoice,
+        foreign: invoice.currency_code != entrepreneur.currency_code,
+    };
+
+    let mut w = XmlWriter::default();
+
+    w.start("Invoice", &[("xmlns", NAMESPACE), ("version", VERSION)]);
+
+    w.text("DocumentType", document_type(invoice.kind));
+    w.text("ID", &invoice.code);
+    w.text("UUID", uuid(invoice));
+    w.text("IssueDate", invoice.created);
+    if invoice.kind != InvoiceKind::Proforma {
+        w.text("TaxPointDate", invoice.created);
+    }
+    w.text("VATApplicable", vat_applicable);
+    w.text("ElectronicPossibilityAgreementReference", "");
+    w.text("LocalCurrencyCode", &entrepreneur.currency_code);
+    if amounts.foreign {
+        w.text("ForeignCurrencyCode", &invoice.currency_code);
+        w.text("CurrRate", invoice.exchange_rate);
+    } else {
+        w.text("CurrRate", 1);
+    }
+    w.text("RefCurrRate", 1);
+
+    w.start("AccountingSupplierParty", &[]);
+    entrepreneur_party(&mut w, entrepreneur, texts);
+    w.end();
+
+    w.start("AccountingCustomerParty", &[]);
+    contact_party(&mut w, contact, texts);
+    w.end();
+
+    if let Some(original) = original {
+        w.start("OriginalDocumentReferences", &[]);
+        w.start("OriginalDocumentReference", &[("id", "original")]);
+        w.text("ID", &original.code);
+        w.text("IssueDate", original.created);
+        w.text("UUID", uuid(original));
+        w.end();
+        w.end();
+    }
+
+    w.start("InvoiceLines", &[]);
+
+    let mut line_id = 0;
+
+    for row in rows.iter() {
+        line_id += 1;
+
+        let rate = row.vat_rate.map(|r| r as u16);
+
+        line(
+            &mut w,
+            &amounts,
+            line_id,
+            row.item_count,
+            row.item_unit.as_deref(),
+            row.item_price,
+            vat::row_net_price(row),
+            rate,
+            &row.item_name,
+        );
+    }
+
+    for recap_line in vat_recap.lines.iter().filter(|l| l.discount != Money::ZERO) {
+        line_id += 1;
+
+        line(
+            &mut w,
+            &amounts,
+            line_id,
+            Quantity::from_int(1),
+            None,
+            -recap_line.discount,
+            -recap_line.discount,
+            recap_line.rate,
+            "Sleva na fakturu", // TODO hard code value
+        );
+    }
+
+    w.end();
+
+    if let Some(deposit) = deposit {
+        // the proforma isn't a tax document
+        w.start("NonTaxedDeposits", &[]);
+        w.start("NonTaxedDeposit", &[]);
+        w.text("ID", &deposit.proforma.code);
+        w.text("VariableSymbol", variable_symbol(&deposit.proforma));
+        amounts.write(&mut w, "DepositAmount", deposit.amount);
+        w.end();
+        w.end();
+    }
+
+    w.start("TaxTotal", &[]);
+    for recap_line in &vat_recap.lines {
+        w.start("TaxSubTotal", &[]);
+        amounts.write(&mut w, "TaxableAmount", recap_line.base);
+        amounts.write(&mut w, "TaxAmount", recap_line.vat);
+        amounts.write(&mut w, "TaxInclusiveAmount", recap_line.gross);
+        amounts.write(&mut w, "AlreadyClaimedTaxableAmount", Money::ZERO);
+        amounts.write(&mut w, "AlreadyClaimedTaxAmount", Money::ZERO);
+        amounts.write(&mut w, "AlreadyClaimedTaxInclusiveAmount", Money::ZERO);
+        amounts.write(&mut w, "DifferenceTaxableAmount", recap_line.base);
+        amounts.write(&mut w, "DifferenceTaxAmount", recap_line.vat);
+        amounts.write(&mut w, "DifferenceTaxInclusiveAmount", recap_line.gross);
+        w.start("TaxCategory", &[]);
+        w.text("Percent", recap_line.rate.unwrap_or_default());
+        w.end();
+        w.end();
+    }
+    amounts.write(&mut w, "TaxAmount", vat_recap.vat);
+    w.end();
+
+    w.start("LegalMonetaryTotal", &[]);
+    amounts.write_total(&mut w, "TaxExclusiveAmount", vat_recap.base);
+    amounts.write_total(&mut w, "TaxInclusiveAmount", vat_recap.gross);
+    amounts.write_total(&mut w, "AlreadyClaimedTaxExclusiveAmount", Money::ZERO);
+    amounts.write_total(&mut w, "AlreadyClaimedTaxInclusiveAmount", Money::ZERO);
+    amounts.write_total(&mut w, "DifferenceTaxExclusiveAmount", vat_recap.base);
+    amounts.write_total(&mut w, "DifferenceTaxInclusiveAmount", vat_recap.gross);
+    amounts.write_total(&mut w, "PaidDepositsAmount", vat_recap.gross - payable);
+    amounts.write_total(&mut w, "PayableAmount", payable);
+    w.end();
+
+    w.start("PaymentMeans", &[]);
+    w.start("Payment", &[]);
+    w.text("PaidAmount", amounts.local(payable));
+    w.text("PaymentMeansCode", 42); // bank transfer
+    w.start("Details", &[]);
+    w.text("PaymentDueDate", invoice.pay_until);
+    bank_account_details(&mut w, bank_account.as_ref());
+    w.text("VariableSymbol", variable_symbol(invoice));
+    w.end();
+    w.end();
+    w.end();
+
+    if !supplements.is_empty() {
+        w.start("SupplementsList", &[]);
+        for supplement in supplements {
+            w.start("Supplement", &[("preview", "true")]);
+            w.text("Filename", supplement.filename);
+            w.empty("DigestMethod", &[("Algorithm", "http://www.w3.org/2000/09/xmldsig#sha1")]);
+            w.text("DigestValue", base64::encode(Sha1::digest(supplement.content)));
+            w.end();
+        }
+        w.end();
+    }
+
+    w.finish()
+}
+
+fn document_type(kind: InvoiceKind) -> u8 {
+    match kind {
+        InvoiceKind::Invoice => 1,
+        InvoiceKind::CreditNote => 2,
+        InvoiceKind::Proforma => 4,
+    }
+}
+
+fn uuid(invoice: &Invoice) -> String {
+    document_uuid(invoice).hyphenated().to_string().to_uppercase()
+}
+
+/// ISDOC states the amounts in the local currency (of the entrepreneur). The ones of an invoice in a foreign currency
+/// are accompanied by the `…Curr` elements with the amounts in that currency.
+struct Amounts<'a> {
+    invoice: &'a Invoice,
+    foreign: bool,
+}
+
+impl Amounts<'_> {
+    /// Writes the amount of the invoice, the foreign one first - as in the lines and the VAT recapitulation.
+    fn write(&self, w: &mut XmlWriter, name: &str, amount: Money) {
+        if self.foreign {
+            w.text(&format!("{}Curr", name), amount);
+        }
+        w.text(name, self.local(amount));
+    }
+
+    /// Writes the amount of the invoice, the foreign one last - as in the totals of the document.
+    fn write_total(&self, w: &mut XmlWriter, name: &str, amount: Money) {
+        w.text(name, self.local(amount));
+        if self.foreign {
+            w.text(&format!("{}Curr", name), amount);
+        }
+    }
+
+    fn local(&self, amount: Money) -> Money {
+        if self.foreign {
+            currency::to_home(self.invoice, amount)
+        } else {
+            amount
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn line(
+    w: &mut XmlWriter,
+    amounts: &Amounts,
+    id: usize,
+    count: Quantity,
+    unit: Option<&str>,
+    unit_price: Money,
+    price: Money,
+    rate: Option<u16>,
+    description: &str,
+) {
+    let vat = vat::vat_of(price, rate);
+
+    w.start("InvoiceLine", &[]);
+    w.text("ID", id);
+    match unit {
+        Some(unit) => w.text_with("InvoicedQuantity", &[("unitCode", unit)], count),
+        None => w.text("InvoicedQuantity", count),
+    };
+    amounts.write(w, "LineExtensionAmount", price);
+    amounts.write(w, "LineExtensionAmountTaxInclusive", price + vat);
+    w.text("LineExtensionTaxAmount", amounts.local(vat));
+    w.text("UnitPrice", amounts.local(unit_price));
+    w.text("UnitPriceTaxInclusive", amounts.local(unit_price + vat::vat_of(unit_price, rate)));
+    w.start("ClassifiedTaxCategory", &[]);
+    w.text("Percent", rate.unwrap_or_default());
+    w.text("VATCalculationMethod", 0); // from the prices without VAT
+    w.end();
+    w.start("Item", &[]);
+    w.text("Description", description);
+    w.end();
+    w.end();
+}
+
+fn entrepreneur_party(w: &mut XmlWriter, entrepreneur: &Entrepreneur, texts: &Catalog) {
+    w.start("Party", &[]);
+    party_details(
+        w,
+        Some(&entrepreneur.code),
+        &entrepreneur.name,
+        &entrepreneur.address,
+        &entrepreneur.vat,
+        texts,
+    );
+
+    if entrepreneur.phone.is_some() || entrepreneur.email.is_some() {
+        w.start("Contact", &[]);
+        if let Some(phone) = &entrepreneur.phone {
+            w.text("Telephone", phone);
+        }
+        if let Some(email) = &entrepreneur.email {
+            w.text("ElectronicMail", email);
+        }
+        w.end();
+    }
+
+    w.end();
+}
+
+fn contact_party(w: &mut XmlWriter, contact: &Contact, texts: &Catalog) {
+    w.start("Party", &[]);
+    party_details(w, contact.code.as_deref(), &contact.name, &contact.address, &contact.vat, texts);
+    w.end();
+}
+
+fn party_details(w: &mut XmlWriter, code: Option<&str>, name: &str, address: &str, vat: &Vat, texts: &Catalog) {
+    let address = PostalAddress::parse(address);
+
+    w.start("PartyIdentification", &[]);
+    w.text("ID", code.unwrap_or_default());
+    w.end();
+
+    w.start("PartyName", &[]);
+    w.text("Name", name);
+    w.end();
+
+    w.start("PostalAddress", &[]);
+    w.text("StreetName", address.street);
+    w.text("BuildingNumber", address.building_number);
+    w.text("CityName", address.city);
+    w.text("PostalZone", address.postal_zone);
+    let country = country_code(vat);
+    w.start("Country", &[]);
+    w.text("IdentificationCode", country);
+    w.text("Name", texts.country_name(country));
+    w.end();
+    w.end();
+
+    if let Vat::Code(code) = vat {
+        w.start("PartyTaxScheme", &[]);
+        w.text("CompanyID", code);
+        w.text("TaxScheme", "VAT");
+        w.end();
+    }
+}
+
+/// The elements are required, they're empty for the parts of the account the entrepreneur doesn't have.
+fn bank_account_details(w: &mut XmlWriter, account: Option<&BankAccount>) {
+    let domestic = account.and_then(BankAccount::domestic);
+
+    w.text("ID", domestic.map(|d| d.number_with_prefix()).unwrap_or_default());
+    w.text("BankCode", domestic.map(|d| format!("{:04}", d.bank_code)).unwrap_or_default());
+    w.text("Name", "");
+    w.text("IBAN", account.map(|a| a.iban.as_str()).unwrap_or_default());
+    w.text("BIC", account.and_then(|a| a.bic.as_deref()).unwrap_or_default());
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+    use crate::fixtures::{document, final_invoice, foreign_invoice};
+    use crate::logic::export::test::{assert_in_order, validate_schema};
+
+    const SCHEMA: &str = "schemas/isdoc/isdoc-invoice-6.0.2.xsd";
+
+    fn assert_valid(xml: &str) {
+        if let Err(errors) = validate_schema(xml, SCHEMA) {
+            panic!("ISDOC schema failed: {}\n{}", errors, xml);
+        }
+    }
+
+    #[test]
+    fn test_invoice() {
+        let xml = create(&document(InvoiceKind::Invoice), &[]);
+
+        assert_in_order(
+            &xml,
+            &[
+                "DocumentType",
+                "ID",
+                "UUID",
+                "IssueDate",
+                "TaxPointDate",
+                "VATApplicable",
+                "ElectronicPossibilityAgreementReference",
+                "LocalCurrencyCode",
+                "CurrRate",
+                "RefCurrRate",
+                "AccountingSupplierParty",
+                "AccountingCustomerParty",
+                "InvoiceLines",
+                "TaxTotal",
+                "LegalMonetaryTotal",
+                "PaymentMeans",
+            ],
+        );
+
+        assert!(xml.contains("<DocumentType>1</DocumentType>"));
+        assert!(xml.contains("<Name>Jan Novák &amp; syn</Name>"));
+        assert!(xml.contains("<InvoicedQuantity unitCode=\"ks\">2.000</InvoicedQuantity>"));
+        // invoice discount split into the VAT groups: 200 at 21 %, 30 at 12 %
+        assert!(xml.contains("<LineExtensionAmount>-200.00</LineExtensionAmount>"));
+        assert!(xml.contains("<LineExtensionAmount>-30.00</LineExtensionAmount>"));
+        assert!(xml.contains("<TaxableAmount>1800.00</TaxableAmount>"));
+        assert!(xml.contains("<TaxAmount>378.00</TaxAmount>"));
+        assert!(xml.contains("<PayableAmount>2480.40</PayableAmount>"));
+        assert!(xml.contains("<IBAN>CZ6508000000192000145399</IBAN>"));
+        assert!(xml.contains("<VariableSymbol>20220002</VariableSymbol>"));
+        assert!(!xml.contains("OriginalDocumentReferences"));
+    }
+
+    #[test]
+    fn test_final_invoice() {
+        let xml = create(&final_invoice(), &[]);
+
+        assert_in_order(&xml, &["InvoiceLines", "NonTaxedDeposits", "TaxTotal", "LegalMonetaryTotal"]);
+        assert_in_order(&xml, &["NonTaxedDeposit", "ID", "VariableSymbol", "DepositAmount"]);
+        assert!(xml.contains("<ID>2022-0001</ID>"));
+        assert!(xml.contains("<VariableSymbol>20220001</VariableSymbol>"));
+        assert!(xml.contains("<DepositAmount>1000.00</DepositAmount>"));
+        // the whole VAT is declared, only the amount to be paid is lower
+        assert!(xml.contains("<TaxAmount>378.00</TaxAmount>"));
+        assert!(xml.contains("<PaidDepositsAmount>1000.00</PaidDepositsAmount>"));
+        assert!(xml.contains("<PayableAmount>1480.40</PayableAmount>"));
+        assert!(xml.contains("<PaidAmount>1480.40</PaidAmount>"));
+    }
+
+    #[test]
+    fn test_foreign_invoice() {
+        let xml = create(&foreign_invoice(), &[]);
+
+        // the amounts are in the local currency, the ones in euros accompany them
+        assert_in_order(&xml, &["LocalCurrencyCode", "ForeignCurrencyCode", "CurrRate", "RefCurrRate"]);
+        assert!(xml.contains("<LocalCurrencyCode>CZK</LocalCurrencyCode>"));
+        assert!(xml.contains("<ForeignCurrencyCode>EUR</ForeignCurrencyCode>"));
+        assert!(xml.contains("<CurrRate>24.500000</CurrRate>"));
+        assert_in_order(&xml, &["TaxSubTotal", "TaxableAmountCurr", "TaxableAmount"]);
+        assert!(xml.contains("<TaxableAmountCurr>1800.00</TaxableAmountCurr>"));
+        assert!(xml.contains("<TaxableAmount>44100.00</TaxableAmount>"));
+        assert_in_order(&xml, &["LegalMonetaryTotal", "PayableAmount", "PayableAmountCurr"]);
+        assert!(xml.contains("<PayableAmount>60769.80</PayableAmount>"));
+        assert!(xml.contains("<PayableAmountCurr>2480.40</PayableAmountCurr>"));
+        // the customer is German, the entrepreneur Czech
+        assert_in_order(
+            &xml,
+            &[
+                "AccountingSupplierParty",
+                "IdentificationCode",
+                "AccountingCustomerParty",
+                "IdentificationCode",
+            ],
+        );
+        assert!(xml.contains("<IdentificationCode>DE</IdentificationCode>"));
+        assert!(xml.contains("<Name>Německo</Name>"));
+    }
+
+    #[test]
+    fn test_credit_note() {
+        let document = document(InvoiceKind::CreditNote);
+        let xml = create(&document, &[]);
+
+        assert!(xml.contains("<DocumentType>2</DocumentType>"));
+        assert_in_order(
+            &xml,
+            &["AccountingCustomerParty", "OriginalDocumentReferences", "ID", "InvoiceLines"],
+        );
+        assert!(xml.contains(&format!("<UUID>{}</UUID>", uuid(document.original.as_ref().unwrap()))));
+        assert_ne!(uuid(&document.invoice), uuid(document.original.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn test_package() {
+        let document = document(InvoiceKind::Invoice);
+        let pdf = b"%PDF-1.4";
+        let package = create_package(
+            &document,
+            &[Supplement {
+                filename: "invoice_2022-0002.pdf",
+                content: pdf,
+            }],
+        )
+        .unwrap();
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(package)).unwrap();
+        let mut isdoc = String::new();
+        zip.by_name("invoice_2022-0002.isdoc").unwrap().read_to_string(&mut isdoc).unwrap();
+        let mut manifest = String::new();
+        zip.by_name("manifest.xml").unwrap().read_to_string(&mut manifest).unwrap();
+
+        assert!(zip.by_name("invoice_2022-0002.pdf").is_ok());
+        assert!(manifest.contains("<maindocument filename=\"invoice_2022-0002.isdoc\"/>"));
+        assert_in_order(&isdoc, &["PaymentMeans", "SupplementsList", "Filename", "DigestValue"]);
+        assert!(isdoc.contains(&base64::encode(Sha1::digest(pdf))));
+    }
+
+    #[test]
+    fn test_against_schema() {
+        let documents = [
+            document(InvoiceKind::Invoice),
+            document(InvoiceKind::CreditNote),
+            document(InvoiceKind::Proforma),
+            final_invoice(),
+            foreign_invoice(),
+        ];
+
+        for document in documents {
+            assert_valid(&create(&document, &[]));
+        }
+
+        let supplement = Supplement {
+            filename: "invoice_2022-0002.pdf",
+            content: b"%PDF-1.4",
+        };
+        assert_valid(&create(&document(InvoiceKind::Invoice), &[supplement]));
+    }
+}
@@ -0,0 +1,222 @@
+use sha2::{Digest, Sha256};
+use uuid::{Builder, Uuid};
+
+use crate::dao::{Invoice, InvoiceKind, Vat};
+use crate::logic::pdf::i18n::Catalog;
+use crate::logic::InvoiceDocument;
+
+pub mod facturx;
+pub mod isdoc;
+pub mod pohoda;
+pub mod ubl;
+pub mod xml;
+
+/// Identifier of the document, derived from the invoice - every export of it carries the same one.
+fn document_uuid(invoice: &Invoice) -> Uuid {
+    let hash = Sha256::digest(format!("invoice/{}/{}", invoice.entrepreneur_id, invoice.id).as_bytes());
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+
+    Builder::from_random_bytes(bytes).into_uuid()
+}
+
+/// Texts in the language of the document.
+fn catalog(document: &InvoiceDocument) -> &'static Catalog {
+    Catalog::of(document.invoice.language.unwrap_or(document.contact.language))
+}
+
+/// UNTDID 1001 code of the document.
+fn type_code(kind: InvoiceKind) -> u16 {
+    match kind {
+        InvoiceKind::Invoice => 380,
+        InvoiceKind::CreditNote => 381,
+        InvoiceKind::Proforma => 386,
+    }
+}
+
+/// UNCL 5305 category of the VAT rate.
+fn tax_category(seller_vat: &Vat, rate: Option<u16>) -> &'static str {
+    match rate {
+        Some(0) => "Z",
+        Some(_) => "S",
+        None if matches!(seller_vat, Vat::Code(_)) => "E",
+        None => "O",
+    }
+}
+
+fn exemption_reason(category: &str, catalog: &Catalog) -> Option<&'static str> {
+    match category {
+        "E" => Some(catalog.vat_exemption),
+        "O" => Some(catalog.not_vat_payer),
+        _ => None,
+    }
+}
+
+/// UN/ECE Recommendation 20 code of the unit; units without a known code are counted in pieces.
+fn unit_code(unit: Option<&str>) -> &'static str {
+    match unit.map(|u| u.trim().trim_end_matches('.').to_lowercase()).as_deref() {
+        Some("h" | "hod" | "hodina" | "hodin") => "HUR",
+        Some("den" | "dny" | "dní" | "dnů") => "DAY",
+        Some("měs" | "měsíc") => "MON",
+        Some("kg") => "KGM",
+        Some("g") => "GRM",
+        Some("m") => "MTR",
+        Some("km") => "KMT",
+        Some("m2" | "m²") => "MTK",
+        Some("l") => "LTR",
+        _ => "C62",
+    }
+}
+
+/// ISO 3166-1 code of the country of a party - the prefix of its VAT number (Greece uses `EL` there). Parties without
+/// one are taken as domestic.
+fn country_code(vat: &Vat) -> &str {
+    let prefix = match vat {
+        Vat::Code(code) => code.get(..2).filter(|p| p.chars().all(|c| c.is_ascii_uppercase())),
+        _ => None,
+    };
+
+    match prefix {
+        Some("EL") => "GR",
+        Some(prefix) => prefix,
+        None => "CZ",
+    }
+}
+
+/// Parts of an address stored as free text - the street (with the building number) on the first line, the postal code
+/// with the city on the last one.
+#[derive(PartialEq, Debug, Default)]
+struct PostalAddress<'a> {
+    street: &'a str,
+    building_number: &'a str,
+    city: &'a str,
+    postal_zone: String,
+}
+
+impl<'a> PostalAddress<'a> {
+    fn parse(address: &'a str) -> Self {
+        let lines = address.split("\r\n").map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>();
+
+        let (street, building_number) = match lines.first() {
+            Some(street) if lines.len() > 1 => match street.rsplit_once(' ') {
+                Some((name, number)) if number.starts_with(|c: char| c.is_ascii_digit()) => (name, number),
+                _ => (*street, ""),
+            },
+            _ => ("", ""),
+        };
+
+        let (postal_zone, city) = match lines.last() {
+            Some(last) => {
+                let digits = last.chars().take_while(|c| c.is_ascii_digit() || *c == ' ').collect::<String>();
+                let postal_zone = digits.replace(' ', "");
+
+                if postal_zone.len() == 5 {
+                    (postal_zone, last[digits.len()..].trim())
+                } else {
+                    (String::new(), *last)
+                }
+            }
+            None => (String::new(), ""),
+        };
+
+        PostalAddress {
+            street,
+            building_number,
+            city,
+            postal_zone,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    /// Validates the `xml` against the official XML schema at the `schema` path (relative to the crate) by `xmllint` of
+    /// libxml2. Returns the errors it reports.
+    pub fn validate_schema(xml: &str, schema: &str) -> Result<(), String> {
+        assert!(
+            Path::new(schema).exists(),
+            "Schema {} is missing, vendor the official one unmodified (see schemas/README.md)",
+            schema
+        );
+
+        let mut xmllint = Command::new("xmllint")
+            .args(["--noout", "--nonet", "--schema", schema, "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("xmllint (of libxml2) is needed to validate the exports");
+
+        xmllint.stdin.take().unwrap().write_all(xml.as_bytes()).unwrap();
+        let output = xmllint.wait_with_output().unwrap();
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    /// Elements of the document in the order the schema requires them.
+    pub fn assert_in_order(xml: &str, elements: &[&str]) {
+        let mut last = 0;
+
+        for element in elements {
+            let position = xml[last..]
+                .find(&format!("<{}>", element))
+                .unwrap_or_else(|| panic!("Missing (or misplaced) element {}", element));
+            last += position;
+        }
+    }
+
+    #[test]
+    fn test_unit_code() {
+        assert_eq!(unit_code(Some("hod.")), "HUR");
+        assert_eq!(unit_code(Some("Kg")), "KGM");
+        assert_eq!(unit_code(Some("ks")), "C62");
+        assert_eq!(unit_code(None), "C62");
+    }
+
+    #[test]
+    fn test_country() {
+        assert_eq!(country_code(&Vat::Code("SK2020123456".to_string())), "SK");
+        assert_eq!(country_code(&Vat::Code("EL123456789".to_string())), "GR");
+        assert_eq!(country_code(&Vat::NotTaxPayer), "CZ");
+    }
+
+    #[test]
+    fn test_postal_address() {
+        assert_eq!(
+            PostalAddress::parse("Hlavní 123/4\r\n436 01 Litvínov"),
+            PostalAddress {
+                street: "Hlavní",
+                building_number: "123/4",
+                city: "Litvínov",
+                postal_zone: "43601".to_string(),
+            }
+        );
+        assert_eq!(
+            PostalAddress::parse("Na Příkopě\r\nNové Město\r\n11000 Praha 1"),
+            PostalAddress {
+                street: "Na Příkopě",
+                building_number: "",
+                city: "Praha 1",
+                postal_zone: "11000".to_string(),
+            }
+        );
+        assert_eq!(
+            PostalAddress::parse("Litvínov"),
+            PostalAddress {
+                city: "Litvínov",
+                ..Default::default()
+            }
+        );
+    }
+}
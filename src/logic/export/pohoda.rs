@@ -0,0 +1,351 @@
+//! XML import (`dataPack`) of Stormware Pohoda, an accounting software (<https://www.stormware.cz/xml>).
+
+use chrono::NaiveDate;
+
+use crate::dao::{Contact, Entrepreneur, InvoiceKind, Money, Percent, Quantity, Vat};
+use crate::logic::discount::Discount;
+use crate::logic::export::xml::XmlWriter;
+use crate::logic::export::PostalAddress;
+use crate::logic::invoices::variable_symbol;
+use crate::logic::vat::{row_discount, VatRecap};
+use crate::logic::InvoiceDocument;
+
+const DATA_NAMESPACE: &str = "http://www.stormware.cz/schema/version_2/data.xsd";
+const INVOICE_NAMESPACE: &str = "http://www.stormware.cz/schema/version_2/invoice.xsd";
+const TYPE_NAMESPACE: &str = "http://www.stormware.cz/schema/version_2/type.xsd";
+const VERSION: &str = "2.0";
+
+/// One `dataPack` with all the documents (of the entrepreneur) issued in the period.
+pub fn create(entrepreneur: &Entrepreneur, from: NaiveDate, to: NaiveDate, documents: &[InvoiceDocument]) -> String {
+    let id = format!("faktury-{}-{}", from, to);
+    let note = format!("Faktury {} - {}", from, to); // TODO hard code value
+
+    let mut w = XmlWriter::default();
+
+    w.start(
+        "dat:dataPack",
+        &[
+            ("xmlns:dat", DATA_NAMESPACE),
+            ("xmlns:inv", INVOICE_NAMESPACE),
+            ("xmlns:typ", TYPE_NAMESPACE),
+            ("version", VERSION),
+            ("id", &id),
+            ("ico", &entrepreneur.code),
+            ("application", "faktury"),
+            ("note", &note),
+        ],
+    );
+
+    for document in documents {
+        w.start("dat:dataPackItem", &[("version", VERSION), ("id", &document.invoice.code)]);
+        invoice(&mut w, document);
+        w.end();
+    }
+
+    w.finish()
+}
+
+fn invoice(w: &mut XmlWriter, document: &InvoiceDocument) {
+    let InvoiceDocument {
+        entrepreneur,
+        bank_account,
+        contact,
+        invoice,
+        original,
+        deposit,
+        rows,
+        ..
+    } = document;
+
+    let vat_recap = VatRecap::new(invoice, rows);
+    let foreign = invoice.currency_code != entrepreneur.currency_code;
+
+    w.start("inv:invoice", &[("version", VERSION)]);
+
+    w.start("inv:invoiceHeader", &[]);
+    w.text("inv:invoiceType", invoice_type(invoice.kind));
+    w.start("inv:number", &[]);
+    w.text("typ:numberRequested", &invoice.code);
+    w.end();
+    w.text("inv:symVar", variable_symbol(invoice));
+    w.text("inv:date", invoice.created);
+    if invoice.kind != InvoiceKind::Proforma {
+        w.text("inv:dateTax", invoice.created);
+    }
+    w.text("inv:dateAccounting", invoice.created);
+    w.text("inv:dateDue", invoice.pay_until);
+    w.text(
+        "inv:text",
+        match original {
+            Some(original) => format!("Opravný daňový doklad k faktuře {}", original.code), // TODO hard code value
+            None => "Fakturujeme Vám".to_string(),                                          // TODO hard code value
+        },
+    );
+    partner(w, contact);
+    w.start("inv:paymentType", &[]);
+    w.text("typ:paymentType", "draft"); // bank transfer
+    w.end();
+    if let Some(account) = bank_account {
+        w.start("inv:account", &[]);
+        match account.domestic() {
+            Some(domestic) => {
+                w.text("typ:accountNo", domestic.number_with_prefix());
+                w.text("typ:bankCode", format!("{:04}", domestic.bank_code));
+            }
+            None => {
+                w.text("typ:accountNo", account.iban.as_str());
+            }
+        }
+        w.end();
+    }
+    if let Some(payed) = invoice.payed {
+        w.text("inv:intNote", format!("Uhrazeno {}", payed)); // TODO hard code value
+    }
+    w.end();
+
+    w.start("inv:invoiceDetail", &[]);
+
+    for row in rows {
+        let rate = row.vat_rate.map(|r| r as u16);
+        let discount = Discount::of_row(row);
+
+        item(
+            w,
+            &row.item_name,
+            row.item_count,
+            row.item_unit.as_deref(),
+            rate,
+            discount.percent,
+            row.item_price,
+            foreign,
+        );
+
+        if discount.amount.is_some() {
+            // a fixed discount can't be put on the item itself
+            let amount = row_discount(row);
+            let text = format!("Sleva - {}", row.item_name); // TODO hard code value
+            item(w, &text, Quantity::from_int(1), None, rate, None, -amount, foreign);
+        }
+    }
+
+    for recap_line in vat_recap.lines.iter().filter(|l| l.discount != Money::ZERO) {
+        let text = "Sleva na fakturu"; // TODO hard code value
+        item(
+            w,
+            text,
+            Quantity::from_int(1),
+            None,
+            recap_line.rate,
+            None,
+            -recap_line.discount,
+            foreign,
+        );
+    }
+
+    if let Some(deposit) = deposit {
+        // the proforma isn't a tax document, so its advance is deducted without any VAT
+        w.start("inv:invoiceAdvancePaymentItem", &[]);
+        w.start("inv:sourceDocument", &[]);
+        w.text("typ:number", &deposit.proforma.code);
+        w.end();
+        w.text("inv:quantity", 1);
+        w.text("inv:payVAT", false);
+        w.text("inv:rateVAT", rate_vat(None));
+        w.start(if foreign { "inv:foreignCurrency" } else { "inv:homeCurrency" }, &[]);
+        w.text("typ:unitPrice", -deposit.amount);
+        w.end();
+        w.end();
+    }
+
+    w.end();
+
+    w.start("inv:invoiceSummary", &[]);
+    w.text("inv:roundingDocument", "none");
+    if foreign {
+        // Pohoda converts the amounts to the home currency by the rate
+        w.start("inv:foreignCurrency", &[]);
+        w.start("typ:currency", &[]);
+        w.text("typ:ids", &invoice.currency_code);
+        w.end();
+        w.text("typ:rate", invoice.exchange_rate);
+        w.text("typ:amount", 1);
+        w.text("typ:priceSum", vat_recap.gross);
+    } else {
+        w.start("inv:homeCurrency", &[]);
+        let none = vat_recap
+            .lines
+            .iter()
+            .filter(|l| rate_vat(l.rate) == "none")
+            .map(|l| l.base)
+            .sum::<Money>();
+        w.text("typ:priceNone", none);
+        for (rate, name) in [(12, "Low"), (21, "High")] {
+            if let Some(line) = vat_recap.lines.iter().find(|l| l.rate == Some(rate)) {
+                w.text(&format!("typ:price{}", name), line.base);
+                w.text(&format!("typ:price{}VAT", name), line.vat);
+                w.text(&format!("typ:price{}Sum", name), line.gross);
+            }
+        }
+    }
+    w.end();
+    w.end();
+
+    w.end();
+}
+
+fn invoice_type(kind: InvoiceKind) -> &'static str {
+    match kind {
+        InvoiceKind::Invoice => "issuedInvoice",
+        InvoiceKind::CreditNote => "issuedCreditNotice",
+        InvoiceKind::Proforma => "issuedAdvanceInvoice",
+    }
+}
+
+/// Pohoda knows the rates by their names only.
+fn rate_vat(rate: Option<u16>) -> &'static str {
+    match rate {
+        Some(21) => "high",
+        Some(12) => "low",
+        _ => "none",
+    }
+}
+
+fn partner(w: &mut XmlWriter, contact: &Contact) {
+    let address = PostalAddress::parse(&contact.address);
+
+    w.start("inv:partnerIdentity", &[]);
+    w.start("typ:address", &[]);
+    w.text("typ:company", &contact.name);
+    w.text("typ:city", address.city);
+    w.text("typ:street", format!("{} {}", address.street, address.building_number).trim());
+    w.text("typ:zip", address.postal_zone);
+    if let Some(code) = &contact.code {
+        w.text("typ:ico", code);
+    }
+    if let Vat::Code(code) = &contact.vat {
+        w.text("typ:dic", code);
+    }
+    w.end();
+    w.end();
+}
+
+/// The `unit_price` is in the home currency, or in the foreign one of the invoice.
+#[allow(clippy::too_many_arguments)]
+fn item(
+    w: &mut XmlWriter,
+    text: &str,
+    quantity: Quantity,
+    unit: Option<&str>,
+    rate: Option<u16>,
+    discount_percent: Option<Percent>,
+    unit_price: Money,
+    foreign: bool,
+) {
+    w.start("inv:invoiceItem", &[]);
+    w.text("inv:text", text);
+    w.text("inv:quantity", quantity);
+    if let Some(unit) = unit {
+        w.text("inv:unit", unit);
+    }
+    w.text("inv:payVAT", false); // the prices are without VAT
+    w.text("inv:rateVAT", rate_vat(rate));
+    if let Some(percent) = discount_percent {
+        w.text("inv:discountPercentage", percent);
+    }
+    w.start(if foreign { "inv:foreignCurrency" } else { "inv:homeCurrency" }, &[]);
+    w.text("typ:unitPrice", unit_price);
+    w.end();
+    w.end();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dao::InvoiceRow;
+    use crate::fixtures::{document, final_invoice, row};
+    use crate::logic::export::test::assert_in_order;
+
+    #[test]
+    fn test_data_pack() {
+        let mut document = document(InvoiceKind::Invoice);
+        document.contact.name = "Odběratel & spol.".to_string();
+        document.invoice.payed = Some(NaiveDate::from_ymd(2022, 3, 20));
+        document.invoice.discount_percent = None;
+        document.invoice.discount_amount = Some(Money::from_int(100));
+
+        let row = |name: &str, rate, discount_percent, discount_amount| InvoiceRow {
+            item_name: name.to_string(),
+            item_unit: Some("h".to_string()),
+            discount_percent,
+            discount_amount,
+            ..row(2, "1000", "2", rate)
+        };
+        document.rows = vec![
+            row("Práce", Some(21), Some(Percent::from_int(10)), None),
+            row("Kniha", Some(12), None, Some(Money::from_int(50))),
+        ];
+        let entrepreneur = document.entrepreneur.clone();
+
+        let xml = create(
+            &entrepreneur,
+            NaiveDate::from_ymd(2022, 1, 1),
+            NaiveDate::from_ymd(2022, 3, 31),
+            &[document.clone()],
+        );
+
+        assert!(xml.contains("<dat:dataPack xmlns:dat=\"http://www.stormware.cz/schema/version_2/data.xsd\""));
+        assert!(xml.contains("ico=\"12345678\""));
+        assert!(xml.contains("<inv:invoiceType>issuedInvoice</inv:invoiceType>"));
+        assert!(xml.contains("<typ:numberRequested>2022-0002</typ:numberRequested>"));
+        assert!(xml.contains("<inv:symVar>20220002</inv:symVar>"));
+        assert!(xml.contains("<typ:accountNo>19-2000145399</typ:accountNo>"));
+        assert!(xml.contains("<typ:bankCode>0800</typ:bankCode>"));
+        assert!(xml.contains("<typ:company>Odběratel &amp; spol.</typ:company>"));
+        assert!(xml.contains("<typ:street>Dlouhá 1</typ:street>"));
+        assert!(xml.contains("<inv:intNote>Uhrazeno 2022-03-20</inv:intNote>"));
+        assert!(xml.contains("<inv:discountPercentage>10.00</inv:discountPercentage>"));
+        // the fixed row discount and the invoice discount (split 1800 : 1950) become items on their own
+        assert!(xml.contains("<inv:text>Sleva - Kniha</inv:text>"));
+        assert!(xml.contains("<typ:unitPrice>-50.00</typ:unitPrice>"));
+        assert!(xml.contains("<typ:unitPrice>-48.00</typ:unitPrice>"));
+        assert!(xml.contains("<typ:unitPrice>-52.00</typ:unitPrice>"));
+        assert!(xml.contains("<typ:priceHigh>1752.00</typ:priceHigh>"));
+        assert!(xml.contains("<typ:priceLow>1898.00</typ:priceLow>"));
+
+        // in a foreign currency, Pohoda gets the rate
+        document.invoice.currency_code = "EUR".to_string();
+        document.invoice.exchange_rate = "24.5".parse().unwrap();
+
+        let xml = create(
+            &entrepreneur,
+            NaiveDate::from_ymd(2022, 1, 1),
+            NaiveDate::from_ymd(2022, 3, 31),
+            &[document],
+        );
+
+        assert!(xml.contains("<typ:ids>EUR</typ:ids>"));
+        assert!(xml.contains("<typ:rate>24.500000</typ:rate>"));
+        assert!(!xml.contains("<inv:homeCurrency>"));
+    }
+    #[test]
+    fn test_final_invoice() {
+        let document = final_invoice();
+        let date = document.invoice.created;
+
+        let xml = create(&document.entrepreneur.clone(), date, date, &[document]);
+
+        assert_in_order(
+            &xml,
+            &[
+                "inv:invoiceItem",
+                "inv:invoiceAdvancePaymentItem",
+                "inv:sourceDocument",
+                "inv:rateVAT",
+            ],
+        );
+        assert!(xml.contains("<typ:number>2022-0001</typ:number>"));
+        assert!(xml.contains("<typ:unitPrice>-1000.00</typ:unitPrice>"));
+        // the rows of the proforma keep their VAT
+        assert!(xml.contains("<inv:rateVAT>high</inv:rateVAT>"));
+    }
+}
@@ -0,0 +1,441 @@
+//! UBL 2.1 invoice following the Peppol BIS Billing 3.0 specification (<https://docs.peppol.eu/poacc/billing/3.0/>).
+
+use err_context::AnyError;
+
+use crate::dao::{Contact, Entrepreneur, InvoiceKind, Money, Vat};
+use crate::logic::bank_account::BankAccount;
+use crate::logic::export::xml::XmlWriter;
+use crate::logic::export::{catalog, country_code, exemption_reason, tax_category, type_code, unit_code, PostalAddress};
+use crate::logic::invoices::variable_symbol;
+use crate::logic::pdf::i18n::Catalog;
+use crate::logic::vat::{self, VatRecap};
+use crate::logic::InvoiceDocument;
+
+const INVOICE_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+const CREDIT_NOTE_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2";
+const CAC_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+const CBC_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+const CUSTOMIZATION_ID: &str = "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0";
+const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+/// Fails when a party has no electronic address (Peppol needs one for both of them).
+pub fn create(document: &InvoiceDocument) -> Result<String, AnyError> {
+    let InvoiceDocument {
+        entrepreneur,
+        bank_account,
+        contact,
+        invoice,
+        original,
+        deposit,
+        rows,
+        ..
+    } = document;
+
+    let seller_endpoint = seller_endpoint(entrepreneur)
+        .ok_or_else(|| AnyError::from("Entrepreneur needs a VAT number or an e-mail to be addressed in Peppol"))?;
+    let buyer_endpoint = endpoint(&contact.vat).ok_or_else(|| AnyError::from("Contact needs a VAT number to be addressed in Peppol"))?;
+
+    let vat_recap = VatRecap::new(invoice, rows);
+    let currency = invoice.currency_code.as_str();
+    let texts = catalog(document);
+    let credit_note = invoice.kind == InvoiceKind::CreditNote;
+
+    // rows of credit notes are negative, while the credit note itself states positive amounts
+    let sign = |amount: Money| if credit_note { -amount } else { amount };
+    let category = |rate| tax_category(&entrepreneur.vat, rate);
+    let amount = |w: &mut XmlWriter, name: &str, amount: Money| {
+        w.text_with(name, &[("currencyID", currency)], sign(amount));
+    };
+
+    let mut w = XmlWriter::default();
+
+    w.start(
+        if credit_note { "CreditNote" } else { "Invoice" },
+        &[
+            ("xmlns", if credit_note { CREDIT_NOTE_NAMESPACE } else { INVOICE_NAMESPACE }),
+            ("xmlns:cac", CAC_NAMESPACE),
+            ("xmlns:cbc", CBC_NAMESPACE),
+        ],
+    );
+
+    w.text("cbc:CustomizationID", CUSTOMIZATION_ID);
+    w.text("cbc:ProfileID", PROFILE_ID);
+    w.text("cbc:ID", &invoice.code);
+    w.text("cbc:IssueDate", invoice.created);
+    if credit_note {
+        w.text("cbc:CreditNoteTypeCode", type_code(invoice.kind));
+    } else {
+        w.text("cbc:DueDate", invoice.pay_until);
+        w.text("cbc:InvoiceTypeCode", type_code(invoice.kind));
+    }
+    w.text("cbc:DocumentCurrencyCode", currency);
+    // there's no reference of the buyer to use, the document is referenced by its own code then
+    w.text("cbc:BuyerReference", &invoice.code);
+
+    if let Some(original) = original {
+        w.start("cac:BillingReference", &[]);
+        w.start("cac:InvoiceDocumentReference", &[]);
+        w.text("cbc:ID", &original.code);
+        w.text("cbc:IssueDate", original.created);
+        w.end();
+        w.end();
+    }
+
+    w.start("cac:AccountingSupplierParty", &[]);
+    seller_party(&mut w, entrepreneur, seller_endpoint);
+    w.end();
+
+    w.start("cac:AccountingCustomerParty", &[]);
+    // documents out of the scope of VAT can't carry any VAT identifier (BR-O-02)
+    let buyer_vat = matches!(entrepreneur.vat, Vat::Code(_));
+    buyer_party(&mut w, contact, buyer_endpoint, buyer_vat);
+    w.end();
+
+    if invoice.kind != InvoiceKind::Proforma {
+        w.start("cac:Delivery", &[]);
+        w.text("cbc:ActualDeliveryDate", invoice.created);
+        w.end();
+    }
+
+    if credit_note {
+        w.start("cac:PaymentTerms", &[]);
+        w.text(
+            "cbc:Note",
+            format!("Vrácení platby do {}", invoice.pay_until.format("%d.%m.%Y")), // TODO hard code value
+        );
+        w.end();
+    } else {
+        payment_means(&mut w, bank_account.as_ref(), &variable_symbol(invoice));
+    }
+
+    for recap_line in vat_recap.lines.iter().filter(|l| l.discount != Money::ZERO) {
+        w.start("cac:AllowanceCharge", &[]);
+        w.text("cbc:ChargeIndicator", false);
+        w.text("cbc:AllowanceChargeReason", "Sleva na fakturu"); // TODO hard code value
+        amount(&mut w, "cbc:Amount", recap_line.discount);
+        tax_category_element(&mut w, "cac:TaxCategory", category(recap_line.rate), recap_line.rate, texts);
+        w.end();
+    }
+
+    w.start("cac:TaxTotal", &[]);
+    amount(&mut w, "cbc:TaxAmount", vat_recap.vat);
+    for recap_line in &vat_recap.lines {
+        w.start("cac:TaxSubtotal", &[]);
+        amount(&mut w, "cbc:TaxableAmount", recap_line.base);
+        amount(&mut w, "cbc:TaxAmount", recap_line.vat);
+        tax_category_element(&mut w, "cac:TaxCategory", category(recap_line.rate), recap_line.rate, texts);
+        w.end();
+    }
+    w.end();
+
+    w.start("cac:LegalMonetaryTotal", &[]);
+    amount(&mut w, "cbc:LineExtensionAmount", vat_recap.subtotal);
+    amount(&mut w, "cbc:TaxExclusiveAmount", vat_recap.base);
+    amount(&mut w, "cbc:TaxInclusiveAmount", vat_recap.gross);
+    amount(&mut w, "cbc:AllowanceTotalAmount", vat_recap.discount);
+    if let Some(deposit) = deposit {
+        amount(&mut w, "cbc:PrepaidAmount", deposit.amount);
+    }
+    amount(&mut w, "cbc:PayableAmount", document.payable(&vat_recap));
+    w.end();
+
+    for (i, row) in rows.iter().enumerate() {
+        let rate = row.vat_rate.map(|r| r as u16);
+        let discount = vat::row_discount(row);
+
+        w.start(if credit_note { "cac:CreditNoteLine" } else { "cac:InvoiceLine" }, &[]);
+        w.text("cbc:ID", i + 1);
+        w.text_with(
+            if credit_note {
+                "cbc:CreditedQuantity"
+            } else {
+                "cbc:InvoicedQuantity"
+            },
+            &[("unitCode", unit_code(row.item_unit.as_deref()))],
+            row.item_count,
+        );
+        amount(&mut w, "cbc:LineExtensionAmount", vat::row_net_price(row));
+        if discount != Money::ZERO {
+            w.start("cac:AllowanceCharge", &[]);
+            w.text("cbc:ChargeIndicator", false);
+            w.text("cbc:AllowanceChargeReason", "Sleva"); // TODO hard code value
+            amount(&mut w, "cbc:Amount", discount);
+            w.end();
+        }
+        w.start("cac:Item", &[]);
+        w.text("cbc:Name", &row.item_name);
+        tax_category_element(&mut w, "cac:ClassifiedTaxCategory", category(rate), rate, texts);
+        w.end();
+        w.start("cac:Price", &[]);
+        amount(&mut w, "cbc:PriceAmount", row.item_price);
+        w.end();
+        w.end();
+    }
+
+    w.end();
+
+    Ok(w.finish())
+}
+
+/// Electronic address of a party - its VAT number, with the scheme of the country (from the Peppol EAS code list).
+fn endpoint(vat: &Vat) -> Option<(&'static str, &str)> {
+    match vat {
+        Vat::Code(code) if code.starts_with("CZ") => Some(("9929", code)),
+        Vat::Code(code) if code.starts_with("SK") => Some(("9950", code)),
+        _ => None,
+    }
+}
+
+/// Entrepreneurs without a VAT number can be addressed by their e-mail.
+fn seller_endpoint(entrepreneur: &Entrepreneur) -> Option<(&'static str, &str)> {
+    endpoint(&entrepreneur.vat).or_else(|| entrepreneur.email.as_deref().map(|email| ("EM", email)))
+}
+
+fn tax_category_element(w: &mut XmlWriter, name: &str, category: &str, rate: Option<u16>, texts: &Catalog) {
+    w.start(name, &[]);
+    w.text("cbc:ID", category);
+    if category != "O" {
+        w.text("cbc:Percent", rate.unwrap_or_default());
+    }
+    if let Some(reason) = exemption_reason(category, texts) {
+        // only the document level categories carry the reason
+        if name == "cac:TaxCategory" {
+            w.text("cbc:TaxExemptionReason", reason);
+        }
+    }
+    w.start("cac:TaxScheme", &[]);
+    w.text("cbc:ID", "VAT");
+    w.end();
+    w.end();
+}
+
+fn seller_party(w: &mut XmlWriter, entrepreneur: &Entrepreneur, endpoint: (&str, &str)) {
+    w.start("cac:Party", &[]);
+    w.text_with("cbc:EndpointID", &[("schemeID", endpoint.0)], endpoint.1);
+    w.start("cac:PartyName", &[]);
+    w.text("cbc:Name", &entrepreneur.name);
+    w.end();
+    postal_address(w, &entrepreneur.address, &entrepreneur.vat);
+    tax_scheme(w, &entrepreneur.vat);
+    legal_entity(w, &entrepreneur.name, Some(&entrepreneur.code));
+    if entrepreneur.phone.is_some() || entrepreneur.email.is_some() {
+        w.start("cac:Contact", &[]);
+        if let Some(phone) = &entrepreneur.phone {
+            w.text("cbc:Telephone", phone);
+        }
+        if let Some(email) = &entrepreneur.email {
+            w.text("cbc:ElectronicMail", email);
+        }
+        w.end();
+    }
+    w.end();
+}
+
+fn buyer_party(w: &mut XmlWriter, contact: &Contact, endpoint: (&str, &str), with_vat: bool) {
+    w.start("cac:Party", &[]);
+    w.text_with("cbc:EndpointID", &[("schemeID", endpoint.0)], endpoint.1);
+    w.start("cac:PartyName", &[]);
+    w.text("cbc:Name", &contact.name);
+    w.end();
+    postal_address(w, &contact.address, &contact.vat);
+    if with_vat {
+        tax_scheme(w, &contact.vat);
+    }
+    legal_entity(w, &contact.name, contact.code.as_deref());
+    w.end();
+}
+
+/// Peppol doesn't allow empty elements, so unknown parts of the address are left out.
+fn postal_address(w: &mut XmlWriter, address: &str, vat: &Vat) {
+    let address = PostalAddress::parse(address);
+    let street = format!("{} {}", address.street, address.building_number);
+
+    w.start("cac:PostalAddress", &[]);
+    if !street.trim().is_empty() {
+        w.text("cbc:StreetName", street.trim());
+    }
+    if !address.city.is_empty() {
+        w.text("cbc:CityName", address.city);
+    }
+    if !address.postal_zone.is_empty() {
+        w.text("cbc:PostalZone", address.postal_zone);
+    }
+    w.start("cac:Country", &[]);
+    w.text("cbc:IdentificationCode", country_code(vat));
+    w.end();
+    w.end();
+}
+
+fn tax_scheme(w: &mut XmlWriter, vat: &Vat) {
+    if let Vat::Code(code) = vat {
+        w.start("cac:PartyTaxScheme", &[]);
+        w.text("cbc:CompanyID", code);
+        w.start("cac:TaxScheme", &[]);
+        w.text("cbc:ID", "VAT");
+        w.end();
+        w.end();
+    }
+}
+
+fn legal_entity(w: &mut XmlWriter, name: &str, code: Option<&str>) {
+    w.start("cac:PartyLegalEntity", &[]);
+    w.text("cbc:RegistrationName", name);
+    if let Some(code) = code {
+        w.text("cbc:CompanyID", code);
+    }
+    w.end();
+}
+
+fn payment_means(w: &mut XmlWriter, account: Option<&BankAccount>, vs: &str) {
+    let account = match account {
+        Some(account) => account,
+        None => return,
+    };
+
+    w.start("cac:PaymentMeans", &[]);
+    w.text("cbc:PaymentMeansCode", 30); // credit transfer
+    w.text("cbc:PaymentID", vs);
+    w.start("cac:PayeeFinancialAccount", &[]);
+    w.text("cbc:ID", account.iban.as_str());
+    if let Some(bic) = &account.bic {
+        w.start("cac:FinancialInstitutionBranch", &[]);
+        w.text("cbc:ID", bic);
+        w.end();
+    }
+    w.end();
+    w.end();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{document, final_invoice};
+    use crate::logic::export::test::{assert_in_order, validate_schema};
+
+    const INVOICE_SCHEMA: &str = "schemas/ubl-2.1/maindoc/UBL-Invoice-2.1.xsd";
+    const CREDIT_NOTE_SCHEMA: &str = "schemas/ubl-2.1/maindoc/UBL-CreditNote-2.1.xsd";
+
+    fn validate(xml: &str) -> Result<(), String> {
+        let schema = if xml.contains("<CreditNote ") {
+            CREDIT_NOTE_SCHEMA
+        } else {
+            INVOICE_SCHEMA
+        };
+
+        validate_schema(xml, schema)
+    }
+
+    fn assert_valid(xml: &str) {
+        if let Err(errors) = validate(xml) {
+            panic!("UBL schema failed: {}\n{}", errors, xml);
+        }
+    }
+
+    /// Document with an electronic address of the customer.
+    fn addressed(mut document: InvoiceDocument) -> InvoiceDocument {
+        document.contact.vat = Vat::Code("CZ87654321".to_string());
+        document
+    }
+
+    #[test]
+    fn test_invoice() {
+        let document = addressed(document(InvoiceKind::Invoice));
+
+        let xml = create(&document).unwrap();
+
+        assert_in_order(
+            &xml,
+            &[
+                "cbc:CustomizationID",
+                "cbc:ProfileID",
+                "cbc:ID",
+                "cbc:IssueDate",
+                "cbc:DueDate",
+                "cbc:InvoiceTypeCode",
+                "cbc:DocumentCurrencyCode",
+                "cbc:BuyerReference",
+                "cac:AccountingSupplierParty",
+                "cac:AccountingCustomerParty",
+                "cac:Delivery",
+                "cac:PaymentMeans",
+                "cac:AllowanceCharge",
+                "cac:TaxTotal",
+                "cac:LegalMonetaryTotal",
+                "cac:InvoiceLine",
+            ],
+        );
+        assert!(xml.contains("<cbc:EndpointID schemeID=\"9929\">CZ87654321</cbc:EndpointID>"));
+        assert!(xml.contains("<cbc:ID>CZ6508000000192000145399</cbc:ID>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"CZK\">2480.40</cbc:PayableAmount>"));
+    }
+
+    #[test]
+    fn test_final_invoice() {
+        let document = addressed(final_invoice());
+
+        let xml = create(&document).unwrap();
+
+        assert!(xml.find("cbc:PrepaidAmount") < xml.find("cbc:PayableAmount"));
+        assert!(xml.contains("<cbc:TaxAmount currencyID=\"CZK\">410.40</cbc:TaxAmount>"));
+        assert!(xml.contains("<cbc:PrepaidAmount currencyID=\"CZK\">1000.00</cbc:PrepaidAmount>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"CZK\">1480.40</cbc:PayableAmount>"));
+    }
+
+    #[test]
+    fn test_credit_note() {
+        let mut document = addressed(document(InvoiceKind::CreditNote));
+        document.rows.iter_mut().for_each(|row| row.item_price = -row.item_price);
+
+        let xml = create(&document).unwrap();
+
+        assert!(xml.contains("<CreditNote xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2\""));
+        assert!(xml.contains("<cbc:CreditNoteTypeCode>381</cbc:CreditNoteTypeCode>"));
+        assert!(xml.contains("<cbc:ID>2022-0001</cbc:ID>"));
+        assert!(!xml.contains(">-"));
+    }
+
+    #[test]
+    fn test_not_vat_payer() {
+        let mut document = document(InvoiceKind::Invoice);
+        document.entrepreneur.vat = Vat::NotTaxPayer;
+        document.contact.vat = Vat::Code("SK2020123456".to_string());
+        document.rows.iter_mut().for_each(|row| row.vat_rate = None);
+
+        let xml = create(&document).unwrap();
+
+        assert!(xml.contains("<cbc:EndpointID schemeID=\"EM\">jan@novak.cz</cbc:EndpointID>"));
+        assert!(xml.contains("<cbc:ID>O</cbc:ID>"));
+
+        document.contact.vat = Vat::NotTaxPayer;
+        assert!(create(&document).is_err());
+    }
+
+    #[test]
+    fn test_against_schema() {
+        let mut credit_note = addressed(document(InvoiceKind::CreditNote));
+        credit_note.rows.iter_mut().for_each(|row| row.item_price = -row.item_price);
+
+        let mut not_vat_payer = addressed(document(InvoiceKind::Invoice));
+        not_vat_payer.entrepreneur.vat = Vat::NotTaxPayer;
+        not_vat_payer.rows.iter_mut().for_each(|row| row.vat_rate = None);
+
+        let documents = [
+            addressed(document(InvoiceKind::Invoice)),
+            addressed(final_invoice()),
+            credit_note,
+            not_vat_payer,
+        ];
+
+        for document in documents {
+            assert_valid(&create(&document).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_schema_catches_errors() {
+        let xml = create(&addressed(document(InvoiceKind::Invoice))).unwrap();
+        let xml = xml.replace("cbc:IssueDate>", "cbc:IssuedOn>");
+
+        assert!(validate(&xml).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::Display;
+
+/// Writer of small, indented XML documents - elements contain either other elements or a text, never both.
+#[derive(Default)]
+pub struct XmlWriter {
+    out: String,
+    open: Vec<String>,
+}
+
+impl XmlWriter {
+    pub fn start(&mut self, name: &str, attributes: &[(&str, &str)]) -> &mut Self {
+        self.indent();
+        self.tag(name, attributes);
+        self.out.push_str(">\n");
+        self.open.push(name.to_string());
+        self
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        let name = self.open.pop().expect("No element to end");
+        self.indent();
+        self.out.push_str(&format!("</{}>\n", name));
+        self
+    }
+
+    /// Element with just the text.
+    pub fn text(&mut self, name: &str, text: impl Display) -> &mut Self {
+        self.text_with(name, &[], text)
+    }
+
+    pub fn text_with(&mut self, name: &str, attributes: &[(&str, &str)], text: impl Display) -> &mut Self {
+        self.indent();
+        self.tag(name, attributes);
+        self.out.push_str(&format!(">{}</{}>\n", escape(&text.to_string()), name));
+        self
+    }
+
+    /// Element without any content.
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) -> &mut Self {
+        self.indent();
+        self.tag(name, attributes);
+        self.out.push_str("/>\n");
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        while !self.open.is_empty() {
+            self.end();
+        }
+
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", self.out)
+    }
+
+    fn tag(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.out.push('<');
+        self.out.push_str(name);
+
+        for (key, value) in attributes {
+            self.out.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+    }
+
+    fn indent(&mut self) {
+        self.out.push_str(&"  ".repeat(self.open.len()));
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_writer() {
+        let mut w = XmlWriter::default();
+        w.start("Invoice", &[("xmlns", "urn:test")]);
+        w.text("Name", "Novák & syn <s.r.o.>");
+        w.empty("Empty", &[("attr", "\"quoted\"")]);
+        w.start("Lines", &[]).text_with("Line", &[("id", "1")], 42);
+
+        assert_eq!(
+            w.finish(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Invoice xmlns=\"urn:test\">\n  \
+             <Name>Novák &amp; syn &lt;s.r.o.&gt;</Name>\n  \
+             <Empty attr=\"&quot;quoted&quot;\"/>\n  \
+             <Lines>\n    \
+             <Line id=\"1\">42</Line>\n  \
+             </Lines>\n\
+             </Invoice>\n"
+        );
+    }
+}
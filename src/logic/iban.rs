@@ -1,81 +1,350 @@
+//! IBANs of the SEPA countries, validated against the formats of the IBAN registry, and the domestic account numbers
+//! of Czechia and Slovakia (`19-2000145399/0800`) the IBANs of those countries are derived from.
+
+use std::fmt;
+use std::str::FromStr;
+
 use err_context::AnyError;
 
-pub fn create(country_code: &str, account_prefix: Option<u64>, account: u64, bank_code: u16) -> Result<String, AnyError> {
-    match country_code {
-        "CZ" | "SK" => {
-            let account_prefix = account_prefix.unwrap_or_default();
+/// BBAN formats of the IBAN registry - lengths of the parts of `n` digits, `a` capital letters or `c` alphanumerics.
+const FORMATS: [(&str, &str); 37] = [
+    ("AD", "4!n4!n12!c"),
+    ("AT", "16!n"),
+    ("BE", "12!n"),
+    ("BG", "4!a6!n8!c"),
+    ("CH", "5!n12!c"),
+    ("CY", "8!n16!c"),
+    ("CZ", "20!n"),
+    ("DE", "18!n"),
+    ("DK", "14!n"),
+    ("EE", "16!n"),
+    ("ES", "20!n"),
+    ("FI", "14!n"),
+    ("FR", "10!n11!c2!n"),
+    ("GB", "4!a14!n"),
+    ("GI", "4!a15!c"),
+    ("GR", "7!n16!c"),
+    ("HR", "17!n"),
+    ("HU", "24!n"),
+    ("IE", "4!a14!n"),
+    ("IS", "22!n"),
+    ("IT", "1!a10!n12!c"),
+    ("LI", "5!n12!c"),
+    ("LT", "16!n"),
+    ("LU", "3!n13!c"),
+    ("LV", "4!a13!c"),
+    ("MC", "10!n11!c2!n"),
+    ("MT", "4!a5!n18!c"),
+    ("NL", "4!a10!n"),
+    ("NO", "11!n"),
+    ("PL", "24!n"),
+    ("PT", "21!n"),
+    ("RO", "4!a16!c"),
+    ("SE", "20!n"),
+    ("SI", "15!n"),
+    ("SK", "20!n"),
+    ("SM", "1!a10!n12!c"),
+    ("VA", "18!n"),
+];
+
+/// Weights of the mod-11 check of the Czech and Slovak account numbers, the prefix uses the last six of them.
+const WEIGHTS: [u64; 10] = [6, 3, 7, 9, 10, 5, 8, 4, 2, 1];
+
+/// IBAN in the electronic format (`CZ6508000000192000145399`), displayed in the paper one (groups of four).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Iban(String);
+
+/// Czech or Slovak account number - the prefix of up to 6 digits, the number of up to 10 digits and the bank code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DomesticAccount {
+    pub prefix: Option<u32>,
+    pub number: u64,
+    pub bank_code: u16,
+}
+
+impl Iban {
+    /// IBAN of a Czech or Slovak account.
+    pub fn from_domestic(country_code: &str, account: &DomesticAccount) -> Result<Self, AnyError> {
+        match country_code {
+            "CZ" | "SK" => {
+                let bban = format!(
+                    "{:04}{:06}{:010}",
+                    account.bank_code,
+                    account.prefix.unwrap_or_default(),
+                    account.number
+                );
+                format!("{}{:02}{}", country_code, checksum(country_code, &bban), bban).parse()
+            }
+            _ => Err(AnyError::from(format!("Unsupported country code: {}", country_code))),
+        }
+    }
+
+    pub fn country_code(&self) -> &str {
+        &self.0[..2]
+    }
+
+    /// The electronic format.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The Czech or Slovak account of the IBAN, none for the other countries.
+    pub fn domestic(&self) -> Option<DomesticAccount> {
+        match self.country_code() {
+            "CZ" | "SK" => {
+                let bban = &self.0[4..];
+                let prefix = bban[4..10].parse().ok().filter(|p| *p != 0);
+                Some(DomesticAccount {
+                    prefix,
+                    number: bban[10..].parse().ok()?,
+                    bank_code: bban[..4].parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Iban {
+    type Err = AnyError;
+
+    /// Accepts both the electronic and the paper format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let iban = s.split_whitespace().collect::<String>().to_ascii_uppercase();
 
-            let mut iban = format!("{:04}{:06}{:010}{}00", bank_code, account_prefix, account, country_code);
-            let chsm = checksum(&iban);
-            iban.truncate(20); // strip "CZ00" suffix
+        if iban.len() < 5 || !iban.is_ascii() {
+            return Err(format!("Invalid IBAN {}", s).into());
+        }
+
+        let (country_code, bban) = (&iban[..2], &iban[4..]);
+        let format = FORMATS
+            .iter()
+            .find(|(c, _)| *c == country_code)
+            .map(|(_, format)| format)
+            .ok_or_else(|| format!("Unsupported country of IBAN {}", s))?;
+
+        if !iban[2..4].chars().all(|c| c.is_ascii_digit()) || !matches_format(bban, format) {
+            return Err(format!("Invalid format of IBAN {}", s).into());
+        }
+        if checksum(country_code, bban) != iban[2..4].parse::<u8>()? {
+            return Err(format!("Invalid checksum of IBAN {}", s).into());
+        }
 
-            Ok(format!("{}{}{}", country_code, chsm, iban))
+        let iban = Iban(iban);
+        if let Some(account) = iban.domestic() {
+            if !account.is_valid() {
+                return Err(format!("Invalid account number of IBAN {}", s).into());
+            }
         }
-        _ => Err(AnyError::from(format!("Unsupported country code: {}", country_code))),
+
+        Ok(iban)
+    }
+}
+
+impl fmt::Display for Iban {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups = self.0.as_bytes().chunks(4).map(|g| String::from_utf8_lossy(g)).collect::<Vec<_>>();
+        write!(f, "{}", groups.join(" "))
+    }
+}
+
+impl DomesticAccount {
+    /// Both the prefix and the number pass the mod-11 check, and fit into their lengths.
+    pub fn is_valid(&self) -> bool {
+        let prefix = self.prefix.unwrap_or_default() as u64;
+
+        prefix < 1_000_000
+            && self.number != 0
+            && self.number < 10_000_000_000
+            && self.bank_code < 10_000
+            && remainder(prefix) == 0
+            && remainder(self.number) == 0
+    }
+
+    /// The account number without the bank code.
+    pub fn number_with_prefix(&self) -> String {
+        match self.prefix {
+            Some(prefix) => format!("{}-{}", prefix, self.number),
+            None => self.number.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DomesticAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{:04}", self.number_with_prefix(), self.bank_code)
     }
 }
 
-fn checksum(iban: &str) -> u8 {
-    // This code is more-or-less copied from iban_validate crate
-    let modulo = iban.as_bytes().iter().fold(0, |acc, c| {
-        // Convert '0'-'Z' to 0-35
-        let digit = (*c as char)
-            .to_digit(36)
-            .expect("An address was supplied to compute_checksum with an invalid character.");
-        // If the number consists of two digits, multiply by 100
+/// BIC (SWIFT code) - 4 letters of the bank, 2 of the country, 2 alphanumerics of the location and optionally 3 of
+/// the branch.
+pub fn is_valid_bic(bic: &str) -> bool {
+    let bytes = bic.as_bytes();
+
+    matches!(bytes.len(), 8 | 11)
+        && bytes[..6].iter().all(u8::is_ascii_uppercase)
+        && bytes[6..].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+fn matches_format(bban: &str, format: &str) -> bool {
+    let mut rest = bban.as_bytes();
+
+    for part in format.split_inclusive(|c: char| c.is_ascii_lowercase()) {
+        let (length, kind) = part.split_at(part.len() - 1);
+        let length = length.trim_end_matches('!').parse::<usize>().expect("The formats must be valid");
+
+        if rest.len() < length {
+            return false;
+        }
+        let (chars, next) = rest.split_at(length);
+        let valid = match kind {
+            "n" => chars.iter().all(u8::is_ascii_digit),
+            "a" => chars.iter().all(u8::is_ascii_uppercase),
+            _ => chars.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()),
+        };
+        if !valid {
+            return false;
+        }
+        rest = next;
+    }
+
+    rest.is_empty()
+}
+
+/// The check digits of the IBAN - the BBAN followed by the country and `00`, taken as a number in base 36, mod 97.
+fn checksum(country_code: &str, bban: &str) -> u8 {
+    let modulo = format!("{}{}00", bban, country_code).chars().fold(0, |acc, c| {
+        // letters are two digits long
+        let digit = c.to_digit(36).expect("The IBAN must be alphanumeric");
         let multiplier = if digit > 9 { 100 } else { 10 };
-        // Calculate modulo
         (acc * multiplier + digit) % 97
     });
 
     98 - modulo as u8
 }
 
+/// Remainder of the sum of the digits multiplied by their weights, divided by 11.
+fn remainder(number: u64) -> u64 {
+    WEIGHTS
+        .iter()
+        .rev()
+        .scan(number, |rest, weight| {
+            let digit = *rest % 10;
+            *rest /= 10;
+            Some(digit * weight)
+        })
+        .sum::<u64>()
+        % 11
+}
+
 #[cfg(test)]
 mod tests {
-    use iban::Iban;
+    use super::*;
 
-    use crate::logic::iban::create;
+    fn domestic(prefix: Option<u32>, number: u64, bank_code: u16) -> DomesticAccount {
+        DomesticAccount { prefix, number, bank_code }
+    }
+
+    /// Our result for the IBAN is the one of the `iban_validate` crate.
+    fn assert_validity(iban: &str, valid: bool) {
+        assert_eq!(iban.parse::<Iban>().is_ok(), valid, "{}", iban);
+        assert_eq!(iban.parse::<iban::Iban>().is_ok(), valid, "{}", iban);
+    }
 
     #[test]
     fn basic1() {
-        let iban_expected = "CZ6508000000192000145399";
-
-        let iban_created = create("CZ", Some(19), 2000145399, 800).unwrap();
+        let iban_created = Iban::from_domestic("CZ", &domestic(Some(19), 2000145399, 800)).unwrap();
 
-        assert_eq!(iban_expected, iban_created.as_str());
-        iban_created.parse::<Iban>().unwrap(); // check it's valid
+        assert_eq!(iban_created.as_str(), "CZ6508000000192000145399");
+        assert_validity(iban_created.as_str(), true);
     }
 
     #[test]
     fn basic2() {
-        let iban_expected = "CZ6230300000001559929018";
+        let iban_created = Iban::from_domestic("CZ", &domestic(None, 1559929018, 3030)).unwrap();
 
-        let iban_created = create("CZ", None, 1559929018, 3030).unwrap();
-
-        assert_eq!(iban_expected, iban_created.as_str());
-        iban_created.parse::<Iban>().unwrap(); // check it's valid
+        assert_eq!(iban_created.as_str(), "CZ6230300000001559929018");
+        assert_validity(iban_created.as_str(), true);
     }
 
     #[test]
     fn basic3() {
-        let iban_expected = "CZ8830300000001311532017";
-
-        let iban_created = create("CZ", None, 1311532017, 3030).unwrap();
+        let iban_created = Iban::from_domestic("CZ", &domestic(None, 1311532017, 3030)).unwrap();
 
-        assert_eq!(iban_expected, iban_created.as_str());
-        iban_created.parse::<Iban>().unwrap(); // check it's valid
+        assert_eq!(iban_created.as_str(), "CZ8830300000001311532017");
+        assert_validity(iban_created.as_str(), true);
     }
 
     #[test]
     fn basic4_sk() {
-        let iban_expected = "SK3112000000198742637541";
+        let iban_created = Iban::from_domestic("SK", &domestic(Some(19), 8742637541, 1200)).unwrap();
 
-        let iban_created = create("SK", Some(19), 8742637541, 1200).unwrap();
+        assert_eq!(iban_created.as_str(), "SK3112000000198742637541");
+        assert_validity(iban_created.as_str(), true);
+    }
+
+    #[test]
+    fn test_sepa_countries() {
+        let ibans = [
+            "AT611904300234573201",
+            "BE68539007547034",
+            "CH9300762011623852957",
+            "DE89370400440532013000",
+            "ES9121000418450200051332",
+            "FR1420041010050500013M02606",
+            "GB29NWBK60161331926819",
+            "IT60X0542811101000000123456",
+            "MT84MALT011000012345MTLCAST001S",
+            "NL91ABNA0417164300",
+            "NO9386011117947",
+            "PL61109010140000071219812874",
+        ];
 
-        assert_eq!(iban_expected, iban_created.as_str());
-        iban_created.parse::<Iban>().unwrap(); // check it's valid
+        for iban in ibans {
+            assert_validity(iban, true);
+            // the last check digit changed
+            let broken = format!("{}{}", &iban[..3], (iban.as_bytes()[3] - b'0' + 1) % 10);
+            assert_validity(&format!("{}{}", broken, &iban[4..]), false);
+        }
     }
-}
 
-// TODO fuzztest?
+    #[test]
+    fn test_formats() {
+        let iban: Iban = "de89 3704 0044 0532 0130 00".parse().unwrap();
+
+        assert_eq!(iban.as_str(), "DE89370400440532013000");
+        assert_eq!(iban.to_string(), "DE89 3704 0044 0532 0130 00");
+        assert_eq!(iban.country_code(), "DE");
+        assert_eq!(iban.domestic(), None);
+
+        // a letter in the numeric BBAN, a missing digit, an unknown country
+        assert!("DE8937040044053201300A".parse::<Iban>().is_err());
+        assert!("DE8937040044053201300".parse::<Iban>().is_err());
+        assert!("XX89370400440532013000".parse::<Iban>().is_err());
+    }
+
+    #[test]
+    fn test_domestic() {
+        let account = domestic(Some(19), 2000145399, 800);
+        let iban = Iban::from_domestic("CZ", &account).unwrap();
+
+        assert_eq!(iban.domestic(), Some(account));
+        assert_eq!(account.to_string(), "19-2000145399/0800");
+        assert_eq!(domestic(None, 1559929018, 3030).to_string(), "1559929018/3030");
+
+        // the numbers failing the mod-11 check
+        assert!(Iban::from_domestic("CZ", &domestic(Some(19), 2000145398, 800)).is_err());
+        assert!(Iban::from_domestic("CZ", &domestic(Some(18), 2000145399, 800)).is_err());
+        assert!(Iban::from_domestic("CZ", &domestic(None, 0, 800)).is_err());
+        assert!(Iban::from_domestic("DE", &account).is_err());
+    }
+
+    #[test]
+    fn test_bic() {
+        assert!(is_valid_bic("GIBACZPX"));
+        assert!(is_valid_bic("DEUTDEFF500"));
+        assert!(!is_valid_bic("GIBACZP"));
+        assert!(!is_valid_bic("gibaczpx"));
+        assert!(!is_valid_bic("G1BACZPX"));
+    }
+}
@@ -1,63 +1,491 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
 use err_context::AnyError;
+use frunk::LabelledGeneric;
+use itertools::Itertools;
 
-pub use inner::InvoiceNamingSchemaType;
 use inner::*;
+pub use inner::{InvoiceNamingSchema, InvoiceNamingSchemaType, InvoiceSeries};
 
-use crate::dao::{Account, Dao, Entrepreneur};
+use crate::dao::{Dao, DaoResult, Invoice, InvoiceCounter, InvoiceKind, InvoiceNumber};
 
-pub async fn next_code(
-    dao: &Dao,
-    account: &Account,
-    entrepreneur: &Entrepreneur,
-    naming_type: InvoiceNamingSchemaType,
-) -> Result<String, AnyError> {
+pub fn naming_schema(naming_type: &InvoiceNamingSchemaType, series: &InvoiceSeries) -> Result<Box<dyn InvoiceNamingSchema>, AnyError> {
     match naming_type {
-        InvoiceNamingSchemaType::Default => DefaultInvoiceNaming::next_code(dao, account, entrepreneur).await,
+        InvoiceNamingSchemaType::Default => Ok(Box::new(DefaultInvoiceNaming::new(series))),
+        InvoiceNamingSchemaType::Pattern { pattern, reset } => Ok(Box::new(PatternInvoiceNaming::new(pattern, *reset, series)?)),
     }
 }
 
+/// Variable symbol of payments of the invoice, the same on the document and in all its exports.
+///
+/// It can contain only (up to 10) digits, so it's the (last 10) digits of the code. A code without any digit (made of a
+/// pattern) uses the id of the invoice instead.
+pub fn variable_symbol(invoice: &Invoice) -> String {
+    let digits = invoice.code.chars().filter(char::is_ascii_digit).collect::<String>();
+
+    if digits.is_empty() {
+        invoice.id.to_string()
+    } else {
+        digits[digits.len().saturating_sub(10)..].to_string()
+    }
+}
+
+/// Numbers given out in a numbering period whose documents don't exist (anymore) - the law expects them to be explained.
+#[derive(LabelledGeneric, PartialEq, Debug, Clone)]
+pub struct NumberingGap {
+    pub kind: InvoiceKind,
+    /// First day of the numbering period, none if the numbering never starts again.
+    pub period_start: Option<NaiveDate>,
+    pub missing: Vec<u32>,
+}
+
+pub async fn get_numbering_gaps(dao: &Dao, entrepreneur_id: u32) -> DaoResult<Vec<NumberingGap>> {
+    let counters = dao.get_invoice_counters(entrepreneur_id).await?;
+    let numbers = dao.get_invoice_numbers(entrepreneur_id).await?;
+
+    Ok(numbering_gaps(&counters, &numbers))
+}
+
+/// Numbers given out before the counter existed aren't tracked, so they can't be checked.
+fn numbering_gaps(counters: &[InvoiceCounter], numbers: &[InvoiceNumber]) -> Vec<NumberingGap> {
+    let used: HashSet<(InvoiceKind, &str, i32)> = numbers.iter().map(|n| (n.kind, n.period.as_str(), n.number)).collect();
+
+    counters
+        .iter()
+        .filter_map(|counter| {
+            let missing = (counter.initial_value + 1..=counter.value)
+                .filter(|number| !used.contains(&(counter.kind, counter.period.as_str(), *number)))
+                .map(|number| number as u32)
+                .collect_vec();
+
+            if missing.is_empty() {
+                return None;
+            }
+
+            Some(NumberingGap {
+                kind: counter.kind,
+                period_start: NaiveDate::parse_from_str(&counter.period, "%Y-%m-%d").ok(),
+                missing,
+            })
+        })
+        .sorted_by(|a, b| a.period_start.cmp(&b.period_start))
+        .collect()
+}
+
 mod inner {
-    use async_trait::async_trait;
-    use chrono::{Datelike, Local};
+    use chrono::{Datelike, NaiveDate};
     use err_context::AnyError;
-    use log::debug;
     use serde::{Deserialize, Serialize};
 
-    use crate::dao::{Account, Dao, Entrepreneur};
+    use crate::dao::InvoiceKind;
 
-    #[async_trait]
+    /// Generates codes of new documents; every kind of document has its own series of numbers, counted in periods.
     pub trait InvoiceNamingSchema {
-        async fn next_code(dao: &Dao, account: &Account, entrepreneur: &Entrepreneur) -> Result<String, AnyError>;
+        /// First day of the numbering period the date belongs to, none if the numbering never starts again.
+        fn period_start(&self, date: NaiveDate) -> Option<NaiveDate>;
+
+        fn code(&self, date: NaiveDate, kind: InvoiceKind, number: u32) -> String;
+
+        /// Number of the `code` of a document created on the `date`, none if the code isn't of this schema.
+        fn number(&self, code: &str, date: NaiveDate, kind: InvoiceKind) -> Option<u32>;
     }
 
-    #[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+    /// Number between the `before` and `after` parts of the `code`, if the code is exactly the one of the number.
+    fn number_between(code: &str, before: &str, after: &str, of: impl Fn(u32) -> String) -> Option<u32> {
+        let digits = code.strip_prefix(before)?.strip_suffix(after)?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let number = digits.parse().ok()?;
+        (of(number) == code).then_some(number)
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
     pub enum InvoiceNamingSchemaType {
+        #[default]
         Default,
+        /// Codes rendered from a pattern like `{YYYY}-{SERIES}-{NNNN}`. The codes of a yearly counter need the year,
+        /// of a monthly one the year and the month.
+        Pattern {
+            pattern: String,
+            #[serde(default)]
+            reset: CounterReset,
+        },
+    }
+
+    /// When the counter of documents starts again from one.
+    #[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+    pub enum CounterReset {
+        #[default]
+        Yearly,
+        Monthly,
+        Never,
+    }
+
+    impl CounterReset {
+        fn period_start(self, date: NaiveDate) -> Option<NaiveDate> {
+            match self {
+                CounterReset::Yearly => Some(NaiveDate::from_ymd(date.year(), 1, 1)),
+                CounterReset::Monthly => Some(NaiveDate::from_ymd(date.year(), date.month(), 1)),
+                CounterReset::Never => None,
+            }
+        }
     }
 
-    impl Default for InvoiceNamingSchemaType {
+    /// Series of the kinds of documents, the `{SERIES}` placeholder of a pattern.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct InvoiceSeries {
+        #[serde(default = "InvoiceSeries::default_invoice")]
+        pub invoice: String,
+        #[serde(default = "InvoiceSeries::default_credit_note")]
+        pub credit_note: String,
+        #[serde(default = "InvoiceSeries::default_proforma")]
+        pub proforma: String,
+    }
+
+    impl InvoiceSeries {
+        fn default_invoice() -> String {
+            "FA".to_string()
+        }
+
+        fn default_credit_note() -> String {
+            "OD".to_string()
+        }
+
+        fn default_proforma() -> String {
+            "ZF".to_string()
+        }
+
+        fn of(&self, kind: InvoiceKind) -> &str {
+            match kind {
+                InvoiceKind::Invoice => &self.invoice,
+                InvoiceKind::CreditNote => &self.credit_note,
+                InvoiceKind::Proforma => &self.proforma,
+            }
+        }
+
+        /// Prepended to codes of patterns without the `{SERIES}` placeholder, so the series don't clash.
+        fn prefix(&self, kind: InvoiceKind) -> &str {
+            match kind {
+                InvoiceKind::Invoice => "",
+                kind => self.of(kind),
+            }
+        }
+    }
+
+    impl Default for InvoiceSeries {
         fn default() -> Self {
-            InvoiceNamingSchemaType::Default
+            InvoiceSeries {
+                invoice: Self::default_invoice(),
+                credit_note: Self::default_credit_note(),
+                proforma: Self::default_proforma(),
+            }
         }
     }
 
-    pub(super) struct DefaultInvoiceNaming;
+    /// `YYYYMM` with the yearly number of two digits (`20220307`), longer past 99 documents a year. Use a pattern for
+    /// a wider counter.
+    pub(super) struct DefaultInvoiceNaming {
+        series: InvoiceSeries,
+    }
+
+    impl DefaultInvoiceNaming {
+        pub fn new(series: &InvoiceSeries) -> Self {
+            Self { series: series.clone() }
+        }
+    }
 
-    #[async_trait]
     impl InvoiceNamingSchema for DefaultInvoiceNaming {
-        async fn next_code(dao: &Dao, _account: &Account, entrepreneur: &Entrepreneur) -> Result<String, AnyError> {
-            let max_id = dao.get_invoices_count(entrepreneur).await?;
-            let next_id = max_id + 1;
+        fn period_start(&self, date: NaiveDate) -> Option<NaiveDate> {
+            CounterReset::Yearly.period_start(date)
+        }
+
+        fn code(&self, date: NaiveDate, kind: InvoiceKind, number: u32) -> String {
+            format!("{}{}{:02}{:02}", self.series.prefix(kind), date.year(), date.month(), number)
+        }
+
+        fn number(&self, code: &str, date: NaiveDate, kind: InvoiceKind) -> Option<u32> {
+            let before = format!("{}{}{:02}", self.series.prefix(kind), date.year(), date.month());
+            number_between(code, &before, "", |number| self.code(date, kind, number))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Literal(String),
+        Year,
+        ShortYear,
+        Month,
+        Series,
+        Counter(usize),
+    }
 
-            let now = Local::now();
+    pub(super) struct PatternInvoiceNaming {
+        tokens: Vec<Token>,
+        reset: CounterReset,
+        series: InvoiceSeries,
+    }
+
+    impl PatternInvoiceNaming {
+        pub fn new(pattern: &str, reset: CounterReset, series: &InvoiceSeries) -> Result<Self, AnyError> {
+            let mut tokens = Vec::new();
+            let mut rest = pattern;
+
+            while let Some(start) = rest.find('{') {
+                if start > 0 {
+                    tokens.push(Token::Literal(rest[..start].to_string()));
+                }
+
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => return Err(AnyError::from(format!("Unclosed placeholder in pattern {}", pattern))),
+                };
+
+                tokens.push(match &rest[start + 1..end] {
+                    "YYYY" => Token::Year,
+                    "YY" => Token::ShortYear,
+                    "MM" => Token::Month,
+                    "SERIES" => Token::Series,
+                    n if !n.is_empty() && n.chars().all(|c| c == 'N') => Token::Counter(n.len()),
+                    other => return Err(AnyError::from(format!("Unknown placeholder {{{}}} in pattern {}", other, pattern))),
+                });
+
+                rest = &rest[end + 1..];
+            }
+
+            if !rest.is_empty() {
+                tokens.push(Token::Literal(rest.to_string()));
+            }
+
+            if tokens.iter().filter(|t| matches!(t, Token::Counter(_))).count() != 1 {
+                return Err(AnyError::from(format!("Pattern {} must contain exactly one counter", pattern)));
+            }
+
+            // the codes of the periods would repeat otherwise (and clash with the existing documents)
+            let year = tokens.contains(&Token::Year) || tokens.contains(&Token::ShortYear);
+            match reset {
+                CounterReset::Yearly if !year => {
+                    return Err(AnyError::from(format!(
+                        "Pattern {} of a yearly counter must contain the year",
+                        pattern
+                    )));
+                }
+                CounterReset::Monthly if !(year && tokens.contains(&Token::Month)) => {
+                    return Err(AnyError::from(format!(
+                        "Pattern {} of a monthly counter must contain the year and the month",
+                        pattern
+                    )));
+                }
+                _ => {}
+            }
+
+            Ok(Self {
+                tokens,
+                reset,
+                series: series.clone(),
+            })
+        }
+    }
+
+    impl InvoiceNamingSchema for PatternInvoiceNaming {
+        fn period_start(&self, date: NaiveDate) -> Option<NaiveDate> {
+            self.reset.period_start(date)
+        }
+
+        fn code(&self, date: NaiveDate, kind: InvoiceKind, number: u32) -> String {
+            let mut code = String::new();
 
-            // TODO next_id % 100?
+            if !self.tokens.contains(&Token::Series) {
+                code.push_str(self.series.prefix(kind));
+            }
+
+            code.push_str(&self.render(&self.tokens, date, kind, number));
+            code
+        }
 
-            let code = format!("{}{:02}{:02}", now.year(), now.month(), next_id);
+        fn number(&self, code: &str, date: NaiveDate, kind: InvoiceKind) -> Option<u32> {
+            let counter = self.tokens.iter().position(|t| matches!(t, Token::Counter(_)))?;
 
-            debug!("Generated code for invoice: {}", code);
+            let mut before = String::new();
+            if !self.tokens.contains(&Token::Series) {
+                before.push_str(self.series.prefix(kind));
+            }
+            before.push_str(&self.render(&self.tokens[..counter], date, kind, 0));
+            let after = self.render(&self.tokens[counter + 1..], date, kind, 0);
 
-            Ok(code)
+            number_between(code, &before, &after, |number| self.code(date, kind, number))
         }
     }
+
+    impl PatternInvoiceNaming {
+        fn render(&self, tokens: &[Token], date: NaiveDate, kind: InvoiceKind, number: u32) -> String {
+            let mut code = String::new();
+
+            for token in tokens {
+                match token {
+                    Token::Literal(s) => code.push_str(s),
+                    Token::Year => code.push_str(&format!("{}", date.year())),
+                    Token::ShortYear => code.push_str(&format!("{:02}", date.year() % 100)),
+                    Token::Month => code.push_str(&format!("{:02}", date.month())),
+                    Token::Series => code.push_str(self.series.of(kind)),
+                    Token::Counter(width) => code.push_str(&format!("{:0width$}", number, width = width)),
+                }
+            }
+
+            code
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn test_code() {
+            let date = NaiveDate::from_ymd(2022, 3, 15);
+
+            let series = InvoiceSeries::default();
+            let default = DefaultInvoiceNaming::new(&series);
+            assert_eq!(default.code(date, InvoiceKind::Invoice, 7), "20220307");
+            assert_eq!(default.code(date, InvoiceKind::CreditNote, 12), "OD20220312");
+            assert_eq!(default.code(date, InvoiceKind::Invoice, 123), "202203123");
+
+            let pattern = PatternInvoiceNaming::new("{YYYY}-{SERIES}-{NNNN}", CounterReset::Never, &series).unwrap();
+            assert_eq!(pattern.code(date, InvoiceKind::Invoice, 42), "2022-FA-0042");
+            assert_eq!(pattern.code(date, InvoiceKind::Proforma, 1), "2022-ZF-0001");
+
+            let short = PatternInvoiceNaming::new("V{YY}/{MM}/{NNN}", CounterReset::Monthly, &series).unwrap();
+            assert_eq!(short.code(date, InvoiceKind::Invoice, 5), "V22/03/005");
+            assert_eq!(short.code(date, InvoiceKind::CreditNote, 5), "ODV22/03/005");
+
+            let series = InvoiceSeries {
+                credit_note: "D".to_string(),
+                ..InvoiceSeries::default()
+            };
+            assert_eq!(
+                DefaultInvoiceNaming::new(&series).code(date, InvoiceKind::CreditNote, 1),
+                "D20220301"
+            );
+        }
+
+        #[test]
+        fn test_number() {
+            let date = NaiveDate::from_ymd(2022, 3, 15);
+            let series = InvoiceSeries::default();
+
+            let default = DefaultInvoiceNaming::new(&series);
+            assert_eq!(default.number("20220307", date, InvoiceKind::Invoice), Some(7));
+            assert_eq!(default.number("202203123", date, InvoiceKind::Invoice), Some(123));
+            assert_eq!(default.number("OD20220312", date, InvoiceKind::CreditNote), Some(12));
+            assert_eq!(default.number("OD20220312", date, InvoiceKind::Invoice), None);
+            assert_eq!(default.number("20220207", date, InvoiceKind::Invoice), None);
+            assert_eq!(default.number("2022030007", date, InvoiceKind::Invoice), None);
+
+            let pattern = PatternInvoiceNaming::new("{YYYY}-{SERIES}-{NNNN}/x", CounterReset::Yearly, &series).unwrap();
+            assert_eq!(pattern.number("2022-FA-0042/x", date, InvoiceKind::Invoice), Some(42));
+            assert_eq!(pattern.number("2022-FA-12345/x", date, InvoiceKind::Invoice), Some(12345));
+            assert_eq!(pattern.number("2022-ZF-0042/x", date, InvoiceKind::Invoice), None);
+            assert_eq!(pattern.number("2022-FA-42/x", date, InvoiceKind::Invoice), None);
+            assert_eq!(pattern.number("2021-FA-0042/x", date, InvoiceKind::Invoice), None);
+
+            let short = PatternInvoiceNaming::new("V{YY}/{MM}/{NNN}", CounterReset::Monthly, &series).unwrap();
+            assert_eq!(short.number("ODV22/03/005", date, InvoiceKind::CreditNote), Some(5));
+            assert_eq!(short.number("V22/03/005", date, InvoiceKind::CreditNote), None);
+        }
+
+        #[test]
+        fn test_invalid_pattern() {
+            assert!(PatternInvoiceNaming::new("{YYYY}", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{NN}-{NNN}", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{YYYY}-{NN", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{DD}-{NN}", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+        }
+
+        #[test]
+        fn test_pattern_of_period() {
+            assert!(PatternInvoiceNaming::new("{NNNN}", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{MM}-{NNNN}", CounterReset::Yearly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{YY}{NNNN}", CounterReset::Yearly, &InvoiceSeries::default()).is_ok());
+            assert!(PatternInvoiceNaming::new("{YYYY}-{NNNN}", CounterReset::Monthly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{MM}-{NNNN}", CounterReset::Monthly, &InvoiceSeries::default()).is_err());
+            assert!(PatternInvoiceNaming::new("{YYYY}{MM}-{NNNN}", CounterReset::Monthly, &InvoiceSeries::default()).is_ok());
+            assert!(PatternInvoiceNaming::new("{NNNN}", CounterReset::Never, &InvoiceSeries::default()).is_ok());
+        }
+
+        #[test]
+        fn test_period_start() {
+            let date = NaiveDate::from_ymd(2022, 3, 15);
+
+            assert_eq!(CounterReset::Yearly.period_start(date), Some(NaiveDate::from_ymd(2022, 1, 1)));
+            assert_eq!(CounterReset::Monthly.period_start(date), Some(NaiveDate::from_ymd(2022, 3, 1)));
+            assert_eq!(CounterReset::Never.period_start(date), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::invoice;
+
+    #[test]
+    fn test_variable_symbol() {
+        let with_code = |code: &str| Invoice {
+            code: code.to_string(),
+            ..invoice(42, InvoiceKind::Invoice)
+        };
+
+        assert_eq!(variable_symbol(&with_code("2022-0002")), "20220002");
+        assert_eq!(variable_symbol(&with_code("OD20220312")), "20220312");
+        assert_eq!(variable_symbol(&with_code("2022-FA-000000042")), "2000000042");
+        assert_eq!(variable_symbol(&with_code("FA-A")), "42");
+    }
+
+    #[test]
+    fn test_numbering_gaps() {
+        let counter = |kind, period: &str, initial_value, value| InvoiceCounter {
+            entrepreneur_id: 1,
+            kind,
+            period: period.to_string(),
+            initial_value,
+            value,
+        };
+        let number = |invoice_id, kind, period: &str, number| InvoiceNumber {
+            invoice_id,
+            entrepreneur_id: 1,
+            kind,
+            period: period.to_string(),
+            number,
+        };
+
+        let counters = vec![
+            counter(InvoiceKind::Invoice, "2022-01-01", 2, 6),
+            counter(InvoiceKind::CreditNote, "2022-01-01", 0, 1),
+            counter(InvoiceKind::Invoice, "2021-01-01", 0, 2),
+        ];
+        let numbers = vec![
+            number(1, InvoiceKind::Invoice, "2022-01-01", 3),
+            number(2, InvoiceKind::Invoice, "2022-01-01", 5),
+            number(3, InvoiceKind::CreditNote, "2022-01-01", 1),
+            number(4, InvoiceKind::Invoice, "2021-01-01", 1),
+        ];
+
+        assert_eq!(
+            numbering_gaps(&counters, &numbers),
+            vec![
+                NumberingGap {
+                    kind: InvoiceKind::Invoice,
+                    period_start: Some(NaiveDate::from_ymd(2021, 1, 1)),
+                    missing: vec![2],
+                },
+                NumberingGap {
+                    kind: InvoiceKind::Invoice,
+                    period_start: Some(NaiveDate::from_ymd(2022, 1, 1)),
+                    missing: vec![4, 6],
+                },
+            ]
+        );
+    }
 }
@@ -1,26 +1,113 @@
 use actix_web::web::Bytes;
+use chrono::{Duration, Local, NaiveDate};
 use err_context::AnyError;
+use itertools::Itertools;
 use log::{debug, warn};
 use std::convert::Infallible;
 
+use pdf::layout::Layout;
 use pdf::PdfManager;
 use settings::AccountSettings;
 
-use crate::dao::{Account, Dao, DaoResult, Entrepreneur, Invoice, InvoiceWithAllInfo};
+use crate::dao::{
+    Contact, Dao, DaoResult, Entrepreneur, EntrepreneurLogo, Invoice, InvoiceKind, InvoiceRecurrence, InvoiceRow, InvoiceWithContactName,
+    Money, NewInvoiceRow, Rate,
+};
 use crate::handlers::dto::NewInvoice;
+use crate::logic::bank_account::BankAccount;
+use crate::logic::export::{isdoc, pohoda, ubl};
 use crate::logic::invoices as InvoicesLogic;
+use crate::logic::vat::VatRecap;
 
 pub mod auth;
+pub mod bank_account;
+pub mod currency;
+pub mod discount;
+pub mod exchange_rates;
+pub mod export;
 pub mod iban;
 pub mod invoices;
 pub mod pdf;
+pub mod recurrence;
 pub mod settings;
+pub mod stats;
+pub mod vat;
 
-pub async fn download_invoice(
-    dao: &Dao,
-    pdf_manager: &PdfManager,
-    id: u32,
-) -> Result<(Invoice, impl futures::Stream<Item = Result<Bytes, Infallible>>), AnyError> {
+/// Invoice with its net price sum, gross (incl. VAT) price sum and the contact name.
+pub type InvoiceWithAllInfo = (Invoice, Money, Money, String);
+
+pub async fn get_invoice(dao: &Dao, id: u32) -> DaoResult<Option<InvoiceWithAllInfo>> {
+    match dao.get_invoice(id).await? {
+        Some(invoice) => Ok(with_price_sums(dao, vec![invoice]).await?.pop()),
+        None => Ok(None),
+    }
+}
+
+pub async fn list_invoices(dao: &Dao, entrepreneur_id: u32, limit: Option<u16>) -> DaoResult<Vec<InvoiceWithAllInfo>> {
+    let invoices = dao.get_invoices(entrepreneur_id, limit).await?;
+    with_price_sums(dao, invoices).await
+}
+
+async fn with_price_sums(dao: &Dao, invoices: Vec<InvoiceWithContactName>) -> DaoResult<Vec<InvoiceWithAllInfo>> {
+    let ids = invoices.iter().map(|(i, _)| i.id).collect_vec();
+    let rows = dao.get_rows_of_invoices(&ids).await?;
+
+    Ok(invoices
+        .into_iter()
+        .map(|(invoice, contact_name)| {
+            let recap = VatRecap::new(&invoice, rows.get(&invoice.id).map(Vec::as_slice).unwrap_or_default());
+            (invoice, recap.base, recap.gross, contact_name)
+        })
+        .collect())
+}
+
+/// Everything a document is made of - for rendering it to a PDF or exporting it.
+#[derive(Clone)]
+pub struct InvoiceDocument {
+    pub settings: AccountSettings,
+    pub layout: Layout,
+    pub entrepreneur: Entrepreneur,
+    pub logo: Option<EntrepreneurLogo>,
+    /// The account the invoice is paid to, none when the entrepreneur has no (valid) one.
+    pub bank_account: Option<BankAccount>,
+    pub contact: Contact,
+    pub invoice: Invoice,
+    /// The invoice corrected by a credit note.
+    pub original: Option<Invoice>,
+    /// The advance paid on the proforma of a final invoice.
+    pub deposit: Option<Deposit>,
+    pub rows: Vec<InvoiceRow>,
+}
+
+/// Advance paid on a proforma. The proforma isn't a tax document, so its final invoice declares the whole base and VAT
+/// and the advance is deducted only from the amount to be paid.
+#[derive(Clone, Debug)]
+pub struct Deposit {
+    pub proforma: Invoice,
+    /// Including VAT, in the currency of the final invoice (the same as of the proforma).
+    pub amount: Money,
+}
+
+impl InvoiceDocument {
+    /// The total price less the paid advance.
+    pub fn payable(&self, vat_recap: &VatRecap) -> Money {
+        vat_recap.gross - self.deposit.as_ref().map_or(Money::ZERO, |d| d.amount)
+    }
+}
+
+async fn load_deposit(dao: &Dao, invoice: &Invoice) -> DaoResult<Option<Deposit>> {
+    let proforma_id = match invoice.proforma_id {
+        Some(proforma_id) => proforma_id as u32,
+        None => return Ok(None),
+    };
+
+    Ok(dao.get_invoice_with_rows(proforma_id).await?.map(|(proforma, rows)| Deposit {
+        amount: VatRecap::new(&proforma, &rows).gross,
+        proforma,
+    }))
+}
+
+pub async fn load_document(dao: &Dao, id: u32) -> Result<InvoiceDocument, AnyError> {
     let (invoice, rows) = match dao.get_invoice_with_rows(id).await? {
         Some(iwr) => iwr,
         None => return Err(AnyError::from("Could not find requested invoice")),
@@ -36,91 +123,324 @@ pub async fn download_invoice(
         .await?
         .expect("This value must exist!");
 
+    let logo = dao.get_entrepreneur_logo(entrepreneur.id as u32).await?;
+
+    let bank_accounts = dao.get_bank_accounts(entrepreneur.id as u32).await?;
+    let bank_account = bank_account::select(&bank_accounts, &invoice, &entrepreneur.currency_code).and_then(BankAccount::of);
+
     let contact = dao.get_contact(invoice.contact_id as u32).await?.expect("This value must exist!");
 
-    let account_settings = AccountSettings::from(&account);
+    let original = match invoice.original_invoice_id {
+        Some(original_id) => dao.get_invoice(original_id as u32).await?.map(|(original, _)| original),
+        None => None,
+    };
+
+    let deposit = load_deposit(dao, &invoice).await?;
 
-    Ok((
-        invoice.clone(),
-        pdf_manager.create(account_settings, entrepreneur, contact, invoice, rows),
-    ))
+    Ok(InvoiceDocument {
+        settings: AccountSettings::from(&account),
+        layout: Layout::of(&account),
+        entrepreneur,
+        logo,
+        bank_account,
+        contact,
+        invoice,
+        original,
+        deposit,
+        rows,
+    })
 }
 
-pub async fn insert_invoice(dao: &Dao, invoice: &NewInvoice) -> DaoResult<InvoiceWithAllInfo> {
-    let entrepreneur = dao
-        .get_entrepreneur(invoice.entrepreneur_id as u32)
-        .await?
-        .expect("This value must exist!");
+pub async fn download_invoice(
+    dao: &Dao,
+    pdf_manager: &PdfManager,
+    id: u32,
+) -> Result<(Invoice, impl futures::Stream<Item = Result<Bytes, Infallible>>), AnyError> {
+    let document = load_document(dao, id).await?;
+
+    Ok((document.invoice.clone(), pdf_manager.create_document(document)))
+}
+
+/// PDF of the invoice in the (not yet uploaded) layout.
+pub async fn preview_pdf_layout(
+    dao: &Dao,
+    pdf_manager: &PdfManager,
+    id: u32,
+    layout: Layout,
+) -> Result<(Invoice, impl futures::Stream<Item = Result<Bytes, Infallible>>), AnyError> {
+    let document = InvoiceDocument {
+        layout,
+        ..load_document(dao, id).await?
+    };
+
+    Ok((document.invoice.clone(), pdf_manager.create_document(document)))
+}
+
+pub async fn get_pdf_layout(dao: &Dao, account_id: u32) -> DaoResult<Layout> {
+    let account = dao.get_account(account_id).await?.expect("This value must exist!");
+
+    Ok(Layout::of(&account))
+}
+
+pub async fn download_isdoc(dao: &Dao, id: u32) -> Result<(Invoice, String), AnyError> {
+    let document = load_document(dao, id).await?;
+
+    Ok((document.invoice.clone(), isdoc::create(&document, &[])))
+}
+
+/// UBL document for the Peppol network, fails for the parties without an electronic address.
+pub async fn download_ubl(dao: &Dao, id: u32) -> Result<(Invoice, String), AnyError> {
+    let document = load_document(dao, id).await?;
+
+    Ok((document.invoice.clone(), ubl::create(&document)?))
+}
+
+/// ISDOCX package - the ISDOC document together with the PDF.
+pub async fn download_isdocx(dao: &Dao, pdf_manager: &PdfManager, id: u32) -> Result<(Invoice, Vec<u8>), AnyError> {
+    let document = load_document(dao, id).await?;
+
+    let pdf = pdf_manager.render(document.clone()).await;
+    let pdf_filename = isdoc::filename(&document.invoice, "pdf");
+
+    let package = isdoc::create_package(
+        &document,
+        &[isdoc::Supplement {
+            filename: &pdf_filename,
+            content: &pdf,
+        }],
+    )?;
+
+    Ok((document.invoice, package))
+}
+
+/// Pohoda `dataPack` with all the documents the entrepreneur issued in the period (both days included).
+pub async fn export_pohoda(dao: &Dao, entrepreneur_id: u32, from: NaiveDate, to: NaiveDate) -> Result<String, AnyError> {
+    let entrepreneur = match dao.get_entrepreneur(entrepreneur_id).await? {
+        Some(entrepreneur) => entrepreneur,
+        None => return Err(AnyError::from("Could not find requested entrepreneur")),
+    };
 
     let account = dao
         .get_account(entrepreneur.account_id as u32)
         .await?
         .expect("This value must exist!");
-
     let settings = AccountSettings::from(&account);
+    let layout = Layout::of(&account);
 
-    debug!("Loaded user settings: {:?}", settings);
+    let bank_accounts = dao.get_bank_accounts(entrepreneur_id).await?;
+    let invoices = dao.get_invoices_with_contacts(entrepreneur_id, from, to).await?;
+    let mut rows = dao.get_rows_of_invoices(&invoices.iter().map(|(i, _)| i.id).collect_vec()).await?;
 
-    let invoice_code = next_invoice_code(dao, &entrepreneur, &account, &settings).await?;
+    let mut documents = Vec::with_capacity(invoices.len());
 
-    // TODO handle duplicated code
+    for (invoice, contact) in invoices {
+        let original = match invoice.original_invoice_id {
+            Some(original_id) => dao.get_invoice(original_id as u32).await?.map(|(original, _)| original),
+            None => None,
+        };
+        let deposit = load_deposit(dao, &invoice).await?;
 
-    dao.insert_invoice(
-        &invoice_code,
-        invoice.entrepreneur_id,
-        invoice.contact_id,
-        &settings.invoice.default_due_length,
-    )
-    .await
+        documents.push(InvoiceDocument {
+            settings: settings.clone(),
+            layout: layout.clone(),
+            entrepreneur: entrepreneur.clone(),
+            logo: None,
+            bank_account: bank_account::select(&bank_accounts, &invoice, &entrepreneur.currency_code).and_then(BankAccount::of),
+            contact,
+            rows: rows.remove(&invoice.id).unwrap_or_default(),
+            invoice,
+            original,
+            deposit,
+        });
+    }
+
+    Ok(pohoda::create(&entrepreneur, from, to, &documents))
 }
 
-pub async fn copy_invoice(dao: &Dao, original: Invoice) -> Result<Invoice, AnyError> {
+pub async fn insert_invoice(dao: &Dao, invoice: &NewInvoice) -> DaoResult<InvoiceWithAllInfo> {
     let entrepreneur = dao
-        .get_entrepreneur(original.entrepreneur_id as u32)
+        .get_entrepreneur(invoice.entrepreneur_id)
         .await?
         .expect("This value must exist!");
+    let today = Local::now().naive_local().date();
 
-    let account = dao
-        .get_account(entrepreneur.account_id as u32)
+    let blank = Invoice {
+        id: 0,
+        entrepreneur_id: invoice.entrepreneur_id as i32,
+        contact_id: invoice.contact_id as i32,
+        code: String::new(),
+        kind: invoice.kind,
+        original_invoice_id: None,
+        proforma_id: None,
+        created: today,
+        pay_until: today,
+        payed: None,
+        discount_percent: None,
+        discount_amount: None,
+        language: None,
+        currency_code: entrepreneur.currency_code,
+        exchange_rate: Rate::from_int(1),
+        bank_account_id: None,
+    };
+    let (invoice, _) = insert_numbered(dao, &blank, &[], None, None).await?;
+
+    Ok(get_invoice(dao, invoice.id as u32)
         .await?
-        .expect("This value must exist!");
+        .expect("Must find newly inserted invoice!"))
+}
 
-    let settings = AccountSettings::from(&account);
+/// Updates the invoice; its kind and the links to other documents can't be changed. A final invoice stays in the
+/// currency of its proforma, which the paid advance is in.
+pub async fn update_invoice(dao: &Dao, mut invoice: Invoice) -> DaoResult<()> {
+    if let Some((current, _)) = dao.get_invoice(invoice.id as u32).await? {
+        invoice.kind = current.kind;
+        invoice.original_invoice_id = current.original_invoice_id;
+        invoice.proforma_id = current.proforma_id;
 
-    debug!("Loaded user settings: {:?}", settings);
+        if current.proforma_id.is_some() {
+            invoice.currency_code = current.currency_code;
+            invoice.exchange_rate = current.exchange_rate;
+        }
+    }
+
+    dao.update_invoice(&invoice).await
+}
 
-    let invoice_code = next_invoice_code(dao, &entrepreneur, &account, &settings).await?;
+pub async fn copy_invoice(dao: &Dao, original: Invoice) -> Result<Invoice, AnyError> {
+    let rows = dao.get_invoice_rows(original.id as u32).await?;
+    let copy = made_of(&original, original.kind, original.original_invoice_id, None);
 
-    let (copy, _, _) = dao
-        .insert_invoice(
-            &invoice_code,
-            original.entrepreneur_id as u32,
-            original.contact_id as u32,
-            &settings.invoice.default_due_length,
-        )
-        .await?;
+    let (copy, _) = insert_numbered(dao, &copy, &copies_of(&rows, false), None, None).await?;
 
-    let original_rows = dao.get_invoice_rows(original.id as u32).await?;
+    Ok(copy)
+}
 
-    for row in original_rows {
-        let _ = dao
-            .insert_invoice_row(copy.id as u32, &row.item_name, row.item_price, row.item_count as u16)
-            .await;
+/// Issues a credit note for the whole `original` invoice, i.e. with all its rows negated. The rows can be edited
+/// afterwards, when only a part of the invoice is to be credited.
+pub async fn insert_credit_note(dao: &Dao, original: Invoice) -> Result<Invoice, AnyError> {
+    if original.kind != InvoiceKind::Invoice {
+        return Err(AnyError::from("A credit note can be issued only for an invoice"));
     }
 
-    Ok(copy)
+    let rows = dao.get_invoice_rows(original.id as u32).await?;
+    let credit_note = made_of(&original, InvoiceKind::CreditNote, Some(original.id), None);
+
+    let (credit_note, _) = insert_numbered(dao, &credit_note, &copies_of(&rows, true), None, None).await?;
+
+    Ok(credit_note)
 }
 
-async fn next_invoice_code(
+/// Turns a paid proforma into the final invoice (a tax document). The invoice contains all rows of the proforma, so it
+/// declares the whole base and VAT; the paid advance is only deducted from the amount to be paid (see `Deposit`).
+pub async fn finalize_proforma(dao: &Dao, proforma: Invoice) -> Result<Invoice, AnyError> {
+    if proforma.kind != InvoiceKind::Proforma {
+        return Err(AnyError::from("Only a proforma can be finalized"));
+    }
+
+    if proforma.payed.is_none() {
+        return Err(AnyError::from("The proforma has not been paid yet"));
+    }
+
+    // a concurrent request passing the check too fails on the unique proforma of invoices
+    if dao.find_invoice_of_proforma(proforma.id as u32).await?.is_some() {
+        return Err(AnyError::from("The proforma has already been finalized"));
+    }
+
+    let rows = dao.get_invoice_rows(proforma.id as u32).await?;
+    let invoice = made_of(&proforma, InvoiceKind::Invoice, None, Some(proforma.id));
+
+    // paid by the advance, unless some amount remains to be paid
+    let deposit = VatRecap::new(&proforma, &rows).gross;
+    let remaining = VatRecap::new(&invoice, &rows).gross - deposit;
+    let invoice = Invoice {
+        payed: if remaining > Money::ZERO { None } else { proforma.payed },
+        ..invoice
+    };
+
+    let (invoice, _) = insert_numbered(dao, &invoice, &copies_of(&rows, false), None, None).await?;
+
+    Ok(invoice)
+}
+
+/// New document of the `kind` made of the `template`: for the same contact, in the same language and currency (at the
+/// same rate), with the same discount and paid to the same account. It's created today.
+fn made_of(template: &Invoice, kind: InvoiceKind, original_invoice_id: Option<i32>, proforma_id: Option<i32>) -> Invoice {
+    let today = Local::now().naive_local().date();
+
+    Invoice {
+        kind,
+        original_invoice_id,
+        proforma_id,
+        created: today,
+        pay_until: today,
+        payed: None,
+        ..template.clone()
+    }
+}
+
+/// Copies of the `rows` of another document, negated for a credit note.
+fn copies_of(rows: &[InvoiceRow], negate: bool) -> Vec<NewInvoiceRow> {
+    rows.iter()
+        .map(|row| NewInvoiceRow {
+            // replaced by the id of the new document
+            invoice_id: row.invoice_id,
+            item_name: row.item_name.clone(),
+            item_price: row.item_price,
+            item_count: if negate { -row.item_count } else { row.item_count },
+            item_unit: row.item_unit.clone(),
+            vat_rate: row.vat_rate,
+            discount_percent: row.discount_percent,
+            discount_amount: row.discount_amount,
+        })
+        .collect()
+}
+
+/// Inserts the `document` (its id and code are ignored) with the next number of the entrepreneur's naming schema for
+/// the day it's created, due in the `due_length` (the default one of the account if none), together with the `rows`.
+/// The `recurrence` creating the document is updated in the same transaction.
+async fn insert_numbered(
     dao: &Dao,
-    entrepreneur: &Entrepreneur,
-    account: &Account,
-    settings: &AccountSettings,
-) -> Result<String, AnyError> {
-    InvoicesLogic::next_code(dao, account, entrepreneur, settings.invoice.naming_schema)
-        .await
-        .map_err(|err| {
+    document: &Invoice,
+    rows: &[NewInvoiceRow],
+    due_length: Option<Duration>,
+    recurrence: Option<&InvoiceRecurrence>,
+) -> Result<(Invoice, Vec<InvoiceRow>), AnyError> {
+    let entrepreneur = dao
+        .get_entrepreneur(document.entrepreneur_id as u32)
+        .await?
+        .expect("This value must exist!");
+
+    let account = dao
+        .get_account(entrepreneur.account_id as u32)
+        .await?
+        .expect("This value must exist!");
+
+    let settings = AccountSettings::from(&account);
+
+    debug!("Loaded user settings: {:?}", settings);
+
+    let naming_schema =
+        InvoicesLogic::naming_schema(settings.invoice.naming_schema_of(&entrepreneur), &settings.invoice.series).map_err(|err| {
             warn!("Could not generate invoice id: {}", err);
             AnyError::from("Could not generate invoice id")
-        })
+        })?;
+
+    let created = document.created;
+    let document = Invoice {
+        pay_until: created + due_length.unwrap_or(*settings.invoice.default_due_length),
+        ..document.clone()
+    };
+
+    dao.insert_invoice(
+        naming_schema.period_start(created),
+        |number| naming_schema.code(created, document.kind, number),
+        |code, date| {
+            let in_period = naming_schema.period_start(date) == naming_schema.period_start(created);
+            in_period.then(|| naming_schema.number(code, date, document.kind)).flatten()
+        },
+        &document,
+        rows,
+        recurrence,
+    )
+    .await
 }
@@ -0,0 +1,415 @@
+//! Translations of the texts of the PDF (and of the exports), together with the formats of dates and numbers. Texts with `{}` are filled
+//! by `Catalog::fill`.
+
+use std::iter;
+
+use chrono::NaiveDate;
+
+use crate::dao::{InvoiceKind, Language};
+
+pub struct Catalog {
+    /// Title of the document in its metadata.
+    pub document_title: &'static str,
+    pub invoice: &'static str,
+    pub credit_note: &'static str,
+    pub proforma: &'static str,
+    pub not_tax_document: &'static str,
+    pub credit_note_for: &'static str,
+    pub supplier: &'static str,
+    pub customer: &'static str,
+    pub company_id: &'static str,
+    pub vat_id: &'static str,
+    pub not_vat_payer: &'static str,
+    /// Legal note of the trade licence, with the licensing office.
+    pub trade_licence: &'static str,
+    pub handed_over: &'static str,
+    pub received_by: &'static str,
+    pub vat_rate: &'static str,
+    pub vat_base: &'static str,
+    pub vat: &'static str,
+    pub total: &'static str,
+    pub invoice_discount: &'static str,
+    pub subtotal: &'static str,
+    /// The advance paid on the proforma, with its code.
+    pub paid_deposit: &'static str,
+    pub amount_due: &'static str,
+    pub row_discount: &'static str,
+    pub quantity: &'static str,
+    pub unit_price: &'static str,
+    pub vat_exempt: &'static str,
+    pub payment_details: &'static str,
+    pub pay_to_account: &'static str,
+    pub variable_symbol: &'static str,
+    pub due_date: &'static str,
+    pub refund: &'static str,
+    pub refund_amount: &'static str,
+    pub refund_account: &'static str,
+    pub continued_next: &'static str,
+    pub continued_previous: &'static str,
+    /// Reason of the VAT exemption (stated in the exports).
+    pub vat_exemption: &'static str,
+    /// Names of the countries of the usual parties (by their ISO 3166-1 codes).
+    countries: &'static [(&'static str, &'static str)],
+    date_format: &'static str,
+    thousands_separator: char,
+    decimal_separator: char,
+}
+
+const CS: Catalog = Catalog {
+    document_title: "Faktura {}",
+    invoice: "faktura",
+    credit_note: "opravný daňový doklad",
+    proforma: "ZÁLOHOVÁ FAKTURA",
+    not_tax_document: "Nejedná se o daňový doklad.",
+    credit_note_for: "k faktuře {}",
+    supplier: "DODAVATEL",
+    customer: "ODBĚRATEL",
+    company_id: "IČO {}",
+    vat_id: "DIČ {}",
+    not_vat_payer: "Neplátce DPH",
+    trade_licence: "Fyzická osoba zapsaná v Živnostenském rejstříku. \
+        Úřad příslušný podle § 71 odst. 2 živnostenského zákona: {}.",
+    handed_over: "Předáno dne:",
+    received_by: "Převzal:",
+    vat_rate: "Sazba DPH",
+    vat_base: "Základ",
+    vat: "DPH",
+    total: "Celkem",
+    invoice_discount: "Sleva na fakturu",
+    subtotal: "Mezisoučet",
+    paid_deposit: "Uhrazeno zálohou {}",
+    amount_due: "K úhradě",
+    row_discount: "sleva",
+    quantity: "Množství",
+    unit_price: "Cena za MJ",
+    vat_exempt: "osvob.",
+    payment_details: "PLATEBNÍ ÚDAJE",
+    pay_to_account: "zaplaťte prosím na účet č.",
+    variable_symbol: "s variabilním symbolem",
+    due_date: "do",
+    refund: "VRÁCENÍ PLATBY",
+    refund_amount: "částku",
+    refund_account: "Vám vrátíme na účet, ze kterého byla faktura uhrazena,",
+    continued_next: "Pokračování na další straně",
+    continued_previous: "Pokračování z předchozí strany",
+    vat_exemption: "Osvobozeno od DPH",
+    countries: &[
+        ("CZ", "Česká republika"),
+        ("SK", "Slovensko"),
+        ("DE", "Německo"),
+        ("AT", "Rakousko"),
+        ("PL", "Polsko"),
+        ("HU", "Maďarsko"),
+    ],
+    date_format: "%d.%m.%Y",
+    thousands_separator: ' ',
+    decimal_separator: ',',
+};
+
+const EN: Catalog = Catalog {
+    document_title: "Invoice {}",
+    invoice: "invoice",
+    credit_note: "credit note",
+    proforma: "PROFORMA INVOICE",
+    not_tax_document: "This is not a tax document.",
+    credit_note_for: "to invoice {}",
+    supplier: "SUPPLIER",
+    customer: "CUSTOMER",
+    company_id: "Company ID {}",
+    vat_id: "VAT ID {}",
+    not_vat_payer: "Not a VAT payer",
+    trade_licence: "A natural person registered in the Trade Register. \
+        Office competent under Section 71(2) of the Trade Licensing Act: {}.",
+    handed_over: "Handed over on:",
+    received_by: "Received by:",
+    vat_rate: "VAT rate",
+    vat_base: "Base",
+    vat: "VAT",
+    total: "Total",
+    invoice_discount: "Invoice discount",
+    subtotal: "Subtotal",
+    paid_deposit: "Paid in advance {}",
+    amount_due: "Amount due",
+    row_discount: "discount",
+    quantity: "Quantity",
+    unit_price: "Unit price",
+    vat_exempt: "exempt",
+    payment_details: "PAYMENT DETAILS",
+    pay_to_account: "please pay to the account no.",
+    variable_symbol: "with the variable symbol",
+    due_date: "by",
+    refund: "REFUND",
+    refund_amount: "the amount of",
+    refund_account: "will be refunded to the account the invoice was paid from,",
+    continued_next: "Continued on the next page",
+    continued_previous: "Continued from the previous page",
+    vat_exemption: "Exempt from VAT",
+    countries: &[
+        ("CZ", "Czech Republic"),
+        ("SK", "Slovakia"),
+        ("DE", "Germany"),
+        ("AT", "Austria"),
+        ("PL", "Poland"),
+        ("HU", "Hungary"),
+    ],
+    date_format: "%-d %b %Y",
+    thousands_separator: ',',
+    decimal_separator: '.',
+};
+
+const DE: Catalog = Catalog {
+    document_title: "Rechnung {}",
+    invoice: "Rechnung",
+    credit_note: "Rechnungskorrektur",
+    proforma: "VORAUSRECHNUNG",
+    not_tax_document: "Dies ist kein Steuerbeleg.",
+    credit_note_for: "zur Rechnung {}",
+    supplier: "LIEFERANT",
+    customer: "KUNDE",
+    company_id: "Firmen-ID {}",
+    vat_id: "USt-IdNr. {}",
+    not_vat_payer: "Nicht umsatzsteuerpflichtig",
+    trade_licence: "Natürliche Person, eingetragen im Gewerberegister. \
+        Zuständige Behörde nach § 71 Abs. 2 des Gewerbegesetzes: {}.",
+    handed_over: "Übergeben am:",
+    received_by: "Übernommen von:",
+    vat_rate: "MwSt.-Satz",
+    vat_base: "Netto",
+    vat: "MwSt.",
+    total: "Gesamt",
+    invoice_discount: "Rechnungsrabatt",
+    subtotal: "Zwischensumme",
+    paid_deposit: "Geleistete Anzahlung {}",
+    amount_due: "Zahlbetrag",
+    row_discount: "Rabatt",
+    quantity: "Menge",
+    unit_price: "Einzelpreis",
+    vat_exempt: "befreit",
+    payment_details: "ZAHLUNGSDATEN",
+    pay_to_account: "bitte zahlen Sie auf das Konto Nr.",
+    variable_symbol: "mit dem variablen Symbol",
+    due_date: "bis",
+    refund: "RÜCKERSTATTUNG",
+    refund_amount: "den Betrag von",
+    refund_account: "erstatten wir auf das Konto, von dem die Rechnung bezahlt wurde,",
+    continued_next: "Fortsetzung auf der nächsten Seite",
+    continued_previous: "Fortsetzung von der vorherigen Seite",
+    vat_exemption: "Von der Umsatzsteuer befreit",
+    countries: &[
+        ("CZ", "Tschechische Republik"),
+        ("SK", "Slowakei"),
+        ("DE", "Deutschland"),
+        ("AT", "Österreich"),
+        ("PL", "Polen"),
+        ("HU", "Ungarn"),
+    ],
+    date_format: "%d.%m.%Y",
+    thousands_separator: '.',
+    decimal_separator: ',',
+};
+
+const SK: Catalog = Catalog {
+    document_title: "Faktúra {}",
+    invoice: "faktúra",
+    credit_note: "opravný daňový doklad",
+    proforma: "ZÁLOHOVÁ FAKTÚRA",
+    not_tax_document: "Nejedná sa o daňový doklad.",
+    credit_note_for: "k faktúre {}",
+    supplier: "DODÁVATEĽ",
+    customer: "ODBERATEĽ",
+    company_id: "IČO {}",
+    vat_id: "IČ DPH {}",
+    not_vat_payer: "Neplatiteľ DPH",
+    trade_licence: "Fyzická osoba zapísaná v Živnostenskom registri. \
+        Úrad príslušný podľa § 71 ods. 2 živnostenského zákona: {}.",
+    handed_over: "Odovzdané dňa:",
+    received_by: "Prevzal:",
+    vat_rate: "Sadzba DPH",
+    vat_base: "Základ",
+    vat: "DPH",
+    total: "Spolu",
+    invoice_discount: "Zľava na faktúru",
+    subtotal: "Medzisúčet",
+    paid_deposit: "Uhradené zálohou {}",
+    amount_due: "K úhrade",
+    row_discount: "zľava",
+    quantity: "Množstvo",
+    unit_price: "Cena za MJ",
+    vat_exempt: "oslob.",
+    payment_details: "PLATOBNÉ ÚDAJE",
+    pay_to_account: "zaplaťte prosím na účet č.",
+    variable_symbol: "s variabilným symbolom",
+    due_date: "do",
+    refund: "VRÁTENIE PLATBY",
+    refund_amount: "sumu",
+    refund_account: "Vám vrátime na účet, z ktorého bola faktúra uhradená,",
+    continued_next: "Pokračovanie na ďalšej strane",
+    continued_previous: "Pokračovanie z predchádzajúcej strany",
+    vat_exemption: "Oslobodené od DPH",
+    countries: &[
+        ("CZ", "Česká republika"),
+        ("SK", "Slovensko"),
+        ("DE", "Nemecko"),
+        ("AT", "Rakúsko"),
+        ("PL", "Poľsko"),
+        ("HU", "Maďarsko"),
+    ],
+    date_format: "%d.%m.%Y",
+    thousands_separator: ' ',
+    decimal_separator: ',',
+};
+
+impl Catalog {
+    pub fn of(language: Language) -> &'static Catalog {
+        match language {
+            Language::Cs => &CS,
+            Language::En => &EN,
+            Language::De => &DE,
+            Language::Sk => &SK,
+        }
+    }
+
+    pub fn title(&self, kind: InvoiceKind) -> &'static str {
+        match kind {
+            InvoiceKind::Invoice => self.invoice,
+            InvoiceKind::CreditNote => self.credit_note,
+            InvoiceKind::Proforma => self.proforma,
+        }
+    }
+
+    /// The text with its `{}` replaced by the `value`.
+    pub fn fill(text: &str, value: &str) -> String {
+        text.replacen("{}", value, 1)
+    }
+
+    /// Name of the country, the others than of the usual parties are named by their `code`.
+    pub fn country_name<'a>(&self, code: &'a str) -> &'a str {
+        self.countries.iter().find(|(c, _)| *c == code).map_or(code, |(_, name)| name)
+    }
+
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        date.format(self.date_format).to_string()
+    }
+
+    /// Number formatted with the `.` decimal point and no grouping (e.g. `-1234.5`), in the format of the language.
+    pub fn format_number(&self, number: &str) -> String {
+        let (sign, number) = match number.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", number),
+        };
+        let (int_part, decimals) = match number.split_once('.') {
+            Some((int_part, decimals)) => (int_part, Some(decimals)),
+            None => (number, None),
+        };
+
+        // split into groups of thousands, from the right
+        let groups = int_part
+            .as_bytes()
+            .rchunks(3)
+            .rev()
+            .map(|g| String::from_utf8_lossy(g).into_owned())
+            .collect::<Vec<_>>()
+            .join(&self.thousands_separator.to_string());
+
+        match decimals {
+            Some(decimals) => format!("{}{}{}{}", sign, groups, self.decimal_separator, decimals),
+            None => format!("{}{}", sign, groups),
+        }
+    }
+}
+
+/// Texts of the document in its language, or in two languages side by side (`faktura / invoice`) for the bilingual
+/// layout. Dates and numbers are always in the format of the (primary) language.
+#[derive(Clone, Copy)]
+pub struct Texts {
+    primary: &'static Catalog,
+    secondary: Option<&'static Catalog>,
+}
+
+impl Texts {
+    /// The `secondary` language is left out when it's the same as the primary one.
+    pub fn new(language: Language, secondary: Option<Language>) -> Self {
+        Texts {
+            primary: Catalog::of(language),
+            secondary: secondary.filter(|s| *s != language).map(Catalog::of),
+        }
+    }
+
+    pub fn is_bilingual(&self) -> bool {
+        self.secondary.is_some()
+    }
+
+    pub fn catalog(&self) -> &'static Catalog {
+        self.primary
+    }
+
+    /// Catalogs of the languages, the primary one first.
+    pub fn catalogs(&self) -> impl Iterator<Item = &'static Catalog> {
+        iter::once(self.primary).chain(self.secondary)
+    }
+
+    pub fn get(&self, text: impl Fn(&Catalog) -> &'static str) -> String {
+        self.catalogs().map(text).collect::<Vec<_>>().join(" / ")
+    }
+
+    /// The text with its `{}` replaced by the `value`. In two languages the value follows both the texts, the `{}` is
+    /// expected at their ends.
+    pub fn fill(&self, text: impl Fn(&Catalog) -> &'static str, value: &str) -> String {
+        match self.secondary {
+            Some(_) => format!("{} {}", self.get(|c| text(c).trim_end_matches("{}").trim_end()), value),
+            None => Catalog::fill(text(self.primary), value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(Catalog::of(Language::Cs).format_number("-1234567.5"), "-1 234 567,5");
+        assert_eq!(Catalog::of(Language::Sk).format_number("0.25"), "0,25");
+        assert_eq!(Catalog::of(Language::En).format_number("1234.50"), "1,234.50");
+        assert_eq!(Catalog::of(Language::De).format_number("1234.50"), "1.234,50");
+        assert_eq!(Catalog::of(Language::De).format_number("100"), "100");
+    }
+
+    #[test]
+    fn test_country_name() {
+        assert_eq!(Catalog::of(Language::Cs).country_name("DE"), "Německo");
+        assert_eq!(Catalog::of(Language::De).country_name("DE"), "Deutschland");
+        assert_eq!(Catalog::of(Language::En).country_name("FR"), "FR");
+    }
+
+    #[test]
+    fn test_format_date() {
+        let date = NaiveDate::from_ymd(2022, 3, 5);
+
+        assert_eq!(Catalog::of(Language::Cs).format_date(date), "05.03.2022");
+        assert_eq!(Catalog::of(Language::En).format_date(date), "5 Mar 2022");
+        assert_eq!(Catalog::of(Language::De).format_date(date), "05.03.2022");
+    }
+
+    #[test]
+    fn test_texts() {
+        let texts = Texts::new(Language::Cs, Some(Language::En));
+
+        assert!(texts.is_bilingual());
+        assert_eq!(texts.get(|c| c.supplier), "DODAVATEL / SUPPLIER");
+        assert_eq!(texts.fill(|c| c.company_id, "12345678"), "IČO / Company ID 12345678");
+
+        let texts = Texts::new(Language::De, Some(Language::De));
+
+        assert!(!texts.is_bilingual());
+        assert_eq!(texts.fill(|c| c.company_id, "12345678"), "Firmen-ID 12345678");
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            Catalog::fill(Catalog::of(Language::En).credit_note_for, "2022-0001"),
+            "to invoice 2022-0001"
+        );
+    }
+}
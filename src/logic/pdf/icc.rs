@@ -0,0 +1,137 @@
+//! sRGB (IEC 61966-2.1) colour profile, the output intent of archived PDFs. It's the plain ICC v2 matrix/TRC display
+//! profile - the D50 adapted primaries and the sRGB transfer curve.
+
+/// PCS illuminant (D50), also the white point the primaries are adapted to.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const RED: [f64; 3] = [0.4360747, 0.2225045, 0.0139322];
+const GREEN: [f64; 3] = [0.3850649, 0.7168786, 0.0971045];
+const BLUE: [f64; 3] = [0.1430804, 0.0606169, 0.7141733];
+const CURVE_POINTS: usize = 1024;
+
+pub const DESCRIPTION: &str = "sRGB IEC61966-2.1";
+
+pub fn srgb_profile() -> Vec<u8> {
+    let curve = curve();
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", description(DESCRIPTION)),
+        (b"cprt", text("No copyright, use freely")),
+        (b"wtpt", xyz(D50)),
+        (b"rXYZ", xyz(RED)),
+        (b"gXYZ", xyz(GREEN)),
+        (b"bXYZ", xyz(BLUE)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut table = u32_bytes(tags.len() as u32);
+    let mut data = Vec::new();
+    let data_offset = 128 + 4 + 12 * tags.len();
+
+    for (signature, content) in tags {
+        table.extend_from_slice(signature);
+        table.extend(u32_bytes((data_offset + data.len()) as u32));
+        table.extend(u32_bytes(content.len() as u32));
+
+        data.extend(content);
+        // every tag starts on a 4 byte boundary
+        data.resize(data.len().div_ceil(4) * 4, 0);
+    }
+
+    let size = 128 + table.len() + data.len();
+
+    let mut profile = Vec::with_capacity(size);
+    profile.extend(u32_bytes(size as u32));
+    profile.extend_from_slice(&[0; 4]); // preferred CMM
+    profile.extend_from_slice(&[2, 0x10, 0, 0]); // version 2.1
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    for n in [2022u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&n.to_be_bytes()); // creation date
+    }
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // platform, flags, manufacturer, model and attributes
+    profile.extend_from_slice(&[0; 4]); // perceptual rendering intent
+    D50.iter().for_each(|&n| profile.extend(s15_fixed16(n)));
+    profile.resize(128, 0); // creator, profile ID and the reserved bytes
+
+    profile.extend(table);
+    profile.extend(data);
+
+    profile
+}
+
+fn u32_bytes(n: u32) -> Vec<u8> {
+    n.to_be_bytes().to_vec()
+}
+
+fn s15_fixed16(n: f64) -> Vec<u8> {
+    ((n * 65536.0).round() as i32).to_be_bytes().to_vec()
+}
+
+fn xyz(value: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    value.iter().for_each(|&n| tag.extend(s15_fixed16(n)));
+    tag
+}
+
+fn text(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// ASCII description, without any Unicode or ScriptCode ones.
+fn description(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend(u32_bytes(text.len() as u32 + 1));
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag.extend_from_slice(&[0; 8]); // Unicode language and length
+    tag.extend_from_slice(&[0; 3]); // ScriptCode code and length
+    tag.extend_from_slice(&[0; 67]);
+    tag
+}
+
+/// sRGB transfer function - linear near the black, gamma 2.4 otherwise.
+fn curve() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend(u32_bytes(CURVE_POINTS as u32));
+
+    for i in 0..CURVE_POINTS {
+        let v = i as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    tag
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_srgb_profile() {
+        let profile = srgb_profile();
+        let u32_at = |i: usize| u32::from_be_bytes(profile[i..i + 4].try_into().unwrap()) as usize;
+
+        assert_eq!(u32_at(0), profile.len());
+        assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!(u32_at(128), 9);
+
+        // every tag lies within the profile, aligned
+        for i in 0..9 {
+            let entry = 132 + 12 * i;
+            assert_eq!(u32_at(entry + 4) % 4, 0);
+            assert!(u32_at(entry + 4) + u32_at(entry + 8) <= profile.len());
+        }
+
+        let trc = u32_at(132 + 12 * 6 + 4);
+        assert_eq!(&profile[trc..trc + 4], b"curv");
+        assert_eq!(u32_at(trc + 8), CURVE_POINTS);
+        assert_eq!(&profile[trc + 12..trc + 14], &[0, 0]);
+        assert_eq!(&profile[trc + 10 + 2 * CURVE_POINTS..trc + 12 + 2 * CURVE_POINTS], &[0xff, 0xff]);
+    }
+}
@@ -0,0 +1,356 @@
+//! Layout template of the PDF - positions of its blocks (in millimetres from the bottom left corner of the page) with
+//! their fonts, sizes and colours. Blocks left out of the template aren't rendered. The default template is
+//! `default-layout.json`, accounts can have their own ones.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use err_context::AnyError;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::dao::Account;
+
+const DEFAULT: &str = include_str!("default-layout.json");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Layout {
+    pub line_space: f64,
+    pub header: Header,
+    /// Logo of the entrepreneur, the templates from before have the default one.
+    #[serde(default = "LogoBlock::default_some")]
+    pub logo: Option<LogoBlock>,
+    pub supplier: Option<PartyBlock>,
+    /// Phone and e-mail of the supplier.
+    pub contact: Option<FlowBlock>,
+    /// Legal note of the trade licence of the supplier.
+    pub legal: Option<FlowBlock>,
+    /// Notes of the handover, shown when the account settings want them.
+    pub handover: Option<FlowBlock>,
+    pub customer: Option<PartyBlock>,
+    pub rows: Option<RowsBlock>,
+    /// Payment details, or the refund for a credit note.
+    pub payment: Option<PaymentBlock>,
+    pub qr: Option<QrBlock>,
+    /// Pages of the rows which don't fit on the first one, the templates from before have the default ones.
+    #[serde(default)]
+    pub pages: PagesBlock,
+}
+
+/// Font, size and colour of the texts of a block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Style {
+    #[serde(default = "Style::default_font")]
+    pub font: String,
+    #[serde(default = "Style::default_bold_font")]
+    pub bold_font: String,
+    #[serde(default = "Style::default_size")]
+    pub size: f64,
+    #[serde(default)]
+    pub color: Color,
+}
+
+/// Title of the document with its code and date below.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub left: f64,
+    pub title_bottom: f64,
+    pub code_bottom: f64,
+    pub date_left: f64,
+    pub title_sizes: TitleSizes,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// Sizes of the title - of an invoice, and of the longer ones (credit notes and proformas), also in two languages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TitleSizes {
+    pub invoice: f64,
+    pub other: f64,
+    pub bilingual_invoice: f64,
+    pub bilingual_other: f64,
+}
+
+/// The logo fits into the box, aligned to its top right corner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogoBlock {
+    pub right: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The supplier or the customer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartyBlock {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// Block which (without its `top`) follows the one above it - the contact follows the supplier, the legal note the
+/// contact and the handover notes are next to the legal note.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowBlock {
+    pub left: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<f64>,
+    pub width: f64,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// Rows with the VAT recapitulation, rendered upwards from the `bottom` (the total). Columns are offsets from the
+/// `right` border.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RowsBlock {
+    pub left: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub header_size: f64,
+    pub quantity_column: f64,
+    pub unit_price_column: f64,
+    pub vat_rate_column: f64,
+    pub recap_column: f64,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// The due date is at the `bottom`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentBlock {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QrBlock {
+    pub left: f64,
+    pub bottom: f64,
+    pub size: f64,
+}
+
+/// Continuation of the rows on the next pages. The next pages start with the title at the `top`, the rows continuing
+/// on the next page end at the `bottom`. Page numbers are aligned to the `right`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PagesBlock {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+    pub number_bottom: f64,
+    #[serde(flatten)]
+    pub style: Style,
+}
+
+/// Colour in the `#rrggbb` notation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Layout {
+    pub fn parse(json: &str) -> Result<Self, AnyError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Layout of the account, the default one when it has none (or it's broken).
+    pub fn of(account: &Account) -> Self {
+        match account.pdf_layout.as_deref().map(Layout::parse) {
+            Some(Ok(layout)) => layout,
+            Some(Err(e)) => {
+                warn!("Invalid PDF layout of account {}, using the default one: {}", account.id, e);
+                Layout::default()
+            }
+            None => Layout::default(),
+        }
+    }
+
+    /// Names of all the fonts the layout uses.
+    pub fn fonts(&self) -> BTreeSet<&str> {
+        let styles = [
+            Some(&self.header.style),
+            self.supplier.as_ref().map(|b| &b.style),
+            self.contact.as_ref().map(|b| &b.style),
+            self.legal.as_ref().map(|b| &b.style),
+            self.handover.as_ref().map(|b| &b.style),
+            self.customer.as_ref().map(|b| &b.style),
+            self.rows.as_ref().map(|b| &b.style),
+            self.payment.as_ref().map(|b| &b.style),
+            Some(&self.pages.style),
+        ];
+
+        styles
+            .into_iter()
+            .flatten()
+            .flat_map(|s| [s.font.as_str(), s.bold_font.as_str()])
+            .collect()
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::parse(DEFAULT).expect("The default layout must be valid")
+    }
+}
+
+impl LogoBlock {
+    fn default_some() -> Option<Self> {
+        Some(LogoBlock {
+            right: 190.0,
+            top: 277.0,
+            width: 60.0,
+            height: 25.0,
+        })
+    }
+}
+
+impl Default for PagesBlock {
+    fn default() -> Self {
+        PagesBlock {
+            left: 20.0,
+            right: 190.0,
+            top: 266.0,
+            bottom: 30.0,
+            number_bottom: 10.0,
+            style: Style {
+                size: 8.0,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            font: Style::default_font(),
+            bold_font: Style::default_bold_font(),
+            size: Style::default_size(),
+            color: Color::default(),
+        }
+    }
+}
+
+impl Style {
+    fn default_font() -> String {
+        String::from("CalibriLight")
+    }
+
+    fn default_bold_font() -> String {
+        String::from("CalibriBold")
+    }
+
+    fn default_size() -> f64 {
+        10.0
+    }
+}
+
+impl Color {
+    pub fn to_pdf(self) -> printpdf::Color {
+        let channel = |c: u8| c as f64 / 255.0;
+        printpdf::Color::Rgb(printpdf::Rgb::new(channel(self.red), channel(self.green), channel(self.blue), None))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .ok_or_else(|| format!("Invalid colour {}, expected #rrggbb", value))?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| format!("Invalid colour {}: {}", value, e));
+
+        Ok(Color {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+        })
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let layout = Layout::default();
+
+        assert_eq!(layout.header.style.size, 10.0);
+        assert_eq!(layout.contact.as_ref().unwrap().style.size, 8.0);
+        assert_eq!(layout.supplier.as_ref().unwrap().style.color, Color::default());
+        assert_eq!(layout.fonts().into_iter().collect::<Vec<_>>(), ["CalibriBold", "CalibriLight"]);
+
+        // serializes back to an equal template
+        assert_eq!(Layout::parse(&serde_json::to_string(&layout).unwrap()).unwrap(), layout);
+    }
+
+    #[test]
+    fn test_optional_blocks() {
+        let mut json: serde_json::Value = serde_json::from_str(DEFAULT).unwrap();
+        let template = json.as_object_mut().unwrap();
+        template.remove("qr");
+        template.remove("pages");
+        template.remove("logo");
+        template.insert("legal".to_owned(), serde_json::Value::Null);
+        template["rows"]["color"] = "#1A2b3c".into();
+
+        let layout = Layout::parse(&json.to_string()).unwrap();
+
+        assert_eq!(layout.qr, None);
+        assert_eq!(layout.legal, None);
+        assert_eq!(layout.pages, PagesBlock::default());
+        assert_eq!(layout.logo, LogoBlock::default_some());
+        assert_eq!(
+            layout.rows.unwrap().style.color,
+            Color {
+                red: 0x1a,
+                green: 0x2b,
+                blue: 0x3c
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_color() {
+        assert!(Color::try_from("1a2b3c".to_owned()).is_err());
+        assert!(Color::try_from("#1a2b3".to_owned()).is_err());
+        assert!(Color::try_from("#1a2b3g".to_owned()).is_err());
+        assert_eq!(Color::try_from("#ffffff".to_owned()).unwrap().to_string(), "#ffffff");
+    }
+}
@@ -0,0 +1,109 @@
+//! Logo of the entrepreneur, decoded into the pixels of an image of the PDF. Transparent parts are laid over white
+//! (PDF/A-1 doesn't allow transparency), SVGs are rasterized.
+
+use err_context::AnyError;
+use printpdf::image_crate::{self, GenericImageView};
+use printpdf::{ColorBits, ColorSpace, ImageXObject, Px};
+
+use crate::dao::LogoFormat;
+
+/// Larger images are scaled down to this width and height, in pixels (SVGs are rendered to this width).
+const MAX_PIXELS: u32 = 1200;
+
+/// Format of the image by its signature, SVG by its root element.
+pub fn detect_format(data: &[u8]) -> Option<LogoFormat> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(LogoFormat::Png)
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(LogoFormat::Jpeg)
+    } else if matches!(std::str::from_utf8(data), Ok(svg) if svg.contains("<svg")) {
+        Some(LogoFormat::Svg)
+    } else {
+        None
+    }
+}
+
+pub fn decode(format: LogoFormat, data: &[u8]) -> Result<ImageXObject, AnyError> {
+    let (width, height, rgba) = match format {
+        LogoFormat::Png | LogoFormat::Jpeg => {
+            let image = image_crate::load_from_memory(data)?;
+            let image = match image.width().max(image.height()) > MAX_PIXELS {
+                true => image.thumbnail(MAX_PIXELS, MAX_PIXELS).to_rgba8(),
+                false => image.to_rgba8(),
+            };
+            (image.width(), image.height(), image.into_raw())
+        }
+        LogoFormat::Svg => rasterize(data)?,
+    };
+
+    let rgb = rgba
+        .chunks(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as u32;
+            [0, 1, 2].map(|i| ((pixel[i] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8)
+        })
+        .collect();
+
+    Ok(ImageXObject {
+        width: Px(width as usize),
+        height: Px(height as usize),
+        color_space: ColorSpace::Rgb,
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: rgb,
+        image_filter: None,
+        clipping_bbox: None,
+    })
+}
+
+/// Renders the SVG over white, returns its size with its (opaque) pixels.
+fn rasterize(data: &[u8]) -> Result<(u32, u32, Vec<u8>), AnyError> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default().to_ref())?;
+    let size = tree.svg_node().size;
+    let height = (size.height() * MAX_PIXELS as f64 / size.width()).ceil() as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(MAX_PIXELS, height).ok_or("Invalid size of the SVG")?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(
+        &tree,
+        usvg::FitTo::Width(MAX_PIXELS),
+        tiny_skia::Transform::default(),
+        pixmap.as_mut(),
+    )
+    .ok_or("Could not render the SVG")?;
+
+    Ok((MAX_PIXELS, height, pixmap.take()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20">
+        <rect width="20" height="20" fill="#ff0000"/>
+    </svg>"##;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(b"\x89PNG\r\n\x1a\n..."), Some(LogoFormat::Png));
+        assert_eq!(detect_format(&[0xff, 0xd8, 0xff, 0xe0]), Some(LogoFormat::Jpeg));
+        assert_eq!(detect_format(SVG.as_bytes()), Some(LogoFormat::Svg));
+        assert_eq!(detect_format(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn test_decode_svg() {
+        let image = decode(LogoFormat::Svg, SVG.as_bytes()).unwrap();
+
+        assert_eq!((image.width, image.height), (Px(1200), Px(600)));
+        // red on the left, the transparent right over white
+        assert_eq!(image.image_data[..3], [255, 0, 0]);
+        assert_eq!(image.image_data[image.image_data.len() - 3..], [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode(LogoFormat::Png, b"\x89PNG\r\n\x1a\nbroken").is_err());
+        assert!(decode(LogoFormat::Svg, b"<svg").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Widths of texts in the fonts of the PDF, measured by the advances of their glyphs (printpdf doesn't kern, so the
+//! advances are what the texts take on the page).
+
+use std::collections::HashMap;
+
+use err_context::AnyError;
+use ttf_parser::{Face, GlyphId};
+
+const MM_PER_POINT: f64 = 25.4 / 72.0;
+
+#[derive(Debug)]
+pub struct FontMetrics {
+    /// Advances of the glyphs of the chars, in ems.
+    advances: HashMap<char, f64>,
+    /// Advance of the glyph the chars missing in the font are rendered with.
+    missing: f64,
+}
+
+impl FontMetrics {
+    pub fn parse(font: &[u8]) -> Result<Self, AnyError> {
+        let face = Face::from_slice(font, 0)?;
+        let units_per_em = face.units_per_em().ok_or("Font without its units per em")? as f64;
+        let advance = |glyph| face.glyph_hor_advance(glyph).unwrap_or(0) as f64 / units_per_em;
+
+        let mut advances = HashMap::new();
+        for subtable in face.character_mapping_subtables().filter(|s| s.is_unicode()) {
+            subtable.codepoints(|code| {
+                if let (Some(c), Some(glyph)) = (char::from_u32(code), subtable.glyph_index(code)) {
+                    advances.entry(c).or_insert_with(|| advance(glyph));
+                }
+            });
+        }
+
+        Ok(FontMetrics {
+            advances,
+            missing: advance(GlyphId(0)),
+        })
+    }
+
+    /// Width of the text in millimetres, in the font of the `size` in points.
+    pub fn width(&self, text: &str, size: f64) -> f64 {
+        let ems: f64 = text.chars().map(|c| self.advances.get(&c).copied().unwrap_or(self.missing)).sum();
+
+        ems * size * MM_PER_POINT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        let metrics = FontMetrics::parse(&std::fs::read("fonts/Calibri Light.ttf").unwrap()).unwrap();
+
+        assert_eq!(metrics.width("", 10.0), 0.0);
+        // digits are tabular
+        assert_eq!(metrics.width("1", 10.0), metrics.width("8", 10.0));
+        assert_eq!(metrics.width("12", 10.0), 2.0 * metrics.width("1", 10.0));
+        assert_eq!(metrics.width("12", 20.0), 2.0 * metrics.width("12", 10.0));
+        assert!(metrics.width("i", 10.0) < metrics.width("W", 10.0));
+        assert!(metrics.width("Kč", 10.0) > 0.0);
+    }
+}
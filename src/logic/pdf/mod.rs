@@ -4,55 +4,120 @@ use err_context::AnyError;
 use futures::channel::mpsc;
 use futures::{SinkExt, StreamExt};
 use itertools::Itertools;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use printpdf::image_crate::bmp::BmpDecoder;
 use printpdf::*;
 use std::collections::HashMap;
 use std::convert::Infallible;
-use std::fs::File;
-use std::io::BufWriter;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::sync::Arc;
 use std::{io, thread};
 
-use crate::dao::{Contact, Entrepreneur, Invoice, InvoiceRow, Vat};
+use crate::dao::{Contact, Entrepreneur, EntrepreneurLogo, Invoice, InvoiceKind, InvoiceRow, Money, Percent, Quantity, Vat};
+use crate::logic::bank_account::BankAccount;
+use crate::logic::currency;
+use crate::logic::discount::Discount;
+use crate::logic::export::facturx;
+use crate::logic::invoices::variable_symbol;
+use crate::logic::pdf::i18n::{Catalog, Texts};
+use crate::logic::pdf::layout::{Color, Layout, LogoBlock, PagesBlock, PaymentBlock, QrBlock, RowsBlock, Style};
+use crate::logic::pdf::metrics::FontMetrics;
 use crate::logic::pdf::qrcode::QrCode;
 use crate::logic::settings::AccountSettings;
+use crate::logic::vat::{self, VatRecap};
+use crate::logic::{Deposit, InvoiceDocument};
 
-// const PAPER_HEIGHT: f64 = 297.0;
 const PAPER_WIDTH: f64 = 210.0;
-const PAPER_BORDER: f64 = 20.0;
-const LINE_SPACE: f64 = 5.25;
-
-const WIDTH_NUMBER: f64 = 1.8;
-const WIDTH_SPACE: f64 = 0.75;
-const WIDTH_DOT: f64 = 0.8;
-const WIDTH_CURR_SYMBOL: f64 = 3.2; // TODO for hardcoded Kč
-
+const PAPER_HEIGHT: f64 = 297.0;
+/// Space between the item name and the quantity of a row.
+const NAME_COLUMN_GAP: f64 = 2.5;
+/// Space between the QR codes printed one above the other.
+const QR_CODE_GAP: f64 = 2.5;
+
+pub mod i18n;
+mod icc;
+pub mod layout;
+pub mod logo;
+mod metrics;
+mod pdfa;
 mod qrcode;
 
 #[derive(Debug, Clone)]
 pub struct PdfManager {
-    fonts: Arc<HashMap<String, String>>,
+    fonts: Arc<HashMap<String, FontFile>>,
+}
+
+/// Font file (in `fonts/`) with the metrics of its glyphs.
+#[derive(Debug)]
+struct FontFile {
+    file: String,
+    metrics: FontMetrics,
 }
 
 impl PdfManager {
     pub fn new() -> Result<Self, AnyError> {
         let mut fonts = HashMap::new();
-        fonts.insert(String::from("CalibriLight"), String::from("Calibri Light.ttf"));
-        fonts.insert(String::from("CalibriBold"), String::from("Calibri Bold.ttf"));
-        fonts.insert(String::from("CalibriLightItalic"), String::from("Calibri Light Italic.ttf"));
+        fonts.insert(String::from("CalibriLight"), FontFile::load("Calibri Light.ttf")?);
+        fonts.insert(String::from("CalibriBold"), FontFile::load("Calibri Bold.ttf")?);
+        fonts.insert(String::from("CalibriLightItalic"), FontFile::load("Calibri Light Italic.ttf")?);
         let fonts = Arc::new(fonts);
 
         Ok(PdfManager { fonts })
     }
 
-    pub fn create(
+    /// Fails for a layout with fonts the manager doesn't have.
+    pub fn check_layout(&self, layout: &Layout) -> Result<(), AnyError> {
+        match layout.fonts().into_iter().find(|font| !self.fonts.contains_key(*font)) {
+            Some(font) => Err(format!("Unknown font {}", font).into()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn create_document(&self, document: InvoiceDocument) -> impl futures::Stream<Item = Result<Bytes, Infallible>> {
+        let facturx = document.settings.invoice.facturx.then(|| facturx::create(&document));
+        let original_code = document.original.map(|original| original.code);
+
+        self.create(
+            document.settings,
+            document.layout,
+            document.entrepreneur,
+            document.logo,
+            document.bank_account,
+            document.contact,
+            document.invoice,
+            original_code,
+            document.deposit,
+            document.rows,
+            facturx,
+        )
+    }
+
+    /// Renders the whole document into memory.
+    pub async fn render(&self, document: InvoiceDocument) -> Vec<u8> {
+        self.create_document(document)
+            .fold(Vec::new(), |mut pdf, chunk| async move {
+                pdf.extend_from_slice(&chunk.unwrap_or_default());
+                pdf
+            })
+            .await
+    }
+
+    /// With the `facturx` XML, the PDF is PDF/A-3 with the XML embedded, PDF/A-2 if the account wants it archival.
+    #[allow(clippy::too_many_arguments)]
+    fn create(
         &self,
         settings: AccountSettings,
+        layout: Layout,
         entrepreneur: Entrepreneur,
+        logo: Option<EntrepreneurLogo>,
+        bank_account: Option<BankAccount>,
         contact: Contact,
         invoice: Invoice,
+        original_code: Option<String>,
+        deposit: Option<Deposit>,
         invoice_rows: Vec<InvoiceRow>,
+        facturx: Option<String>,
     ) -> impl futures::Stream<Item = Result<Bytes, Infallible>> {
         let (tx, rx) = mpsc::channel::<Vec<u8>>(1024);
         let w = BlockingWriter(tx);
@@ -60,12 +125,44 @@ impl PdfManager {
         let fonts = self.fonts.clone();
 
         thread::spawn(move || {
-            let creator = PdfCreator::from(fonts);
-            let doc = creator.create(settings, entrepreneur, contact, invoice, invoice_rows).unwrap();
+            let archival = settings.invoice.pdfa || facturx.is_some();
+            let texts = Texts::new(invoice.language.unwrap_or(contact.language), contact.secondary_language);
+            let description = pdfa::Description {
+                title: texts.fill(|c| c.document_title, &invoice.code),
+                author: entrepreneur.name.clone(),
+                identifier: invoice.code.clone(),
+            };
+
+            let currency = currency::symbol(&invoice.currency_code);
+            let creator = PdfCreator::new(fonts, archival, texts, layout, currency, &description.title);
+            let doc = creator
+                .create(
+                    settings,
+                    entrepreneur,
+                    logo,
+                    bank_account,
+                    contact,
+                    invoice,
+                    original_code,
+                    deposit,
+                    invoice_rows,
+                )
+                .unwrap();
+
+            let pdf = doc.save_to_bytes().map_err(AnyError::from).and_then(|pdf| match &facturx {
+                Some(xml) => pdfa::embed_facturx(&pdf, &description, xml),
+                None if archival => pdfa::convert(&pdf, &description),
+                None => Ok(pdf),
+            });
 
             // TODO how to handle errors? :-(
-            if let Err(e) = doc.save(&mut BufWriter::new(w)) {
-                debug!("Error while generating PDF: {}", e);
+            match pdf {
+                Ok(pdf) => {
+                    if let Err(e) = BufWriter::new(w).write_all(&pdf) {
+                        debug!("Error while writing PDF: {}", e);
+                    }
+                }
+                Err(e) => debug!("Error while generating PDF: {}", e),
             }
         });
 
@@ -74,189 +171,350 @@ impl PdfManager {
 }
 
 struct PdfCreator {
-    fonts: Arc<HashMap<String, String>>,
+    font_files: Arc<HashMap<String, FontFile>>,
     doc: PdfDocumentReference,
     current_layer: PdfLayerReference,
+    /// Layers of all the pages, the current one last.
+    pages: Vec<PdfLayerReference>,
+    texts: Texts,
+    layout: Layout,
+    /// Symbol (or code) of the currency of the amounts.
+    currency: String,
+}
+
+/// Fonts of a block with the size of its texts.
+#[derive(Clone, Copy)]
+struct TextStyle<'a> {
+    font: &'a IndirectFontRef,
+    bold: &'a IndirectFontRef,
+    metrics: &'a FontMetrics,
+    bold_metrics: &'a FontMetrics,
+    size: f64,
+}
+
+/// Font added to the document.
+struct DocumentFont<'a> {
+    reference: IndirectFontRef,
+    metrics: &'a FontMetrics,
 }
 
-impl From<Arc<HashMap<String, String>>> for PdfCreator {
-    fn from(fonts: Arc<HashMap<String, String>>) -> Self {
-        let (doc, page1, layer1) = PdfDocument::new("Faktura", Mm(210.0), Mm(297.0), "Layer 1");
+impl FontFile {
+    fn load(file: &str) -> Result<Self, AnyError> {
+        let metrics = FontMetrics::parse(&fs::read(format!("fonts/{}", file))?)?;
+
+        Ok(FontFile {
+            file: file.to_owned(),
+            metrics,
+        })
+    }
+}
+
+impl TextStyle<'_> {
+    /// Width of the text in the regular font.
+    fn width(&self, text: &str) -> f64 {
+        self.metrics.width(text, self.size)
+    }
+
+    /// The style with the bold font as its regular one.
+    fn bolded(self) -> Self {
+        TextStyle {
+            font: self.bold,
+            metrics: self.bold_metrics,
+            ..self
+        }
+    }
+}
+
+impl PdfCreator {
+    /// An `archival` document is prepared to be turned into PDF/A (by `pdfa`).
+    fn new(font_files: Arc<HashMap<String, FontFile>>, archival: bool, texts: Texts, layout: Layout, currency: &str, title: &str) -> Self {
+        let (doc, page1, layer1) = PdfDocument::new(title, Mm(PAPER_WIDTH), Mm(PAPER_HEIGHT), "Layer 1");
 
         let current_layer = doc.get_page(page1).get_layer(layer1);
 
         let doc = doc.with_conformance(PdfConformance::Custom(CustomPdfConformance {
-            requires_icc_profile: false,
-            requires_xmp_metadata: false,
+            identifier: if archival { String::from("PDF/A") } else { String::new() },
+            requires_icc_profile: archival,
+            requires_xmp_metadata: archival,
             ..Default::default()
         }));
 
-        PdfCreator { fonts, doc, current_layer }
+        PdfCreator {
+            font_files,
+            doc,
+            current_layer: current_layer.clone(),
+            pages: vec![current_layer],
+            texts,
+            layout,
+            currency: currency.to_owned(),
+        }
     }
-}
 
-impl PdfCreator {
-    fn load_font(&self, font_name: &str) -> Result<IndirectFontRef, AnyError> {
-        let path = format!("fonts/{}", self.fonts.get(font_name).unwrap());
-        self.doc
-            .add_external_font(File::open(path).map_err(AnyError::from)?)
-            .map_err(AnyError::from)
+    /// All the fonts of the layout, by their names.
+    fn load_fonts<'f>(&self, font_files: &'f HashMap<String, FontFile>) -> Result<HashMap<String, DocumentFont<'f>>, AnyError> {
+        self.layout
+            .fonts()
+            .into_iter()
+            .map(|name| {
+                let font_file = font_files.get(name).ok_or_else(|| format!("Unknown font {}", name))?;
+                let reference = self.doc.add_external_font(File::open(format!("fonts/{}", font_file.file))?)?;
+                let metrics = &font_file.metrics;
+                Ok((name.to_owned(), DocumentFont { reference, metrics }))
+            })
+            .collect()
     }
 
+    /// Sets the colour of the block's texts, returns its fonts.
+    fn use_style<'f>(&self, fonts: &'f HashMap<String, DocumentFont>, style: &Style) -> TextStyle<'f> {
+        self.current_layer.set_fill_color(style.color.to_pdf());
+        self.current_layer.set_outline_color(style.color.to_pdf());
+
+        TextStyle {
+            font: &fonts[&style.font].reference,
+            bold: &fonts[&style.bold_font].reference,
+            metrics: fonts[&style.font].metrics,
+            bold_metrics: fonts[&style.bold_font].metrics,
+            size: style.size,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn create(
-        self,
+        mut self,
         settings: AccountSettings,
         entrepreneur: Entrepreneur,
+        logo: Option<EntrepreneurLogo>,
+        bank_account: Option<BankAccount>,
         contact: Contact,
         invoice: Invoice,
+        original_code: Option<String>,
+        deposit: Option<Deposit>,
         invoice_rows: Vec<InvoiceRow>,
     ) -> Result<PdfDocumentReference, AnyError> {
         trace!("Using account settings: {:?}", settings);
 
-        let font_calibri_light = self.load_font("CalibriLight")?;
-        let font_calibri_bold = self.load_font("CalibriBold")?;
-
-        let outline_color = Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+        let font_files = self.font_files.clone();
+        let fonts = self.load_fonts(&font_files)?;
+        let layout = &self.layout.clone();
+        let line_space = layout.line_space;
 
-        self.current_layer.set_outline_color(outline_color);
         self.current_layer.set_outline_thickness(0.25);
 
         // ----
 
-        self.current_layer // TODO hard code value
-            .use_text("faktura", 44.0, Mm(PAPER_BORDER), Mm(266.0), &font_calibri_bold);
+        let header = &layout.header;
+        let style = self.use_style(&fonts, &header.style);
 
-        self.current_layer
-            .use_text(&invoice.code, 10.0, Mm(PAPER_BORDER), Mm(260.5), &font_calibri_light);
-
-        self.current_layer.use_text(
-            invoice.created.format("%d.%m.%Y").to_string(),
-            10.0,
-            Mm(PAPER_BORDER + 30.0),
-            Mm(260.5),
-            &font_calibri_light,
-        );
+        let title = self.texts.get(|c| c.title(invoice.kind));
+        let title_size = match (invoice.kind, self.texts.is_bilingual()) {
+            (InvoiceKind::Invoice, false) => header.title_sizes.invoice,
+            (_, false) => header.title_sizes.other,
+            (InvoiceKind::Invoice, true) => header.title_sizes.bilingual_invoice,
+            (_, true) => header.title_sizes.bilingual_other,
+        };
+
+        if let (Some(block), Some(logo)) = (&layout.logo, &logo) {
+            self.logo(block, logo);
+        }
+
+        let layer = &self.current_layer;
+
+        // the title in the accent colour of the entrepreneur
+        let accent_color = entrepreneur.accent_color.clone().and_then(|color| Color::try_from(color).ok());
+        if let Some(color) = accent_color {
+            layer.set_fill_color(color.to_pdf());
+        }
+        layer.use_text(title, title_size, Mm(header.left), Mm(header.title_bottom), style.bold);
+        if accent_color.is_some() {
+            layer.set_fill_color(header.style.color.to_pdf());
+        }
+        layer.use_text(&invoice.code, style.size, Mm(header.left), Mm(header.code_bottom), style.font);
+
+        let created = self.texts.catalog().format_date(invoice.created);
+        layer.use_text(created, style.size, Mm(header.date_left), Mm(header.code_bottom), style.font);
+
+        let note = match original_code {
+            Some(original_code) => Some(self.texts.fill(|c| c.credit_note_for, &original_code)),
+            None if invoice.kind == InvoiceKind::Proforma => Some(self.texts.get(|c| c.not_tax_document)),
+            None => None,
+        };
+
+        // the lowest line above the rows
+        let mut upper_bottom = header.code_bottom;
 
-        // entrepreneur
-        let offset_bottom = self.entrepreneur_box(
-            65.0,
-            230.0,
-            &font_calibri_light,
-            &font_calibri_bold,
-            "DODAVATEL", // TODO hard code value
-            &entrepreneur.name,
-            &entrepreneur.address,
-            Some(&entrepreneur.code),
-            &entrepreneur.vat,
-        )?;
-
-        let offset_bottom = self.contact_box(
-            65.0,
-            offset_bottom - 2.0 * LINE_SPACE,
-            &font_calibri_light,
-            &entrepreneur.phone,
-            &entrepreneur.email,
-        )?;
-
-        self.lawyer_bullshit_box(65.0, offset_bottom - 2.0 * LINE_SPACE, &font_calibri_light, "Městský úřad Litvínov")?; // TODO hard code value
-
-        if settings.invoice.show_lawyerbox_handover {
-            self.lawyer_bullshit_box2(132.5, offset_bottom - 2.0 * LINE_SPACE, &font_calibri_light)?;
+        if let Some(note) = note {
+            upper_bottom -= line_space;
+            layer.use_text(note, style.size, Mm(header.left), Mm(upper_bottom), style.font);
+        }
+
+        // entrepreneur, with the blocks following it
+        let mut flow = header.code_bottom;
+
+        if let Some(block) = &layout.supplier {
+            flow = self.entrepreneur_box(
+                block.left,
+                block.top,
+                block.width,
+                self.use_style(&fonts, &block.style),
+                &self.texts.get(|c| c.supplier),
+                &entrepreneur.name,
+                &entrepreneur.address,
+                Some(&entrepreneur.code),
+                &entrepreneur.vat,
+            );
+            upper_bottom = upper_bottom.min(flow);
+        }
+
+        if let Some(block) = &layout.contact {
+            let top = block.top.unwrap_or(flow - 2.0 * line_space);
+            let style = self.use_style(&fonts, &block.style);
+            flow = self.contact_box(block.left, top, style, &entrepreneur.phone, &entrepreneur.email)?;
+            upper_bottom = upper_bottom.min(flow);
+        }
+
+        let legal_top = layout.legal.as_ref().and_then(|b| b.top).unwrap_or(flow - 2.0 * line_space);
+
+        if let Some(block) = &layout.legal {
+            let office = "Městský úřad Litvínov"; // TODO hard code value
+            let style = self.use_style(&fonts, &block.style);
+            let bottom = self.lawyer_bullshit_box(block.left, legal_top, block.width, style, office);
+            upper_bottom = upper_bottom.min(bottom);
+        }
+
+        match &layout.handover {
+            Some(block) if settings.invoice.show_lawyerbox_handover => {
+                let style = self.use_style(&fonts, &block.style);
+                let bottom = self.lawyer_bullshit_box2(block.left, block.top.unwrap_or(legal_top), style);
+                upper_bottom = upper_bottom.min(bottom);
+            }
+            _ => {}
         }
 
         // contact
-        let _ = self.entrepreneur_box(
-            132.5,
-            230.0,
-            &font_calibri_light,
-            &font_calibri_bold,
-            "ODBĚRATEL", // TODO hard code value
-            &contact.name,
-            &contact.address,
-            contact.code.as_ref().map(AsRef::as_ref),
-            &contact.vat,
-        )?;
-
-        let total_price = invoice_rows.iter().fold(0f32, |tp, r| tp + r.item_count as f32 * r.item_price);
-
-        self.rows(65.0, 77.0, &font_calibri_light, &font_calibri_bold, invoice_rows)?;
-
-        self.payment_box(
-            65.0,
-            PAPER_BORDER + 25.0,
-            &font_calibri_light,
-            &font_calibri_bold,
-            total_price,
-            &entrepreneur.currency_code,
-            &entrepreneur.account_number_country_code,
-            entrepreneur.account_number_prefix.map(|p| p as u64),
-            entrepreneur.account_number as u64,
-            entrepreneur.account_bank_code as u16,
-            &invoice.code,
-            invoice.pay_until,
-        )?;
+        if let Some(block) = &layout.customer {
+            let bottom = self.entrepreneur_box(
+                block.left,
+                block.top,
+                block.width,
+                self.use_style(&fonts, &block.style),
+                &self.texts.get(|c| c.customer),
+                &contact.name,
+                &contact.address,
+                contact.code.as_ref().map(AsRef::as_ref),
+                &contact.vat,
+            );
+            upper_bottom = upper_bottom.min(bottom);
+        }
+
+        let vat_recap = VatRecap::new(&invoice, &invoice_rows);
+        // the paid advance is deducted from the amount to be paid only
+        let total_price = vat_recap.gross - deposit.as_ref().map_or(Money::ZERO, |d| d.amount);
+        let vs = variable_symbol(&invoice);
+
+        if let Some(block) = &layout.rows {
+            let top = upper_bottom - 2.0 * line_space;
+            self.rows(&fonts, block, top, &invoice, invoice_rows, &vat_recap, deposit.as_ref());
+        }
+
+        match &layout.payment {
+            Some(block) if total_price.is_negative() => {
+                self.refund_box(block, self.use_style(&fonts, &block.style), -total_price, invoice.pay_until);
+            }
+            Some(block) => {
+                let style = self.use_style(&fonts, &block.style);
+                self.payment_box(block, style, bank_account.as_ref(), &vs, invoice.pay_until);
+            }
+            None => {}
+        }
+
+        match (&layout.qr, &bank_account) {
+            (Some(block), Some(account)) if !total_price.is_negative() => {
+                let format = settings.invoice.qr_codes;
+                let mut images = Vec::new();
+
+                if format.spayd() {
+                    images.push(QrCode::spayd(total_price, &invoice.currency_code, &account.iban, &vs)?);
+                }
+                if format.epc(&invoice.currency_code) {
+                    images.push(QrCode::epc(total_price, account, &entrepreneur.name, &invoice.code)?);
+                }
+
+                // stacked up from the bottom of the block
+                for (i, image) in images.into_iter().enumerate() {
+                    self.qr_code(block, block.bottom + i as f64 * (block.size + QR_CODE_GAP), image);
+                }
+            }
+            _ => {}
+        }
+
+        self.page_numbers(&fonts);
 
         Ok(self.doc)
     }
 
+    /// Labels wrap to the `width` of the box.
     #[allow(clippy::too_many_arguments)]
     fn entrepreneur_box(
         &self,
         offset_left: f64,
         offset_bottom: f64,
-        font: &IndirectFontRef,
-        font_bold: &IndirectFontRef,
+        width: f64,
+        style: TextStyle,
         header: &str,
         name: &str,
         addr: &str,
         code: Option<&str>,
         vat: &Vat,
-    ) -> Result<f64, AnyError> {
+    ) -> f64 {
         let mut offset_bottom = offset_bottom;
+        let line_space = self.layout.line_space;
 
         let layer = &self.current_layer;
 
-        layer.use_text(header, 10.0, Mm(offset_left), Mm(offset_bottom), font);
-        offset_bottom -= 2.0 * LINE_SPACE;
+        offset_bottom = self.wrapped_text(header, offset_left, offset_bottom, width, style);
+        offset_bottom -= 2.0 * line_space;
 
-        for line in name.split("\r\n") {
-            offset_bottom -= LINE_SPACE;
-            layer.use_text(line, 10.0, Mm(offset_left), Mm(offset_bottom), font_bold)
+        for line in name
+            .split("\r\n")
+            .flat_map(|line| Self::wrap(line, width, |t| style.bolded().width(t)))
+        {
+            offset_bottom -= line_space;
+            layer.use_text(line, style.size, Mm(offset_left), Mm(offset_bottom), style.bold)
         }
 
-        for line in addr.split("\r\n") {
-            offset_bottom -= LINE_SPACE;
-            layer.use_text(line, 10.0, Mm(offset_left), Mm(offset_bottom), font)
+        for line in addr.split("\r\n").flat_map(|line| Self::wrap(line, width, |t| style.width(t))) {
+            offset_bottom -= line_space;
+            layer.use_text(line, style.size, Mm(offset_left), Mm(offset_bottom), style.font)
         }
 
         // ičo
-        offset_bottom -= 2.0 * LINE_SPACE;
+        offset_bottom -= 2.0 * line_space;
         let code = match code {
             None => String::new(),
-            Some(code) => format!("IČO {}", code), // TODO hard code value
+            Some(code) => self.texts.fill(|c| c.company_id, code),
         };
-        layer.use_text(code, 10.0, Mm(offset_left), Mm(offset_bottom), font);
+        offset_bottom = self.wrapped_text(&code, offset_left, offset_bottom, width, style);
 
         // dič
-        offset_bottom -= LINE_SPACE;
+        offset_bottom -= line_space;
         let vat = match vat {
-            Vat::Code(code) => Some(format!("DIČ {}", code)),    // TODO hard code value
-            Vat::NotTaxPayer => Some("Neplátce DPH".to_owned()), // TODO hard code value
+            Vat::Code(code) => Some(self.texts.fill(|c| c.vat_id, code)),
+            Vat::NotTaxPayer => Some(self.texts.get(|c| c.not_vat_payer)),
             Vat::DontDisplay => None,
         };
 
         if let Some(vat) = vat {
-            layer.use_text(vat, 10.0, Mm(offset_left), Mm(offset_bottom), font);
+            offset_bottom = self.wrapped_text(&vat, offset_left, offset_bottom, width, style);
         }
 
-        Ok(offset_bottom)
+        offset_bottom
     }
 
     fn contact_box(
         &self,
         offset_left: f64,
         offset_bottom: f64,
-        font: &IndirectFontRef,
+        style: TextStyle,
         phone: &Option<String>,
         email: &Option<String>,
     ) -> Result<f64, AnyError> {
@@ -267,129 +525,380 @@ impl PdfCreator {
         if let Some(phone) = phone {
             let phone = PdfCreator::split_phone_parts(phone);
 
-            layer.use_text(phone, 8.0, Mm(offset_left + 5.6), Mm(offset_bottom), font);
+            layer.use_text(phone, style.size, Mm(offset_left + 5.6), Mm(offset_bottom), style.font);
             self.add_img("icon_phone.bmp", offset_left, offset_bottom - 1.0)?;
-            offset_bottom -= LINE_SPACE;
+            offset_bottom -= self.layout.line_space;
         }
 
         if let Some(email) = email {
-            layer.use_text(email, 8.0, Mm(offset_left + 5.6), Mm(offset_bottom), font);
+            layer.use_text(email, style.size, Mm(offset_left + 5.6), Mm(offset_bottom), style.font);
             self.add_img("icon_mail.bmp", offset_left, offset_bottom - 1.0)?;
         }
 
         Ok(offset_bottom)
     }
 
-    fn lawyer_bullshit_box(
-        &self,
-        offset_left: f64,
-        offset_bottom: f64,
-        font: &IndirectFontRef,
-        office_place: &str,
-    ) -> Result<(), AnyError> {
+    /// The note in each of the languages, on its own lines.
+    fn lawyer_bullshit_box(&self, offset_left: f64, offset_bottom: f64, width: f64, style: TextStyle, office_place: &str) -> f64 {
+        let mut offset_bottom = offset_bottom + self.layout.line_space;
+
+        for catalog in self.texts.catalogs() {
+            let note = Catalog::fill(catalog.trade_licence, office_place);
+            offset_bottom = self.wrapped_text(&note, offset_left, offset_bottom - self.layout.line_space, width, style);
+        }
+
+        offset_bottom
+    }
+
+    fn lawyer_bullshit_box2(&self, offset_left: f64, offset_bottom: f64, style: TextStyle) -> f64 {
         let mut offset_bottom = offset_bottom;
 
         let layer = &self.current_layer;
 
         layer.use_text(
-            "Fyzická osoba zapsaná v Živnostenském rejstříku.", // TODO hard code value
-            8.0,
+            self.texts.get(|c| c.handed_over),
+            style.size,
             Mm(offset_left),
             Mm(offset_bottom),
-            font,
+            style.font,
         );
-        offset_bottom -= LINE_SPACE;
+        offset_bottom -= self.layout.line_space;
         layer.use_text(
-            "Úřad příslušný podle § 71 odst. 2 živnostenského", // TODO hard code value
-            8.0,
+            self.texts.get(|c| c.received_by),
+            style.size,
             Mm(offset_left),
             Mm(offset_bottom),
-            font,
+            style.font,
         );
-        offset_bottom -= LINE_SPACE; // TODO hard code value
-        layer.use_text(format!("zákona: {}.", office_place), 8.0, Mm(offset_left), Mm(offset_bottom), font);
 
-        Ok(())
+        offset_bottom
     }
 
-    fn lawyer_bullshit_box2(&self, offset_left: f64, offset_bottom: f64, font: &IndirectFontRef) -> Result<(), AnyError> {
-        let mut offset_bottom = offset_bottom;
+    /// Rows of the invoice above the totals. The rows which don't fit between the `top` and the totals continue on the
+    /// next pages, the totals (and everything below them) are on the last page.
+    #[allow(clippy::too_many_arguments)]
+    fn rows(
+        &mut self,
+        fonts: &HashMap<String, DocumentFont>,
+        block: &RowsBlock,
+        top: f64,
+        invoice: &Invoice,
+        invoice_rows: Vec<InvoiceRow>,
+        vat_recap: &VatRecap,
+        deposit: Option<&Deposit>,
+    ) {
+        let line_space = self.layout.line_space;
+        let pages_block = self.layout.pages.clone();
+
+        let use_decs = invoice_rows
+            .iter()
+            .any(|r| !r.item_price.is_integer() || !vat::row_price(r).is_integer() || !vat::row_discount(r).is_integer())
+            || !vat_recap.vat.is_integer()
+            || !vat_recap.discount.is_integer()
+            || deposit.is_some_and(|d| !d.amount.is_integer());
+        let show_vat = vat_recap.has_vat();
+
+        // the languages of the column headers stack upwards
+        let header_space = (self.texts.catalogs().count() - 1) as f64 * block.header_size * 0.44;
+        let first_top = top - header_space;
+        let next_top = pages_block.top - 3.0 * line_space - header_space;
+
+        let style = self.use_style(fonts, &block.style);
+        let heights = invoice_rows.iter().map(|r| self.row_height(block, style, r)).collect_vec();
+        let totals_top = block.bottom + self.totals_height(vat_recap, show_vat, deposit.is_some());
+        let pages = Self::paginate(&heights, first_top, next_top, pages_block.bottom, totals_top);
+
+        let mut rows = invoice_rows.into_iter();
+        let mut heights = heights.into_iter();
+
+        for (page, count) in pages.iter().enumerate() {
+            if page > 0 {
+                self.add_page();
+                let style = self.use_style(fonts, &pages_block.style);
+                self.continuation_header(&pages_block, style, &invoice.code);
+            }
 
-        let layer = &self.current_layer;
+            let page_heights = heights.by_ref().take(*count).collect_vec();
+            let mut offset_bottom = match (page, pages.len()) {
+                // a single page keeps the rows right above the totals
+                (0, 1) => totals_top + page_heights.iter().sum::<f64>(),
+                (0, _) => first_top,
+                _ => next_top,
+            };
 
-        // TODO hard code value
-        layer.use_text("Předáno dne:", 8.0, Mm(offset_left), Mm(offset_bottom), font);
-        offset_bottom -= LINE_SPACE;
-        layer.use_text("Převzal:", 8.0, Mm(offset_left), Mm(offset_bottom), font);
+            let style = self.use_style(fonts, &block.style);
+            self.rows_header(block, style, show_vat, offset_bottom);
 
-        Ok(())
+            for (row, height) in rows.by_ref().take(*count).zip(page_heights) {
+                offset_bottom -= height;
+                self.row(block, style, &row, use_decs, show_vat, offset_bottom);
+            }
+
+            if page + 1 < pages.len() {
+                let style = self.use_style(fonts, &pages_block.style);
+                let text = self.texts.get(|c| c.continued_next);
+                let bottom = Mm(pages_block.number_bottom);
+                self.current_layer
+                    .use_text(text, style.size, Mm(pages_block.left), bottom, style.font);
+            }
+        }
+
+        let style = self.use_style(fonts, &block.style);
+        let discount = Discount::of_invoice(invoice);
+        self.totals(block, style, vat_recap, &discount, deposit, use_decs, show_vat);
     }
 
-    fn rows(
-        &self,
-        offset_left: f64,
-        offset_bottom: f64,
-        font: &IndirectFontRef,
-        font_bold: &IndirectFontRef,
-        mut invoice_rows: Vec<InvoiceRow>,
-    ) -> Result<(), AnyError> {
-        let mut offset_bottom = offset_bottom;
-        // from bottom up!!!
+    /// Numbers of the rows on the pages. The rows fill the pages from the `first_top` (and `next_top` on the next
+    /// pages) down to the `bottom`, the last page ends with the totals at the `totals_top` and has at least one row.
+    fn paginate(heights: &[f64], first_top: f64, next_top: f64, bottom: f64, totals_top: f64) -> Vec<usize> {
+        let mut pages = Vec::new();
+        let mut rest = heights;
+        let mut top = first_top;
+
+        loop {
+            // a row taller than the page can't be split, it just overflows it
+            if top - rest.iter().sum::<f64>() >= totals_top || rest.is_empty() || (rest.len() == 1 && !pages.is_empty()) {
+                pages.push(rest.len());
+                return pages;
+            }
+
+            let fitting = rest
+                .iter()
+                .scan(top, |offset, height| {
+                    *offset -= height;
+                    Some(*offset)
+                })
+                .take_while(|offset| *offset >= bottom)
+                .count();
+            let count = fitting.min(rest.len() - 1).max(if pages.is_empty() { 0 } else { 1 });
+
+            pages.push(count);
+            rest = &rest[count..];
+            top = next_top;
+        }
+    }
+
+    fn add_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAPER_WIDTH), Mm(PAPER_HEIGHT), "Layer 1");
+
+        self.current_layer = self.doc.get_page(page).get_layer(layer);
+        self.current_layer.set_outline_thickness(0.25);
+        self.pages.push(self.current_layer.clone());
+    }
 
+    /// Title of the document at the top of the next pages.
+    fn continuation_header(&self, block: &PagesBlock, style: TextStyle, code: &str) {
         let layer = &self.current_layer;
 
-        let (total_price, use_decs) = invoice_rows.iter().fold((0f64, false), |(tp, decs), r| {
-            let row_price = r.item_count as f64 * r.item_price as f64;
-            let decs = decs || row_price % 1.0 != 0.0;
-            (tp + row_price, decs)
-        });
+        let title = self.texts.fill(|c| c.document_title, code);
+        layer.use_text(title, style.size, Mm(block.left), Mm(block.top), style.bold);
 
-        let line = vec![
-            (Point::new(Mm(offset_left), Mm(offset_bottom + 6.0)), false),
-            (Point::new(Mm(PAPER_WIDTH - PAPER_BORDER), Mm(offset_bottom + 6.0)), false),
-        ];
+        let text = self.texts.get(|c| c.continued_previous);
+        let bottom = Mm(block.top - self.layout.line_space);
+        layer.use_text(text, style.size, Mm(block.left), bottom, style.font);
+    }
 
-        let total_price_formatted = PdfCreator::format_price(total_price, use_decs);
+    /// Numbers of the pages (`1/3`), when there's more than one.
+    fn page_numbers(&self, fonts: &HashMap<String, DocumentFont>) {
+        let block = &self.layout.pages;
+        let font = &fonts[&block.style.font];
 
-        layer.use_text(
-            format!("{} Kč", total_price_formatted), // TODO hard code value
-            10.0,
-            Mm(PAPER_WIDTH - PAPER_BORDER - Self::price_width(&total_price_formatted) - WIDTH_SPACE - WIDTH_CURR_SYMBOL),
-            Mm(offset_bottom),
-            font_bold,
-        );
-        offset_bottom += 2.0 * 3.0 + LINE_SPACE;
+        if self.pages.len() < 2 {
+            return;
+        }
 
-        invoice_rows.reverse();
+        for (i, layer) in self.pages.iter().enumerate() {
+            let number = format!("{}/{}", i + 1, self.pages.len());
+            let left = block.right - font.metrics.width(&number, block.style.size);
 
-        for row in invoice_rows {
-            let price = row.item_count as f64 * row.item_price as f64;
+            layer.set_fill_color(block.style.color.to_pdf());
+            layer.use_text(number, block.style.size, Mm(left), Mm(block.number_bottom), &font.reference);
+        }
+    }
+
+    fn row_height(&self, block: &RowsBlock, style: TextStyle, row: &InvoiceRow) -> f64 {
+        let discount_lines = if vat::row_discount(row) != Money::ZERO { 1 } else { 0 };
 
-            let mut item_name_rows = row.item_name.split("\r\n").collect_vec();
-            item_name_rows.reverse(); // because rows are rendered from bottom
+        (self.item_name_lines(block, style, row).len() + discount_lines) as f64 * self.layout.line_space
+    }
 
-            let mut base_row = true;
-            for item_name_row in item_name_rows {
-                layer.use_text(item_name_row, 10.0, Mm(offset_left), Mm(offset_bottom), font);
+    /// Lines of the item name, wrapped to the space left of the quantity.
+    fn item_name_lines(&self, block: &RowsBlock, style: TextStyle, row: &InvoiceRow) -> Vec<String> {
+        let width = Self::quantity_left(block, style, row, self.texts.catalog()) - NAME_COLUMN_GAP - block.left;
 
-                if base_row {
-                    let price_formatted = PdfCreator::format_price(price, use_decs);
+        row.item_name
+            .split("\r\n")
+            .flat_map(|line| Self::wrap(line, width, |t| style.width(t)))
+            .collect()
+    }
 
-                    let left_align = PAPER_WIDTH - PAPER_BORDER - Self::price_width(&price_formatted) - WIDTH_SPACE - WIDTH_CURR_SYMBOL;
+    /// Left of the quantity of the row, aligned to the right of its column.
+    fn quantity_left(block: &RowsBlock, style: TextStyle, row: &InvoiceRow, catalog: &Catalog) -> f64 {
+        let quantity = Self::format_quantity(row.item_count, &row.item_unit, catalog);
 
-                    layer.use_text(
-                        format!("{} Kč", price_formatted), // TODO hard code value
-                        10.0,
-                        Mm(left_align),
-                        Mm(offset_bottom),
-                        font,
-                    );
+        block.right - block.quantity_column - style.width(&quantity)
+    }
 
-                    base_row = false;
+    /// Renders the row upwards from the `offset_bottom` (its discount below it).
+    #[allow(clippy::too_many_arguments)]
+    fn row(&self, block: &RowsBlock, style: TextStyle, row: &InvoiceRow, use_decs: bool, show_vat: bool, offset_bottom: f64) {
+        let layer = &self.current_layer;
+        let (font, size, right_border) = (style.font, style.size, block.right);
+        let mut offset_bottom = offset_bottom;
+
+        let price = vat::row_price(row);
+        let discount = vat::row_discount(row);
+
+        if discount != Money::ZERO {
+            let label = Self::discount_label(&self.texts.get(|c| c.row_discount), &Discount::of_row(row), self.texts.catalog());
+            layer.use_text(label, block.header_size, Mm(block.left + 3.0), Mm(offset_bottom), font);
+            self.price_text(-discount, use_decs, right_border, offset_bottom, style);
+            offset_bottom += self.layout.line_space;
+        }
+
+        let mut item_name_rows = self.item_name_lines(block, style, row);
+        item_name_rows.reverse(); // because rows are rendered from bottom
+
+        let mut base_row = true;
+        for item_name_row in item_name_rows {
+            layer.use_text(item_name_row, size, Mm(block.left), Mm(offset_bottom), font);
+
+            if base_row {
+                let quantity = Self::format_quantity(row.item_count, &row.item_unit, self.texts.catalog());
+                let quantity_left = Self::quantity_left(block, style, row, self.texts.catalog());
+                layer.use_text(quantity, size, Mm(quantity_left), Mm(offset_bottom), font);
+
+                let unit_price_right = right_border - block.unit_price_column;
+                self.price_text(row.item_price, use_decs, unit_price_right, offset_bottom, style);
+
+                if show_vat {
+                    // the column is too narrow for both the languages
+                    let rate = Self::vat_rate_label(row.vat_rate.map(|r| r as u16), self.texts.catalog().vat_exempt);
+                    layer.use_text(rate, size, Mm(right_border - block.vat_rate_column), Mm(offset_bottom), font);
                 }
 
-                offset_bottom += LINE_SPACE;
+                self.price_text(price, use_decs, right_border, offset_bottom, style);
+
+                base_row = false;
+            }
+
+            offset_bottom += self.layout.line_space;
+        }
+    }
+
+    fn rows_header(&self, block: &RowsBlock, style: TextStyle, show_vat: bool, offset_bottom: f64) {
+        let (font, header_size, right_border) = (style.font, block.header_size, block.right);
+
+        let quantity_left = right_border - block.quantity_column - 11.5;
+        self.column_header(|c| c.quantity, quantity_left, offset_bottom, font, header_size);
+        let unit_price_left = right_border - block.unit_price_column - 14.0;
+        self.column_header(|c| c.unit_price, unit_price_left, offset_bottom, font, header_size);
+        if show_vat {
+            self.column_header(|c| c.vat, right_border - block.vat_rate_column, offset_bottom, font, header_size);
+        }
+        self.column_header(|c| c.total, right_border - 9.5, offset_bottom, font, header_size);
+    }
+
+    /// Height of the totals rendered by `totals`.
+    fn totals_height(&self, vat_recap: &VatRecap, show_vat: bool, deposit: bool) -> f64 {
+        let line_space = self.layout.line_space;
+
+        let recap = if show_vat {
+            (vat_recap.lines.len() + 2) as f64 * line_space
+        } else {
+            0.0
+        };
+        let discount = if vat_recap.discount != Money::ZERO { 3.0 * line_space } else { 0.0 };
+        let deposit = if deposit { 2.0 * line_space } else { 0.0 };
+
+        2.0 * 3.0 + line_space + recap + discount + deposit
+    }
+
+    /// The total price with the VAT recapitulation and the invoice discount above it. The paid advance is deducted
+    /// below the total price.
+    #[allow(clippy::too_many_arguments)]
+    fn totals(
+        &self,
+        block: &RowsBlock,
+        style: TextStyle,
+        vat_recap: &VatRecap,
+        invoice_discount: &Discount,
+        deposit: Option<&Deposit>,
+        use_decs: bool,
+        show_vat: bool,
+    ) {
+        let offset_left = block.left;
+        let mut offset_bottom = block.bottom;
+        // from bottom up!!!
+
+        let layer = &self.current_layer;
+        let line_space = self.layout.line_space;
+        let (font, size, header_size) = (style.font, style.size, block.header_size);
+
+        let right_border = block.right;
+        let recap_column = block.recap_column;
+
+        match deposit {
+            Some(deposit) => {
+                let bold = style.bolded();
+                layer.use_text(
+                    self.texts.get(|c| c.amount_due),
+                    size,
+                    Mm(offset_left),
+                    Mm(offset_bottom),
+                    bold.font,
+                );
+                self.price_text(vat_recap.gross - deposit.amount, use_decs, right_border, offset_bottom, bold);
+                offset_bottom += line_space;
+
+                let label = self.texts.fill(|c| c.paid_deposit, &deposit.proforma.code);
+                layer.use_text(label, size, Mm(offset_left), Mm(offset_bottom), font);
+                self.price_text(-deposit.amount, use_decs, right_border, offset_bottom, style);
+                offset_bottom += line_space;
+
+                layer.use_text(self.texts.get(|c| c.total), size, Mm(offset_left), Mm(offset_bottom), font);
+                self.price_text(vat_recap.gross, use_decs, right_border, offset_bottom, style);
+            }
+            None => self.price_text(vat_recap.gross, use_decs, right_border, offset_bottom, style.bolded()),
+        }
+        offset_bottom += 2.0 * 3.0;
+
+        let line = vec![
+            (Point::new(Mm(offset_left), Mm(offset_bottom)), false),
+            (Point::new(Mm(right_border), Mm(offset_bottom)), false),
+        ];
+
+        offset_bottom += line_space;
+
+        if show_vat {
+            for recap_line in vat_recap.lines.iter().rev() {
+                let rate = Self::vat_rate_label(recap_line.rate, &self.texts.get(|c| c.vat_exempt));
+                layer.use_text(rate, size, Mm(offset_left), Mm(offset_bottom), font);
+                let base_right = right_border - recap_column * 2.0;
+                self.price_text(recap_line.base, use_decs, base_right, offset_bottom, style);
+                self.price_text(recap_line.vat, use_decs, right_border - recap_column, offset_bottom, style);
+                self.price_text(recap_line.gross, use_decs, right_border, offset_bottom, style);
+
+                offset_bottom += line_space;
             }
+
+            self.column_header(|c| c.vat_rate, offset_left, offset_bottom, font, header_size);
+            let base_left = right_border - recap_column * 2.0 - 9.0;
+            self.column_header(|c| c.vat_base, base_left, offset_bottom, font, header_size);
+            self.column_header(|c| c.vat, right_border - recap_column - 6.0, offset_bottom, font, header_size);
+            self.column_header(|c| c.total, right_border - 9.5, offset_bottom, font, header_size);
+
+            offset_bottom += 2.0 * line_space;
+        }
+
+        if vat_recap.discount != Money::ZERO {
+            let label = Self::discount_label(&self.texts.get(|c| c.invoice_discount), invoice_discount, self.texts.catalog());
+            layer.use_text(label, size, Mm(offset_left), Mm(offset_bottom), font);
+            self.price_text(-vat_recap.discount, use_decs, right_border, offset_bottom, style);
+            offset_bottom += line_space;
+
+            layer.use_text(self.texts.get(|c| c.subtotal), size, Mm(offset_left), Mm(offset_bottom), font);
+            self.price_text(vat_recap.subtotal, use_decs, right_border, offset_bottom, style);
         }
 
         let line = Line {
@@ -401,96 +910,216 @@ impl PdfCreator {
         };
 
         self.current_layer.add_shape(line);
+    }
 
-        Ok(())
+    /// The price with the currency.
+    fn amount(&self, price: Money, use_decs: bool) -> String {
+        format!(
+            "{} {}",
+            PdfCreator::format_price(price, use_decs, self.texts.catalog()),
+            self.currency
+        )
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn payment_box(
+    /// Renders the price with currency, aligned to the right to `offset_right`.
+    fn price_text(&self, price: Money, use_decs: bool, offset_right: f64, offset_bottom: f64, style: TextStyle) {
+        let text = self.amount(price, use_decs);
+        let left_align = offset_right - style.width(&text);
+
+        self.current_layer
+            .use_text(text, style.size, Mm(left_align), Mm(offset_bottom), style.font);
+    }
+
+    fn discount_label(label: &str, discount: &Discount, catalog: &Catalog) -> String {
+        match discount.percent {
+            Some(percent) => format!("{} {} %", label, Self::format_percent(percent, catalog)),
+            None => label.to_owned(),
+        }
+    }
+
+    fn vat_rate_label(rate: Option<u16>, exempt: &str) -> String {
+        match rate {
+            Some(rate) => format!("{} %", rate),
+            None => exempt.to_owned(),
+        }
+    }
+
+    /// Header of a column, in two languages one above the other (the primary one on top).
+    fn column_header(
         &self,
+        text: impl Fn(&Catalog) -> &'static str,
         offset_left: f64,
-        mut offset_bottom: f64,
+        offset_bottom: f64,
         font: &IndirectFontRef,
-        font_bold: &IndirectFontRef,
-        price: f32,
-        currency: &str,
-        account_number_country_code: &str,
-        account_prefix: Option<u64>,
-        account_no: u64,
-        bank_code: u16,
-        vs: &str,
-        due_date: NaiveDate,
-    ) -> Result<(), AnyError> {
-        let layer = &self.current_layer;
+        size: f64,
+    ) {
+        let catalogs = self.texts.catalogs().collect_vec();
+
+        for (i, catalog) in catalogs.iter().enumerate() {
+            let offset_bottom = offset_bottom + (catalogs.len() - 1 - i) as f64 * size * 0.44;
+            let layer = &self.current_layer;
+            layer.use_text(text(catalog), size, Mm(offset_left), Mm(offset_bottom), font);
+        }
+    }
 
-        // TODO hard code value
-        layer.use_text("PLATEBNÍ ÚDAJE", 10.0, Mm(offset_left), Mm(offset_bottom), font);
+    /// The domestic account number is followed by the IBAN, the IBAN of a foreign account by the BIC.
+    fn payment_box(&self, block: &PaymentBlock, style: TextStyle, account: Option<&BankAccount>, vs: &str, due_date: NaiveDate) {
+        let layer = &self.current_layer;
 
-        offset_bottom -= 2.0 * LINE_SPACE;
+        let texts = self.texts;
+        let (offset_left, width) = (block.left, block.right - block.left);
+        let mut offset_bottom = block.top;
 
-        // TODO hard code value
-        layer.use_text("zaplaťte prosím na účet č.", 10.0, Mm(offset_left), Mm(offset_bottom), font);
         layer.use_text(
-            format!("{}/{:04}", account_no, bank_code),
-            10.0,
-            Mm(offset_left + 37.0),
+            texts.get(|c| c.payment_details),
+            style.size,
+            Mm(offset_left),
             Mm(offset_bottom),
-            font_bold,
+            style.font,
         );
 
-        offset_bottom -= LINE_SPACE;
+        offset_bottom -= 2.0 * self.layout.line_space;
+
+        if let Some(account) = account {
+            let label = texts.get(|c| c.pay_to_account);
+            offset_bottom = self.labeled_text(&label, &account.to_string(), offset_left, offset_bottom, width, style);
+            offset_bottom -= self.layout.line_space;
+
+            let details = match (account.domestic(), &account.bic) {
+                (Some(_), Some(bic)) => Some(("IBAN", format!("{}, BIC {}", account.iban, bic))),
+                (Some(_), None) => Some(("IBAN", account.iban.to_string())),
+                (None, Some(bic)) => Some(("BIC", bic.clone())),
+                (None, None) => None,
+            };
+            if let Some((label, value)) = details {
+                offset_bottom = self.labeled_text(label, &value, offset_left, offset_bottom, width, style);
+                offset_bottom -= self.layout.line_space;
+            }
+        }
 
-        // TODO hard code value
-        layer.use_text("s variabilním symbolem", 10.0, Mm(offset_left), Mm(offset_bottom), font);
-        layer.use_text(vs, 10.0, Mm(offset_left + 34.5), Mm(offset_bottom), font_bold);
+        let label = texts.get(|c| c.variable_symbol);
+        self.labeled_text(&label, vs, offset_left, offset_bottom, width, style);
 
-        offset_bottom = PAPER_BORDER;
+        let (label, due_date) = (texts.get(|c| c.due_date), texts.catalog().format_date(due_date));
+        self.labeled_text(&label, &due_date, offset_left, block.bottom, width, style);
+    }
 
-        // TODO hard code value
-        layer.use_text("do", 10.0, Mm(offset_left), Mm(offset_bottom), font);
-        layer.use_text(
-            due_date.format("%d.%m.%Y").to_string(),
-            10.0,
-            Mm(offset_left + 5.0),
-            Mm(offset_bottom),
-            font_bold,
+    /// A broken logo is left out, the invoice is rendered without it.
+    fn logo(&self, block: &LogoBlock, logo: &EntrepreneurLogo) {
+        let image = match logo::decode(logo.format, &logo.data) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!("Invalid logo of entrepreneur {}: {}", logo.entrepreneur_id, e);
+                return;
+            }
+        };
+
+        // the resolution at which the image fills the box in one of its dimensions
+        let (width, height) = (image.width.0 as f64, image.height.0 as f64);
+        let dpi = (width / block.width).max(height / block.height) * 25.4;
+
+        Image::from(image).add_to_layer(
+            self.current_layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(block.right - width / dpi * 25.4)),
+                translate_y: Some(Mm(block.top - height / dpi * 25.4)),
+                rotate: None,
+                scale_x: None,
+                scale_y: None,
+                dpi: Some(dpi),
+            },
         );
+    }
 
+    fn qr_code(&self, block: &QrBlock, bottom: f64, image_data: Vec<u8>) {
         let qrcode = ImageXObject {
             width: Px(256),
             height: Px(256),
             color_space: ColorSpace::Greyscale,
             bits_per_component: ColorBits::Bit8,
             interpolate: true,
-            image_data: QrCode::get(
-                price,
-                currency,
-                account_number_country_code,
-                account_prefix,
-                account_no,
-                bank_code,
-                vs,
-            )?,
+            image_data,
             image_filter: None,
             clipping_bbox: None,
         };
 
-        let qr_size = 30.0;
-
         let qrcode_image = Image::from(qrcode);
         qrcode_image.add_to_layer(
             self.current_layer.clone(),
             ImageTransform {
-                translate_x: Some(Mm(PAPER_BORDER - 1.2)),
-                translate_y: Some(Mm(PAPER_BORDER - 1.2)),
+                translate_x: Some(Mm(block.left)),
+                translate_y: Some(Mm(bottom)),
                 rotate: None,
                 scale_x: None,
                 scale_y: None,
-                dpi: Some(2.54 / (qr_size / 10.0) * 256.0),
+                dpi: Some(2.54 / (block.size / 10.0) * 256.0),
             },
         );
+    }
 
-        Ok(())
+    /// Replaces the payment box when the amount is to be paid back to the customer (a credit note).
+    fn refund_box(&self, block: &PaymentBlock, style: TextStyle, price: Money, due_date: NaiveDate) {
+        let layer = &self.current_layer;
+        let line_space = self.layout.line_space;
+
+        let texts = self.texts;
+        let (offset_left, width) = (block.left, block.right - block.left);
+
+        layer.use_text(texts.get(|c| c.refund), style.size, Mm(offset_left), Mm(block.top), style.font);
+
+        let amount = self.amount(price, !price.is_integer());
+        let label = texts.get(|c| c.refund_amount);
+        let offset_bottom = self.labeled_text(&label, &amount, offset_left, block.top - 2.0 * line_space, width, style);
+        let account = texts.get(|c| c.refund_account);
+        self.wrapped_text(&account, offset_left, offset_bottom - line_space, width, style);
+
+        let (label, due_date) = (texts.get(|c| c.due_date), texts.catalog().format_date(due_date));
+        self.labeled_text(&label, &due_date, offset_left, block.bottom, width, style);
+    }
+
+    /// Renders the text wrapped to the `width`, line by line downwards. Returns the offset of the last line.
+    fn wrapped_text(&self, text: &str, offset_left: f64, offset_bottom: f64, width: f64, style: TextStyle) -> f64 {
+        let line_space = self.layout.line_space;
+        let mut offset_bottom = offset_bottom + line_space;
+
+        for line in Self::wrap(text, width, |t| style.width(t)) {
+            offset_bottom -= line_space;
+            self.current_layer
+                .use_text(line, style.size, Mm(offset_left), Mm(offset_bottom), style.font);
+        }
+
+        offset_bottom
+    }
+
+    /// Renders the label followed by the bold value, placed after the label by the widths of its glyphs. The label
+    /// wraps to the `width` (the value moves to the next line when it doesn't fit). Returns the offset of the last line.
+    fn labeled_text(&self, label: &str, value: &str, left: f64, bottom: f64, width: f64, style: TextStyle) -> f64 {
+        let layer = &self.current_layer;
+        let mut bottom = bottom;
+
+        let mut lines = Self::wrap(label, width, |t| style.width(t));
+        let mut last = lines.pop().unwrap_or_default();
+        if style.width(&format!("{} ", last)) + style.bolded().width(value) > width {
+            lines.push(last);
+            last = String::new();
+        }
+
+        for line in lines {
+            layer.use_text(line, style.size, Mm(left), Mm(bottom), style.font);
+            bottom -= self.layout.line_space;
+        }
+
+        layer.begin_text_section();
+        layer.set_text_cursor(Mm(left), Mm(bottom));
+        if !last.is_empty() {
+            layer.set_font(style.font, style.size);
+            layer.write_text(format!("{} ", last), style.font);
+        }
+        layer.set_font(style.bold, style.size);
+        layer.write_text(value, style.bold);
+        layer.end_text_section();
+
+        bottom
     }
 
     fn add_img(&self, path: &str, x: f64, y: f64) -> Result<(), AnyError> {
@@ -512,12 +1141,38 @@ impl PdfCreator {
         Ok(())
     }
 
-    fn price_width(formatted: &str) -> f64 {
-        formatted.chars().fold(0f64, |sum, c| match c {
-            '.' => sum + WIDTH_DOT,
-            ' ' => sum + WIDTH_SPACE,
-            _ => sum + WIDTH_NUMBER,
-        })
+    /// Splits the text at spaces into lines no wider than the `width` as measured by the `measure`; a longer word
+    /// makes a line of its own.
+    fn wrap(text: &str, width: f64, measure: impl Fn(&str) -> f64) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+
+        for word in text.split(' ') {
+            match lines.last_mut() {
+                Some(line) if measure(&format!("{} {}", line, word)) <= width => {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_owned()),
+            }
+        }
+
+        lines
+    }
+
+    /// Quantity with the unit, without trailing zeros (e.g. `1.5 hod`).
+    fn format_quantity(quantity: Quantity, unit: &Option<String>, catalog: &Catalog) -> String {
+        let quantity = quantity.to_string();
+        let quantity = catalog.format_number(quantity.trim_end_matches('0').trim_end_matches('.'));
+
+        match unit {
+            Some(unit) if !unit.is_empty() => format!("{} {}", quantity, unit),
+            _ => quantity,
+        }
+    }
+
+    fn format_percent(percent: Percent, catalog: &Catalog) -> String {
+        let percent = percent.to_string();
+        catalog.format_number(percent.trim_end_matches('0').trim_end_matches('.'))
     }
 
     fn split_phone_parts(phone: &str) -> String {
@@ -541,20 +1196,16 @@ impl PdfCreator {
         tmp.iter().collect()
     }
 
-    fn format_price(price: f64, use_decimals: bool) -> String {
-        let prec: usize = if use_decimals { 2 } else { 0 };
-        let places: usize = if use_decimals { 6 } else { 3 }; // 000.00 == 6 chars
+    fn format_price(price: Money, use_decimals: bool, catalog: &Catalog) -> String {
+        let price = if use_decimals { price } else { price.rescale::<0>().rescale() };
 
-        if price < 1_000f64 {
-            format!("{:.prec$}", price, prec = prec)
-        } else if price < 1_000_000f64 {
-            format!("{} {:0p$.prec$}", price as u64 / 1_000, price % 1_000f64, prec = prec, p = places)
-        } else {
-            let mils = price as u64 / 1_000_000;
-            let thousands = (price as u64 % 1000000) / 1_000;
-            let rest = price % 1_000f64;
+        let sign = if price.is_negative() { "-" } else { "" };
+        let int_part = price.abs().trunc().to_string();
 
-            format!("{} {:03} {:0p$.prec$}", mils, thousands, rest, prec = prec, p = places)
+        if use_decimals {
+            catalog.format_number(&format!("{}{}.{:02}", sign, int_part, price.abs().units() % 100))
+        } else {
+            catalog.format_number(&format!("{}{}", sign, int_part))
         }
     }
 }
@@ -581,6 +1232,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::dao::Language;
 
     #[test]
     fn test_split_phone_parts() {
@@ -594,29 +1246,100 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_format_quantity() {
+        let cs = Catalog::of(Language::Cs);
+        let quantity = |s: &str| s.parse::<Quantity>().unwrap();
+
+        assert_eq!(PdfCreator::format_quantity(quantity("1"), &None, cs), "1");
+        assert_eq!(PdfCreator::format_quantity(quantity("10"), &Some("ks".to_owned()), cs), "10 ks");
+        assert_eq!(PdfCreator::format_quantity(quantity("1.5"), &Some("hod".to_owned()), cs), "1,5 hod");
+        assert_eq!(PdfCreator::format_quantity(quantity("0.25"), &Some(String::new()), cs), "0,25");
+    }
+
+    #[test]
+    fn test_discount_label() {
+        let percent = |s: &str| s.parse::<Percent>().unwrap();
+
+        let label = |d: Discount| PdfCreator::discount_label("sleva", &d, Catalog::of(Language::Cs));
+
+        assert_eq!(label(Discount::new(Some(percent("10")), None)), "sleva 10 %");
+        assert_eq!(label(Discount::new(Some(percent("12.50")), None)), "sleva 12,5 %");
+        assert_eq!(label(Discount::new(None, Some(percent("100")))), "sleva");
+
+        let label = PdfCreator::discount_label("Rabatt", &Discount::new(Some(percent("12.50")), None), Catalog::of(Language::De));
+        assert_eq!(label, "Rabatt 12,5 %");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let metrics = FontMetrics::parse(&fs::read("fonts/Calibri Light.ttf").unwrap()).unwrap();
+        let note = Catalog::fill(Catalog::of(Language::Cs).trade_licence, "Městský úřad Litvínov");
+
+        assert_eq!(
+            PdfCreator::wrap(&note, Layout::default().legal.unwrap().width, |t| metrics.width(t, 8.0)),
+            vec![
+                "Fyzická osoba zapsaná v Živnostenském rejstříku. Úřad",
+                "příslušný podle § 71 odst. 2 živnostenského zákona:",
+                "Městský úřad Litvínov.",
+            ]
+        );
+
+        let chars = |t: &str| t.chars().count() as f64;
+        assert_eq!(PdfCreator::wrap("DODAVATEL / SUPPLIER", 11.0, chars), ["DODAVATEL /", "SUPPLIER"]);
+        assert_eq!(PdfCreator::wrap("", 10.0, chars), vec![""]);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let paginate = |rows: usize| PdfCreator::paginate(&vec![10.0; rows], 100.0, 250.0, 30.0, 50.0);
+
+        assert_eq!(paginate(0), [0]);
+        assert_eq!(paginate(5), [5]);
+        // the last row goes to the page with the totals
+        assert_eq!(paginate(6), [5, 1]);
+        assert_eq!(paginate(20), [7, 13]);
+        assert_eq!(paginate(40), [7, 22, 11]);
+
+        // rows taller than a page overflow it
+        assert_eq!(PdfCreator::paginate(&[300.0], 100.0, 250.0, 30.0, 50.0), [0, 1]);
+        assert_eq!(PdfCreator::paginate(&[300.0, 10.0], 100.0, 250.0, 30.0, 50.0), [0, 1, 1]);
+    }
+
     #[test]
     fn test_format_price() {
-        assert_eq!(PdfCreator::format_price(1.0, false), "1");
-        assert_eq!(PdfCreator::format_price(1.0, true), "1.00");
+        let cs = Catalog::of(Language::Cs);
+        let price = |s: &str| s.parse::<Money>().unwrap();
+
+        assert_eq!(PdfCreator::format_price(price("1"), false, cs), "1");
+        assert_eq!(PdfCreator::format_price(price("1"), true, cs), "1,00");
+
+        assert_eq!(PdfCreator::format_price(price("100"), false, cs), "100");
+        assert_eq!(PdfCreator::format_price(price("100"), true, cs), "100,00");
+
+        assert_eq!(PdfCreator::format_price(price("1000"), false, cs), "1 000");
+        assert_eq!(PdfCreator::format_price(price("1000.1"), true, cs), "1 000,10");
+        assert_eq!(PdfCreator::format_price(price("1000.12"), true, cs), "1 000,12");
+        assert_eq!(PdfCreator::format_price(price("1000.12"), false, cs), "1 000");
+        assert_eq!(PdfCreator::format_price(price("1000.50"), false, cs), "1 001");
 
-        assert_eq!(PdfCreator::format_price(100.0, false), "100");
-        assert_eq!(PdfCreator::format_price(100.0, true), "100.00");
+        assert_eq!(PdfCreator::format_price(price("100000"), false, cs), "100 000");
+        assert_eq!(PdfCreator::format_price(price("100000.1"), true, cs), "100 000,10");
+        assert_eq!(PdfCreator::format_price(price("100000.12"), true, cs), "100 000,12");
 
-        assert_eq!(PdfCreator::format_price(1000.0, false), "1 000");
-        assert_eq!(PdfCreator::format_price(1000.1, true), "1 000.10");
-        assert_eq!(PdfCreator::format_price(1000.12, true), "1 000.12");
-        assert_eq!(PdfCreator::format_price(1000.12, false), "1 000");
+        assert_eq!(PdfCreator::format_price(price("1000000"), false, cs), "1 000 000");
+        assert_eq!(PdfCreator::format_price(price("1000456.1"), true, cs), "1 000 456,10");
+        assert_eq!(PdfCreator::format_price(price("1000768.12"), true, cs), "1 000 768,12");
 
-        assert_eq!(PdfCreator::format_price(100000.0, false), "100 000");
-        assert_eq!(PdfCreator::format_price(100000.1, true), "100 000.10");
-        assert_eq!(PdfCreator::format_price(100000.12, true), "100 000.12");
+        assert_eq!(PdfCreator::format_price(price("10000000"), false, cs), "10 000 000");
+        assert_eq!(PdfCreator::format_price(price("10000000.1"), true, cs), "10 000 000,10");
+        assert_eq!(PdfCreator::format_price(price("10000000.12"), true, cs), "10 000 000,12");
 
-        assert_eq!(PdfCreator::format_price(1000000.0, false), "1 000 000");
-        assert_eq!(PdfCreator::format_price(1000456.1, true), "1 000 456.10");
-        assert_eq!(PdfCreator::format_price(1000768.12, true), "1 000 768.12");
+        assert_eq!(PdfCreator::format_price(price("-1234.5"), true, cs), "-1 234,50");
 
-        assert_eq!(PdfCreator::format_price(10000000.0, false), "10 000 000");
-        assert_eq!(PdfCreator::format_price(10000000.1, true), "10 000 000.10");
-        assert_eq!(PdfCreator::format_price(10000000.12, true), "10 000 000.12");
+        let (en, de) = (Catalog::of(Language::En), Catalog::of(Language::De));
+        assert_eq!(PdfCreator::format_price(price("-1234.5"), true, en), "-1,234.50");
+        assert_eq!(PdfCreator::format_price(price("1234567.89"), true, de), "1.234.567,89");
+        assert_eq!(PdfCreator::format_price(price("1234.5"), false, de), "1.235");
     }
 }
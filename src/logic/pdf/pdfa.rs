@@ -0,0 +1,418 @@
+//! Turns the PDF written by `printpdf` into PDF/A - PDF/A-2b for archiving, PDF/A-3b with the Factur-X invoice
+//! attached. `printpdf` knows neither the PDF/A identification nor embedded files and its only output intent is a CMYK
+//! one, so the saved document is patched afterwards. Fonts are embedded by `printpdf` as a whole already.
+
+use err_context::AnyError;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::logic::export::facturx;
+use crate::logic::pdf::icc;
+
+const PRODUCER: &str = "faktury";
+
+/// What the document is about, written to both its information dictionary and XMP metadata.
+pub struct Description {
+    pub title: String,
+    pub author: String,
+    /// Code of the invoice.
+    pub identifier: String,
+}
+
+/// A file embedded into the PDF, together with the relationship to the document (PDF/A-3 associated file).
+struct Attachment<'a> {
+    filename: &'a str,
+    mime_type: &'a str,
+    description: &'a str,
+    relationship: &'a str,
+    content: &'a [u8],
+}
+
+/// Makes PDF/A-2b of the `pdf`.
+pub fn convert(pdf: &[u8], description: &Description) -> Result<Vec<u8>, AnyError> {
+    patch(pdf, description, 2, None, "")
+}
+
+/// Makes PDF/A-3b of the `pdf`, with the Factur-X `xml` attached as its alternative representation.
+pub fn embed_facturx(pdf: &[u8], description: &Description, xml: &str) -> Result<Vec<u8>, AnyError> {
+    let attachment = Attachment {
+        filename: facturx::FILENAME,
+        mime_type: "text/xml",
+        description: "Factur-X", // TODO hard code value
+        relationship: "Alternative",
+        content: xml.as_bytes(),
+    };
+
+    patch(pdf, description, 3, Some(attachment), &facturx_xmp())
+}
+
+/// PDF/A of the given `part`, with XMP `extension` properties (and their schemas) added to the metadata.
+fn patch(pdf: &[u8], description: &Description, part: u8, attachment: Option<Attachment>, extension: &str) -> Result<Vec<u8>, AnyError> {
+    let mut doc = Document::load_mem(pdf)?;
+
+    let info = info(&mut doc, description)?;
+    let metadata = xmp_metadata(&info, description, part, extension);
+
+    if let Some(attachment) = attachment {
+        attach(&mut doc, attachment, &info.mod_date)?;
+    }
+    identify(&mut doc, &metadata)?;
+
+    doc.prune_objects();
+
+    let mut out = Vec::new();
+    doc.save_to(&mut out)?;
+
+    Ok(out)
+}
+
+/// Entries of the document information dictionary (not set by the `Description`) which have to match the XMP metadata.
+struct Info {
+    creation_date: String,
+    mod_date: String,
+    trapped: String,
+}
+
+fn info(doc: &mut Document, description: &Description) -> Result<Info, AnyError> {
+    let info_id = doc.trailer.get(b"Info")?.as_reference()?;
+    let info = doc.get_object_mut(info_id)?.as_dict_mut()?;
+
+    // PDF/X leftover, means nothing for PDF/A
+    info.remove(b"GTS_PDFXVersion");
+    info.set("Producer", Object::string_literal(PRODUCER));
+    info.set("Creator", Object::string_literal(PRODUCER));
+    info.set("Title", text_string(&description.title));
+    info.set("Author", text_string(&description.author));
+
+    let text = |key: &[u8]| -> Result<String, AnyError> { Ok(String::from_utf8_lossy(info.get(key)?.as_str()?).into_owned()) };
+
+    Ok(Info {
+        creation_date: text(b"CreationDate")?,
+        mod_date: text(b"ModDate")?,
+        trapped: String::from_utf8_lossy(info.get(b"Trapped")?.as_name()?).into_owned(),
+    })
+}
+
+fn attach(doc: &mut Document, attachment: Attachment, mod_date: &str) -> Result<(), AnyError> {
+    let mut params = Dictionary::new();
+    params.set("Size", attachment.content.len() as i64);
+    params.set("ModDate", Object::string_literal(mod_date));
+
+    let mut file_dict = Dictionary::new();
+    file_dict.set("Type", Object::Name(b"EmbeddedFile".to_vec()));
+    file_dict.set("Subtype", Object::Name(attachment.mime_type.as_bytes().to_vec()));
+    file_dict.set("Params", params);
+    let file_id = doc.add_object(Stream::new(file_dict, attachment.content.to_vec()));
+
+    let mut ef = Dictionary::new();
+    ef.set("F", file_id);
+    ef.set("UF", file_id);
+
+    let mut filespec = Dictionary::new();
+    filespec.set("Type", Object::Name(b"Filespec".to_vec()));
+    filespec.set("F", Object::string_literal(attachment.filename));
+    filespec.set("UF", Object::string_literal(attachment.filename));
+    filespec.set("Desc", Object::string_literal(attachment.description));
+    filespec.set("AFRelationship", Object::Name(attachment.relationship.as_bytes().to_vec()));
+    filespec.set("EF", ef);
+    let filespec_id = doc.add_object(filespec);
+
+    let mut embedded_files = Dictionary::new();
+    embedded_files.set(
+        "Names",
+        vec![Object::string_literal(attachment.filename), Object::Reference(filespec_id)],
+    );
+    let mut names = Dictionary::new();
+    names.set("EmbeddedFiles", embedded_files);
+
+    let catalog = catalog(doc)?;
+    catalog.set("Names", names);
+    catalog.set("AF", vec![Object::Reference(filespec_id)]);
+
+    Ok(())
+}
+
+/// Replaces the metadata `printpdf` writes for PDF/X with the PDF/A ones.
+fn identify(doc: &mut Document, metadata: &str) -> Result<(), AnyError> {
+    // PDF/A wants a comment with (at least four) binary characters right after the header, which isn't written by lopdf
+    doc.version = "1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}".to_string();
+
+    let mut metadata_dict = Dictionary::new();
+    metadata_dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    metadata_dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    // the metadata must stay readable without decompression
+    let mut stream = Stream::new(metadata_dict, metadata.as_bytes().to_vec());
+    stream.allows_compression = false;
+    let metadata_id = doc.add_object(stream);
+
+    // the colours are RGB, the CMYK output intent of printpdf is replaced (DeviceRGB wouldn't be allowed with it)
+    let mut profile_dict = Dictionary::new();
+    profile_dict.set("N", 3);
+    let profile_id = doc.add_object(Stream::new(profile_dict, icc::srgb_profile()));
+
+    let mut intent = Dictionary::new();
+    intent.set("Type", Object::Name(b"OutputIntent".to_vec()));
+    intent.set("S", Object::Name(b"GTS_PDFA1".to_vec()));
+    intent.set("OutputConditionIdentifier", Object::string_literal(icc::DESCRIPTION));
+    intent.set("RegistryName", Object::string_literal("http://www.color.org"));
+    intent.set("Info", Object::string_literal(icc::DESCRIPTION));
+    intent.set("DestOutputProfile", profile_id);
+    let intent_id = doc.add_object(intent);
+
+    let catalog = catalog(doc)?;
+    catalog.set("Metadata", metadata_id);
+
+    catalog.set("OutputIntents", vec![Object::Reference(intent_id)]);
+
+    // every optional content configuration must be named
+    if let Ok(properties) = catalog.get_mut(b"OCProperties").and_then(Object::as_dict_mut) {
+        properties
+            .get_mut(b"D")?
+            .as_dict_mut()?
+            .set("Name", Object::string_literal("Default"));
+    }
+
+    Ok(())
+}
+
+/// PDF text string - PDFDocEncoding can't hold all the Czech characters, so it's UTF-16 with the byte order mark.
+fn text_string(text: &str) -> Object {
+    let mut bytes = vec![0xfe, 0xff];
+    text.encode_utf16().for_each(|c| bytes.extend_from_slice(&c.to_be_bytes()));
+
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+fn catalog(doc: &mut Document) -> Result<&mut Dictionary, AnyError> {
+    let catalog_id: ObjectId = doc.trailer.get(b"Root")?.as_reference()?;
+    Ok(doc.get_object_mut(catalog_id)?.as_dict_mut()?)
+}
+
+/// XMP date (`2022-03-15T10:20:30+00:00`) of the PDF date (`D:20220315102030+00'00'`).
+fn xmp_date(pdf_date: &str) -> String {
+    let d = pdf_date.trim_start_matches("D:");
+
+    if d.len() < 14 || !d[..14].chars().all(|c| c.is_ascii_digit()) {
+        return String::new();
+    }
+
+    let zone = match d[14..].replace('\'', "") {
+        zone if zone.len() == 5 => format!("{}:{}", &zone[..3], &zone[3..]),
+        _ => "Z".to_string(),
+    };
+
+    format!(
+        "{}-{}-{}T{}:{}:{}{}",
+        &d[..4],
+        &d[4..6],
+        &d[6..8],
+        &d[8..10],
+        &d[10..12],
+        &d[12..14],
+        zone
+    )
+}
+
+fn xmp_metadata(info: &Info, description: &Description, part: u8, extension: &str) -> String {
+    let escape = crate::logic::export::xml::escape;
+
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+      <pdfaid:part>{part}</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:format>application/pdf</dc:format>
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">{title}</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+      <dc:creator>
+        <rdf:Seq>
+          <rdf:li>{author}</rdf:li>
+        </rdf:Seq>
+      </dc:creator>
+      <dc:identifier>{identifier}</dc:identifier>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <xmp:CreatorTool>{producer}</xmp:CreatorTool>
+      <xmp:CreateDate>{created}</xmp:CreateDate>
+      <xmp:ModifyDate>{modified}</xmp:ModifyDate>
+      <xmp:MetadataDate>{modified}</xmp:MetadataDate>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+      <pdf:Producer>{producer}</pdf:Producer>
+      <pdf:Trapped>{trapped}</pdf:Trapped>
+    </rdf:Description>
+{extension}  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        part = part,
+        title = escape(&description.title),
+        author = escape(&description.author),
+        identifier = escape(&description.identifier),
+        producer = PRODUCER,
+        created = xmp_date(&info.creation_date),
+        modified = xmp_date(&info.mod_date),
+        trapped = escape(&info.trapped),
+        extension = extension,
+    )
+}
+
+/// Factur-X properties, with the description of their (not predefined) schema.
+fn facturx_xmp() -> String {
+    let property = |name: &str, description: &str| {
+        format!(
+            r#"                <rdf:li rdf:parseType="Resource">
+                  <pdfaProperty:name>{}</pdfaProperty:name>
+                  <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+                  <pdfaProperty:category>external</pdfaProperty:category>
+                  <pdfaProperty:description>{}</pdfaProperty:description>
+                </rdf:li>
+"#,
+            name, description
+        )
+    };
+
+    format!(
+        r#"    <rdf:Description rdf:about="" xmlns:fx="urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#">
+      <fx:DocumentType>INVOICE</fx:DocumentType>
+      <fx:DocumentFileName>{filename}</fx:DocumentFileName>
+      <fx:Version>1.0</fx:Version>
+      <fx:ConformanceLevel>{level}</fx:ConformanceLevel>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/" xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#" xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+      <pdfaExtension:schemas>
+        <rdf:Bag>
+          <rdf:li rdf:parseType="Resource">
+            <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>
+            <pdfaSchema:namespaceURI>urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#</pdfaSchema:namespaceURI>
+            <pdfaSchema:prefix>fx</pdfaSchema:prefix>
+            <pdfaSchema:property>
+              <rdf:Seq>
+{properties}              </rdf:Seq>
+            </pdfaSchema:property>
+          </rdf:li>
+        </rdf:Bag>
+      </pdfaExtension:schemas>
+    </rdf:Description>
+"#,
+        filename = facturx::FILENAME,
+        level = facturx::CONFORMANCE_LEVEL,
+        properties = [
+            property("DocumentFileName", "name of the embedded XML invoice file"),
+            property("DocumentType", "INVOICE"),
+            property("Version", "version of the Factur-X XML schema"),
+            property("ConformanceLevel", "conformance level of the embedded XML invoice"),
+        ]
+        .concat(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use printpdf::{CustomPdfConformance, Mm, PdfConformance, PdfDocument};
+
+    use super::*;
+
+    fn printpdf_document() -> Vec<u8> {
+        let (doc, _, _) = PdfDocument::new("Faktura", Mm(210.0), Mm(297.0), "Layer 1");
+
+        doc.with_conformance(PdfConformance::Custom(CustomPdfConformance {
+            requires_icc_profile: true,
+            requires_xmp_metadata: true,
+            ..Default::default()
+        }))
+        .save_to_bytes()
+        .unwrap()
+    }
+
+    fn description() -> Description {
+        Description {
+            title: "Faktura 2022-0002".to_string(),
+            author: "Jan Novák".to_string(),
+            identifier: "2022-0002".to_string(),
+        }
+    }
+
+    fn metadata(doc: &Document) -> String {
+        let metadata_id = doc.catalog().unwrap().get(b"Metadata").unwrap().as_reference().unwrap();
+        let metadata = doc.get_object(metadata_id).unwrap().as_stream().unwrap();
+
+        String::from_utf8(metadata.content.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_convert() {
+        let pdf = convert(&printpdf_document(), &description()).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}\n".as_bytes()));
+
+        let doc = Document::load_mem(&pdf).unwrap();
+        let catalog = doc.catalog().unwrap();
+        assert!(catalog.get(b"AF").is_err());
+
+        let metadata = metadata(&doc);
+        assert!(metadata.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(metadata.contains("<rdf:li xml:lang=\"x-default\">Faktura 2022-0002</rdf:li>"));
+        assert!(metadata.contains("<rdf:li>Jan Novák</rdf:li>"));
+        assert!(metadata.contains("<dc:identifier>2022-0002</dc:identifier>"));
+
+        let info_id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let author = doc.get_dictionary(info_id).unwrap().get(b"Author").unwrap().as_str().unwrap();
+        assert_eq!(author, b"\xfe\xff\0J\0a\0n\0 \0N\0o\0v\0\xe1\0k");
+
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = doc.get_dictionary(intents[0].as_reference().unwrap()).unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+        let profile_id = intent.get(b"DestOutputProfile").unwrap().as_reference().unwrap();
+        let profile = doc.get_object(profile_id).unwrap().as_stream().unwrap();
+        assert_eq!(profile.dict.get(b"N").unwrap().as_i64().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_embed_facturx() {
+        let pdf = embed_facturx(&printpdf_document(), &description(), "<rsm:CrossIndustryInvoice/>").unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}\n".as_bytes()));
+
+        let doc = Document::load_mem(&pdf).unwrap();
+        let catalog = doc.catalog().unwrap();
+
+        let filespec_id = catalog.get(b"AF").unwrap().as_array().unwrap()[0].as_reference().unwrap();
+        let filespec = doc.get_dictionary(filespec_id).unwrap();
+        assert_eq!(filespec.get(b"UF").unwrap().as_str().unwrap(), b"factur-x.xml");
+        assert_eq!(filespec.get(b"AFRelationship").unwrap().as_name().unwrap(), b"Alternative");
+
+        let file_id = filespec
+            .get(b"EF")
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .get(b"F")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let file = doc.get_object(file_id).unwrap().as_stream().unwrap();
+        assert_eq!(
+            file.decompressed_content().unwrap_or_else(|_| file.content.clone()),
+            b"<rsm:CrossIndustryInvoice/>"
+        );
+
+        let metadata = metadata(&doc);
+        assert!(metadata.contains("<pdfaid:part>3</pdfaid:part>"));
+        assert!(metadata.contains("<fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>"));
+    }
+
+    #[test]
+    fn test_xmp_date() {
+        assert_eq!(xmp_date("D:20220315102030+00'00'"), "2022-03-15T10:20:30+00:00");
+        assert_eq!(xmp_date("D:20220315102030+02'00"), "2022-03-15T10:20:30+02:00");
+        assert_eq!(xmp_date("D:20220315102030"), "2022-03-15T10:20:30Z");
+        assert_eq!(xmp_date("D:2022"), "");
+    }
+}
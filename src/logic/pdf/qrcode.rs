@@ -2,24 +2,100 @@ use err_context::AnyError;
 use log::trace;
 use qrcode_generator::QrCodeEcc;
 
+use crate::dao::Money;
+use crate::logic::bank_account::BankAccount;
+use crate::logic::iban::Iban;
+
+/// Limits of the EPC069-12 fields, in characters.
+const EPC_NAME_LENGTH: usize = 70;
+const EPC_REMITTANCE_LENGTH: usize = 140;
+
 pub struct QrCode;
 
 impl QrCode {
-    pub fn get(
-        price: f32,
-        currency: &str,
-        country_code: &str,
-        account_prefix: Option<u64>,
-        account: u64,
-        bank_code: u16,
-        vs: &str,
-    ) -> Result<Vec<u8>, AnyError> {
-        let iban = crate::logic::iban::create(country_code, account_prefix, account, bank_code)?;
-
-        let payment_string = format!("SPD*1.0*ACC:{}*AM:{:.2}*CC:{}*X-VS:{}", iban, price, currency, vs,);
+    /// The Czech Short Payment Descriptor.
+    pub fn spayd(price: Money, currency: &str, iban: &Iban, vs: &str) -> Result<Vec<u8>, AnyError> {
+        Self::image(spayd_string(price, currency, iban, vs), QrCodeEcc::Low)
+    }
 
+    /// The EPC069-12 SEPA credit transfer of EUR to the account of the `name`, with the `remittance` information.
+    pub fn epc(price: Money, account: &BankAccount, name: &str, remittance: &str) -> Result<Vec<u8>, AnyError> {
+        // the guidelines require the medium error correction
+        Self::image(epc_string(price, account, name, remittance), QrCodeEcc::Medium)
+    }
+
+    fn image(payment_string: String, ecc: QrCodeEcc) -> Result<Vec<u8>, AnyError> {
         trace!("Payment string: {}", payment_string);
 
-        Ok(qrcode_generator::to_image(payment_string, QrCodeEcc::Low, 256)?)
+        Ok(qrcode_generator::to_image(payment_string, ecc, 256)?)
+    }
+}
+
+fn spayd_string(price: Money, currency: &str, iban: &Iban, vs: &str) -> String {
+    format!("SPD*1.0*ACC:{}*AM:{}*CC:{}*X-VS:{}", iban.as_str(), price, currency, vs)
+}
+
+/// Version 002 with the UTF-8 character set, the BIC is optional there.
+fn epc_string(price: Money, account: &BankAccount, name: &str, remittance: &str) -> String {
+    // the amount is optional, but at least 0.01 when present
+    let amount = if price > Money::ZERO {
+        format!("EUR{}", price)
+    } else {
+        String::new()
+    };
+
+    [
+        "BCD",
+        "002",
+        "1",
+        "SCT",
+        account.bic.as_deref().unwrap_or_default(),
+        &truncate(name, EPC_NAME_LENGTH),
+        account.iban.as_str(),
+        &amount,
+        // purpose and the structured (creditor) reference
+        "",
+        "",
+        &truncate(remittance, EPC_REMITTANCE_LENGTH),
+    ]
+    .join("\n")
+}
+
+fn truncate(text: &str, length: usize) -> String {
+    text.trim().chars().take(length).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spayd_string() {
+        let iban = "CZ6508000000192000145399".parse().unwrap();
+
+        assert_eq!(
+            spayd_string("1234.50".parse().unwrap(), "CZK", &iban, "2024001"),
+            "SPD*1.0*ACC:CZ6508000000192000145399*AM:1234.50*CC:CZK*X-VS:2024001"
+        );
+    }
+
+    #[test]
+    fn test_epc_string() {
+        let account = BankAccount {
+            iban: "DE89370400440532013000".parse().unwrap(),
+            bic: Some("COBADEFFXXX".to_owned()),
+        };
+
+        assert_eq!(
+            epc_string("1234.50".parse().unwrap(), &account, "Jan Novák", "2024-0001"),
+            "BCD\n002\n1\nSCT\nCOBADEFFXXX\nJan Novák\nDE89370400440532013000\nEUR1234.50\n\n\n2024-0001"
+        );
+
+        let account = BankAccount { bic: None, ..account };
+        let name = "x".repeat(80);
+        assert_eq!(
+            epc_string(Money::ZERO, &account, &name, "2024-0001"),
+            format!("BCD\n002\n1\nSCT\n\n{}\nDE89370400440532013000\n\n\n\n2024-0001", "x".repeat(70))
+        );
     }
 }
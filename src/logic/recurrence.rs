@@ -0,0 +1,231 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use err_context::AnyError;
+use log::{debug, info, warn};
+
+use crate::config::SchedulerConfig;
+use crate::dao::{Dao, DaoResult, Invoice, InvoiceKind, InvoiceRecurrence, NewInvoiceRecurrence, RecurrencePeriod};
+
+/// Sets up a recurrence of the `invoice`. The first run is the first matching day, one period after the invoice was
+/// created, but never in the past.
+pub async fn insert_recurrence(
+    dao: &Dao,
+    invoice: &Invoice,
+    period: RecurrencePeriod,
+    day_of_month: u8,
+    end_date: Option<NaiveDate>,
+) -> Result<InvoiceRecurrence, AnyError> {
+    if invoice.kind == InvoiceKind::CreditNote {
+        return Err(AnyError::from("A credit note can't recur"));
+    }
+
+    let next_run = first_run(invoice.created, period, day_of_month, today());
+
+    debug!("First run of recurrence of invoice {} is {}", invoice.id, next_run);
+
+    dao.insert_recurrence(&NewInvoiceRecurrence {
+        invoice_id: invoice.id,
+        period,
+        day_of_month: day_of_month as i16,
+        end_date,
+        next_run,
+    })
+    .await
+}
+
+pub async fn set_paused(dao: &Dao, mut recurrence: InvoiceRecurrence, paused: bool) -> DaoResult<InvoiceRecurrence> {
+    recurrence.paused = paused;
+    dao.update_recurrence(&recurrence).await?;
+    Ok(recurrence)
+}
+
+/// Upcoming runs of the recurrence (at most `count`); there are none when it's paused or finished.
+pub fn upcoming_runs(recurrence: &InvoiceRecurrence, count: usize) -> Vec<NaiveDate> {
+    if recurrence.paused {
+        return Vec::new();
+    }
+
+    let mut runs = Vec::with_capacity(count);
+    let mut run = recurrence.next_run;
+
+    while runs.len() < count && recurrence.end_date.map(|end| run <= end).unwrap_or(true) {
+        runs.push(run);
+        run = next_run(recurrence, run);
+    }
+
+    runs
+}
+
+/// Runs the recurrences periodically, for as long as the server is running.
+pub fn start_scheduler(dao: Dao, config: SchedulerConfig) {
+    let period = config.interval.to_std().expect("Scheduler interval must be positive");
+
+    info!("Starting recurring invoices scheduler, running every {:?}", period);
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(period);
+
+        loop {
+            interval.tick().await;
+
+            match run_due(&dao, today()).await {
+                Ok(0) => debug!("No recurring invoices to create"),
+                Ok(count) => info!("Created {} recurring invoice(s)", count),
+                Err(e) => warn!("Error while creating recurring invoices: {}", e),
+            }
+        }
+    });
+}
+
+/// Creates invoices of all recurrences due until the `date` (incl.), catching up the missed runs too. Returns the
+/// number of created invoices. A failing recurrence is skipped, so it doesn't stop the other ones.
+pub async fn run_due(dao: &Dao, date: NaiveDate) -> Result<usize, AnyError> {
+    let mut created = 0;
+
+    for recurrence in dao.get_due_recurrences(date).await? {
+        if let Err(e) = run(dao, recurrence.clone(), date, &mut created).await {
+            warn!("Error while running recurrence {}: {}", recurrence.id, e);
+        }
+    }
+
+    Ok(created)
+}
+
+/// Creates invoices of the recurrence due until the `date` (incl.), adding them to the `created` count.
+async fn run(dao: &Dao, mut recurrence: InvoiceRecurrence, date: NaiveDate, created: &mut usize) -> Result<(), AnyError> {
+    let template = match dao.get_invoice(recurrence.invoice_id as u32).await? {
+        Some((invoice, _)) => invoice,
+        None => return Ok(()),
+    };
+
+    while recurrence.next_run <= date && recurrence.end_date.map(|end| recurrence.next_run <= end).unwrap_or(true) {
+        let run = recurrence.next_run;
+        recurrence.next_run = next_run(&recurrence, run);
+
+        let invoice = create_invoice(dao, &template, run, &recurrence).await?;
+        debug!("Created invoice {} by recurrence {}", invoice.code, recurrence.id);
+        *created += 1;
+    }
+
+    Ok(())
+}
+
+/// Copies the template as created on the `run` date and due in the same length as the template, numbered for that date.
+/// The `recurrence` (with its next run already moved) is updated together with the insert, so the invoice is never
+/// created twice.
+async fn create_invoice(dao: &Dao, template: &Invoice, run: NaiveDate, recurrence: &InvoiceRecurrence) -> Result<Invoice, AnyError> {
+    let rows = dao.get_invoice_rows(template.id as u32).await?;
+    let invoice = Invoice {
+        created: run,
+        ..super::made_of(template, template.kind, template.original_invoice_id, None)
+    };
+
+    let (invoice, _) = super::insert_numbered(
+        dao,
+        &invoice,
+        &super::copies_of(&rows, false),
+        Some(template.pay_until - template.created),
+        Some(recurrence),
+    )
+    .await?;
+
+    Ok(invoice)
+}
+
+fn next_run(recurrence: &InvoiceRecurrence, after: NaiveDate) -> NaiveDate {
+    shift(after, recurrence.period.months(), recurrence.day_of_month as u8)
+}
+
+fn first_run(created: NaiveDate, period: RecurrencePeriod, day_of_month: u8, today: NaiveDate) -> NaiveDate {
+    let mut run = shift(created, period.months(), day_of_month);
+
+    while run < today {
+        run = shift(run, period.months(), day_of_month);
+    }
+
+    run
+}
+
+/// The `day_of_month` in the month `months` after the month of `date`; the day is limited to the last day of the
+/// month (so e.g. 31 means "the last day of month").
+fn shift(date: NaiveDate, months: u32, day_of_month: u8) -> NaiveDate {
+    let month0 = date.month0() + months;
+    let year = date.year() + (month0 / 12) as i32;
+    let month = month0 % 12 + 1;
+
+    let first_of_next = if month == 12 {
+        NaiveDate::from_ymd(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd(year, month + 1, 1)
+    };
+    let last_day = (first_of_next - Duration::days(1)).day();
+
+    NaiveDate::from_ymd(year, month, (day_of_month as u32).clamp(1, last_day))
+}
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn recurrence(period: RecurrencePeriod, day_of_month: i16, next_run: &str, end_date: Option<&str>) -> InvoiceRecurrence {
+        InvoiceRecurrence {
+            id: 1,
+            invoice_id: 1,
+            period,
+            day_of_month,
+            end_date: end_date.map(date),
+            next_run: date(next_run),
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn test_shift() {
+        assert_eq!(shift(date("2022-01-15"), 1, 1), date("2022-02-01"));
+        assert_eq!(shift(date("2022-01-15"), 1, 31), date("2022-02-28"));
+        assert_eq!(shift(date("2024-01-15"), 1, 30), date("2024-02-29"));
+        assert_eq!(shift(date("2022-11-30"), 3, 15), date("2023-02-15"));
+        assert_eq!(shift(date("2022-12-01"), 1, 31), date("2023-01-31"));
+    }
+
+    #[test]
+    fn test_first_run() {
+        let today = date("2022-06-10");
+
+        assert_eq!(
+            first_run(date("2022-06-01"), RecurrencePeriod::Monthly, 5, today),
+            date("2022-07-05")
+        );
+        assert_eq!(
+            first_run(date("2022-01-01"), RecurrencePeriod::Monthly, 15, today),
+            date("2022-06-15")
+        );
+        assert_eq!(
+            first_run(date("2022-01-01"), RecurrencePeriod::Quarterly, 1, today),
+            date("2022-07-01")
+        );
+    }
+
+    #[test]
+    fn test_upcoming_runs() {
+        let rec = recurrence(RecurrencePeriod::Monthly, 31, "2022-01-31", None);
+        assert_eq!(
+            upcoming_runs(&rec, 3),
+            vec![date("2022-01-31"), date("2022-02-28"), date("2022-03-31")]
+        );
+
+        let rec = recurrence(RecurrencePeriod::Quarterly, 1, "2022-01-01", Some("2022-06-30"));
+        assert_eq!(upcoming_runs(&rec, 5), vec![date("2022-01-01"), date("2022-04-01")]);
+
+        let mut rec = recurrence(RecurrencePeriod::Monthly, 1, "2022-01-01", None);
+        rec.paused = true;
+        assert!(upcoming_runs(&rec, 3).is_empty());
+    }
+}
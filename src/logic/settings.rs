@@ -1,11 +1,12 @@
+use std::collections::HashMap;
 use std::ops::Deref;
 
 use chrono::Duration;
 use log::trace;
 use serde::{Deserialize, Serialize};
 
-use crate::dao::Account;
-use crate::logic::invoices::InvoiceNamingSchemaType;
+use crate::dao::{Account, Entrepreneur};
+use crate::logic::invoices::{InvoiceNamingSchemaType, InvoiceSeries};
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct AccountSettings {
@@ -30,12 +31,59 @@ impl From<&Account> for AccountSettings {
 pub struct AccountInvoiceSettings {
     #[serde(default)]
     pub naming_schema: InvoiceNamingSchemaType,
+    /// Naming schemas of particular entrepreneurs (by ID), overriding the `naming_schema`.
+    #[serde(default)]
+    pub entrepreneur_naming_schemas: HashMap<i32, InvoiceNamingSchemaType>,
+    /// Series of the kinds of documents; the codes of credit notes and proformas start with theirs.
+    #[serde(default)]
+    pub series: InvoiceSeries,
     #[serde(default, with = "default_due_length_serde")]
     pub default_due_length: DefaultDueLength,
     #[serde(default)]
     pub show_lawyerbox_handover: bool,
+    /// Makes PDFs of invoices PDF/A-2b, for the long-term archiving.
+    #[serde(default)]
+    pub pdfa: bool,
+    /// Makes PDFs of invoices PDF/A-3 with the Factur-X (EN 16931) invoice embedded (regardless of `pdfa`).
+    #[serde(default)]
+    pub facturx: bool,
+    /// Payment QR codes printed on invoices.
+    #[serde(default)]
+    pub qr_codes: QrCodeFormat,
+}
+
+impl AccountInvoiceSettings {
+    pub fn naming_schema_of(&self, entrepreneur: &Entrepreneur) -> &InvoiceNamingSchemaType {
+        self.entrepreneur_naming_schemas
+            .get(&entrepreneur.id)
+            .unwrap_or(&self.naming_schema)
+    }
 }
 
+/// The Czech Short Payment Descriptor (SPAYD) is read by the Czech and Slovak banks, the EPC069-12 SEPA credit transfer
+/// code by the others - EUR invoices only.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum QrCodeFormat {
+    #[default]
+    Spayd,
+    Epc,
+    Both,
+    None,
+}
+
+impl QrCodeFormat {
+    pub fn spayd(self) -> bool {
+        matches!(self, QrCodeFormat::Spayd | QrCodeFormat::Both)
+    }
+
+    pub fn epc(self, currency_code: &str) -> bool {
+        matches!(self, QrCodeFormat::Epc | QrCodeFormat::Both) && currency_code == EPC_CURRENCY
+    }
+}
+
+/// The only currency of the SEPA credit transfers.
+const EPC_CURRENCY: &str = "EUR";
+
 #[derive(Clone, Debug)]
 pub struct DefaultDueLength(Duration);
 
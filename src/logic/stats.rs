@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use chrono::Datelike;
+use itertools::Itertools;
+
+use crate::dao::{Dao, DaoResult, Invoice, InvoiceKind, InvoiceRow, Money};
+use crate::logic::currency;
+use crate::logic::vat::VatRecap;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MonthlyMoney {
+    pub money: Money,
+    pub money_gross: Money,
+    pub month: u8,
+}
+
+/// Sums of paid and unpaid invoices of the entrepreneur in the given year, by months (months without any invoice
+/// are missing). Invoices in foreign currencies count at their exchange rates.
+pub async fn get_yearly_stats(dao: &Dao, entrepreneur_id: u32, year: u16) -> DaoResult<(Vec<MonthlyMoney>, Vec<MonthlyMoney>)> {
+    let invoices = dao.get_yearly_invoices(entrepreneur_id, year).await?;
+    let ids = invoices.iter().map(|i| i.id).collect_vec();
+    let rows = dao.get_rows_of_invoices(&ids).await?;
+
+    Ok((monthly_sums(&invoices, &rows, true), monthly_sums(&invoices, &rows, false)))
+}
+
+/// Credit notes are netted against their original invoice - they count to its month and to its paid/unpaid sums.
+/// Proformas aren't tax documents, so they don't count at all - their final invoices do.
+fn monthly_sums(invoices: &[Invoice], rows: &HashMap<i32, Vec<InvoiceRow>>, paid: bool) -> Vec<MonthlyMoney> {
+    let by_id: HashMap<i32, &Invoice> = invoices.iter().map(|i| (i.id, i)).collect();
+
+    invoices
+        .iter()
+        .filter_map(|invoice| {
+            let counted_as = invoice
+                .original_invoice_id
+                .and_then(|id| by_id.get(&id).copied())
+                .unwrap_or(invoice);
+
+            if counted_as.payed.is_some() != paid || invoice.kind == InvoiceKind::Proforma {
+                return None;
+            }
+
+            let recap = VatRecap::new(invoice, rows.get(&invoice.id).map(Vec::as_slice).unwrap_or_default());
+            let (base, gross) = (currency::to_home(invoice, recap.base), currency::to_home(invoice, recap.gross));
+            Some((counted_as.created.month() as u8, (base, gross)))
+        })
+        .into_group_map()
+        .into_iter()
+        .map(|(month, sums)| MonthlyMoney {
+            money: sums.iter().map(|(net, _)| *net).sum(),
+            money_gross: sums.iter().map(|(_, gross)| *gross).sum(),
+            month,
+        })
+        .sorted_by_key(|m| m.month)
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::fixtures::{self, money, row};
+
+    fn invoice(id: i32, month: u32, payed: bool, original_invoice_id: Option<i32>) -> Invoice {
+        let created = NaiveDate::from_ymd(2022, month, 1);
+        let kind = original_invoice_id.map(|_| InvoiceKind::CreditNote).unwrap_or_default();
+
+        Invoice {
+            original_invoice_id,
+            created,
+            pay_until: created,
+            payed: if payed { Some(created) } else { None },
+            ..fixtures::invoice(id, kind)
+        }
+    }
+
+    #[test]
+    fn test_credit_notes_and_proformas() {
+        let mut invoices = [
+            invoice(1, 1, true, None),
+            invoice(2, 1, false, None),
+            // credit note issued in March for the January invoice
+            invoice(3, 3, false, Some(1)),
+            invoice(4, 2, false, None),
+            invoice(5, 2, true, None),
+            // the final invoice of the paid proforma
+            invoice(6, 4, true, None),
+        ];
+        // proformas don't count at all, paid or not
+        invoices[3].kind = InvoiceKind::Proforma;
+        invoices[4].kind = InvoiceKind::Proforma;
+        invoices[5].proforma_id = Some(5);
+
+        let rows = vec![
+            (1, vec![row(1, "1000", "2", Some(21))]),
+            (2, vec![row(2, "500", "1", Some(21))]),
+            (3, vec![row(3, "1000", "-1", Some(21))]),
+            (4, vec![row(4, "300", "1", Some(21))]),
+            (5, vec![row(5, "200", "1", Some(21))]),
+            (6, vec![row(6, "200", "1", Some(21))]),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            monthly_sums(&invoices, &rows, true),
+            vec![
+                MonthlyMoney {
+                    money: money("1000"),
+                    money_gross: money("1210"),
+                    month: 1
+                },
+                MonthlyMoney {
+                    money: money("200"),
+                    money_gross: money("242"),
+                    month: 4
+                }
+            ]
+        );
+        assert_eq!(
+            monthly_sums(&invoices, &rows, false),
+            vec![MonthlyMoney {
+                money: money("500"),
+                money_gross: money("605"),
+                month: 1
+            }]
+        );
+    }
+    #[test]
+    fn test_foreign_currencies() {
+        let mut invoices = [invoice(1, 1, true, None), invoice(2, 1, true, None)];
+        invoices[1].currency_code = "EUR".to_string();
+        invoices[1].exchange_rate = "24.5".parse().unwrap();
+
+        let rows = vec![(1, vec![row(1, "1000", "1", Some(21))]), (2, vec![row(2, "100", "1", Some(21))])]
+            .into_iter()
+            .collect();
+
+        // 1000 CZK + 100 EUR at 24.50
+        assert_eq!(
+            monthly_sums(&invoices, &rows, true),
+            vec![MonthlyMoney {
+                money: money("3450"),
+                money_gross: money("4174.50"),
+                month: 1
+            }]
+        );
+    }
+}
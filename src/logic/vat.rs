@@ -0,0 +1,235 @@
+use itertools::Itertools;
+
+use crate::dao::{Decimal, Invoice, InvoiceRow, Money};
+use crate::logic::discount::Discount;
+
+/// VAT rates (in percent) which can be used on an invoice row. A row without any rate is exempt from VAT.
+pub const VAT_RATES: [u16; 3] = [21, 12, 0];
+
+pub fn is_valid_rate(rate: Option<u16>) -> bool {
+    match rate {
+        Some(rate) => VAT_RATES.contains(&rate),
+        None => true,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VatRecapLine {
+    /// `None` for rows exempt from VAT.
+    pub rate: Option<u16>,
+    /// Share of the invoice discount, already deducted from the `base`.
+    pub discount: Money,
+    pub base: Money,
+    pub vat: Money,
+    pub gross: Money,
+}
+
+/// Summary of an invoice grouped by VAT rates.
+///
+/// Rows are grouped by their rate, after their own discounts, and the invoice discount is split among the groups
+/// proportionally (see `Discount::split`). VAT is then computed and rounded (to cents) once per group. This is the
+/// single place where invoice totals are computed - the invoices list and yearly stats use it as well, so all the
+/// numbers always match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VatRecap {
+    pub lines: Vec<VatRecapLine>,
+    /// Sum of the rows, before the invoice discount.
+    pub subtotal: Money,
+    /// Invoice discount (the rows' own discounts are already included in the `subtotal`).
+    pub discount: Money,
+    pub base: Money,
+    pub vat: Money,
+    pub gross: Money,
+}
+
+impl VatRecap {
+    pub fn new(invoice: &Invoice, rows: &[InvoiceRow]) -> Self {
+        let groups = rows
+            .iter()
+            .map(|row| (row.vat_rate.map(|r| r as u16), row_net_price(row)))
+            .into_group_map()
+            .into_iter()
+            .map(|(rate, prices)| (rate, prices.into_iter().sum::<Money>()))
+            .sorted_by(|a, b| b.0.cmp(&a.0))
+            .collect_vec();
+
+        let subtotals = groups.iter().map(|(_, subtotal)| *subtotal).collect_vec();
+        let discounts = Discount::of_invoice(invoice).split(&subtotals);
+
+        let lines = groups
+            .into_iter()
+            .zip(discounts.iter())
+            .map(|((rate, subtotal), discount)| {
+                let base = subtotal - *discount;
+                let vat = vat_of(base, rate);
+
+                VatRecapLine {
+                    rate,
+                    discount: *discount,
+                    base,
+                    vat,
+                    gross: base + vat,
+                }
+            })
+            .collect_vec();
+
+        let base = lines.iter().map(|l| l.base).sum();
+        let vat = lines.iter().map(|l| l.vat).sum();
+
+        VatRecap {
+            lines,
+            subtotal: subtotals.iter().sum(),
+            discount: discounts.iter().sum(),
+            base,
+            vat,
+            gross: base + vat,
+        }
+    }
+
+    /// Whether any of the rows carries a VAT rate, i.e. whether the VAT should be displayed at all.
+    pub fn has_vat(&self) -> bool {
+        self.lines.iter().any(|l| l.rate.is_some())
+    }
+}
+
+/// Price of the whole row before its discount, rounded to cents.
+pub fn row_price(row: &InvoiceRow) -> Money {
+    row.item_price.times(row.item_count)
+}
+
+/// Discount of the whole row, rounded to cents.
+pub fn row_discount(row: &InvoiceRow) -> Money {
+    Discount::of_row(row).of(row_price(row))
+}
+
+/// Price of the whole row after its discount.
+pub fn row_net_price(row: &InvoiceRow) -> Money {
+    row_price(row) - row_discount(row)
+}
+
+pub fn vat_of(base: Money, rate: Option<u16>) -> Money {
+    match rate {
+        Some(rate) => base.percent(Decimal::<0>::from_int(rate as i64)),
+        None => Money::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dao::InvoiceKind;
+    use crate::fixtures::{invoice, money, row};
+
+    #[test]
+    fn test_recap_groups_by_rate() {
+        let recap = VatRecap::new(
+            &invoice(1, InvoiceKind::Invoice),
+            &[
+                row(1, "100", "2", Some(21)),
+                row(1, "50.10", "1", Some(12)),
+                row(1, "10.05", "3", Some(21)),
+                row(1, "5", "1", None),
+            ],
+        );
+
+        assert_eq!(
+            recap.lines,
+            vec![
+                VatRecapLine {
+                    rate: Some(21),
+                    discount: Money::ZERO,
+                    base: money("230.15"),
+                    vat: money("48.33"), // 42 + 6.3315
+                    gross: money("278.48")
+                },
+                VatRecapLine {
+                    rate: Some(12),
+                    discount: Money::ZERO,
+                    base: money("50.10"),
+                    vat: money("6.01"), // 6.012
+                    gross: money("56.11")
+                },
+                VatRecapLine {
+                    rate: None,
+                    discount: Money::ZERO,
+                    base: money("5"),
+                    vat: Money::ZERO,
+                    gross: money("5")
+                },
+            ]
+        );
+
+        assert_eq!(recap.base, money("285.25"));
+        assert_eq!(recap.vat, money("54.34"));
+        assert_eq!(recap.gross, money("339.59"));
+        assert!(recap.has_vat());
+    }
+
+    #[test]
+    fn test_recap_without_vat() {
+        let recap = VatRecap::new(&invoice(1, InvoiceKind::Invoice), &[row(1, "100", "2", None)]);
+
+        assert!(!recap.has_vat());
+        assert_eq!(recap.base, money("200"));
+        assert_eq!(recap.gross, money("200"));
+    }
+
+    #[test]
+    fn test_recap_with_discounts() {
+        let mut discounted = row(1, "1000", "1", Some(21));
+        discounted.discount_percent = Some(money("10"));
+
+        let rows = [discounted, row(1, "500", "2", Some(12)), row(1, "100", "1", None)];
+        let mut invoice = invoice(1, InvoiceKind::Invoice);
+        invoice.discount_amount = Some(money("100"));
+        let recap = VatRecap::new(&invoice, &rows);
+
+        // the invoice discount is split 45 / 50 / 5 (by the row prices 900 / 1000 / 100)
+        assert_eq!(
+            recap.lines.iter().map(|l| (l.base, l.vat)).collect_vec(),
+            vec![
+                (money("855"), money("179.55")),
+                (money("950"), money("114")),
+                (money("95"), Money::ZERO),
+            ]
+        );
+        assert_eq!(recap.subtotal, money("2000"));
+        assert_eq!(recap.discount, money("100"));
+        assert_eq!(recap.base, money("1900"));
+        assert_eq!(recap.gross, money("2193.55"));
+
+        invoice.discount_amount = None;
+        invoice.discount_percent = Some(money("12.5"));
+        let recap = VatRecap::new(&invoice, &rows);
+        assert_eq!(recap.discount, money("250"));
+        assert_eq!(recap.base, money("1750"));
+    }
+
+    #[test]
+    fn test_row_discount() {
+        let mut r = row(1, "333.33", "3", None);
+        r.discount_percent = Some(money("15"));
+        assert_eq!(row_discount(&r), money("150")); // 149.9985
+        assert_eq!(row_net_price(&r), money("849.99"));
+
+        r.discount_percent = None;
+        r.discount_amount = Some(money("99.99"));
+        assert_eq!(row_net_price(&r), money("900"));
+    }
+
+    #[test]
+    fn test_fractional_quantity() {
+        assert_eq!(row_price(&row(1, "500", "1.5", None)), money("750"));
+        assert_eq!(row_price(&row(1, "333.33", "0.25", None)), money("83.33")); // 83.3325
+        assert_eq!(row_price(&row(1, "0.99", "0.005", None)), money("0")); // 0.00495
+        assert_eq!(row_price(&row(1, "1.01", "0.005", None)), money("0.01")); // 0.00505
+    }
+
+    #[test]
+    fn test_valid_rates() {
+        assert!(is_valid_rate(None));
+        assert!(is_valid_rate(Some(21)));
+        assert!(is_valid_rate(Some(0)));
+        assert!(!is_valid_rate(Some(15)));
+    }
+}
@@ -15,12 +15,14 @@ use actix_files::NamedFile;
 use actix_web::{middleware, web, App, HttpRequest, HttpServer, Result as ActixResult};
 use log::{debug, info, trace};
 
-use crate::config::{AccountsConfig, AppConfig};
+use crate::config::{AccountsConfig, AppConfig, ExchangeRatesConfig};
 use crate::dao::Dao;
 use crate::logic::pdf::PdfManager;
 
 mod config;
 mod dao;
+#[cfg(test)]
+mod fixtures;
 mod handlers;
 mod logic;
 
@@ -29,6 +31,7 @@ pub struct RequestContext {
     dao: Dao,
     pdf_manager: PdfManager,
     accounts_config: AccountsConfig,
+    exchange_rates_config: ExchangeRatesConfig,
 }
 
 async fn web_ui(req: HttpRequest) -> ActixResult<NamedFile> {
@@ -61,6 +64,9 @@ async fn main() {
     let pdf_manager = PdfManager::new().expect("Could not initialize PDF manager!"); // let it fail
     let addr = SocketAddr::from_str(&config.http.listen).expect("Could not parse listen address!"); // let it fail
 
+    logic::recurrence::start_scheduler(dao.clone(), config.scheduler.clone());
+    logic::exchange_rates::start_import(dao.clone(), config.exchange_rates.clone());
+
     info!("Starting server on {}", addr);
 
     // TODO CORS headers
@@ -70,6 +76,7 @@ async fn main() {
             dao: dao.clone(),
             pdf_manager: pdf_manager.clone(),
             accounts_config: config.accounts.clone(),
+            exchange_rates_config: config.exchange_rates.clone(),
         };
 
         let cors = config
@@ -86,30 +93,56 @@ async fn main() {
             .wrap(cors)
             .wrap(middleware::Compress::default())
             .service(handlers::download_invoice)
+            .service(handlers::download_isdoc)
+            .service(handlers::download_isdocx)
+            .service(handlers::download_ubl)
+            .service(handlers::export_pohoda)
+            .service(handlers::preview_pdf_layout)
+            .service(handlers::get_pdf_layout)
+            .service(handlers::update_pdf_layout)
+            .service(handlers::delete_pdf_layout)
+            .service(handlers::get_entrepreneur_logo)
+            .service(handlers::update_entrepreneur_logo)
+            .service(handlers::delete_entrepreneur_logo)
+            .service(handlers::import_exchange_rates)
+            .service(handlers::get_exchange_rate)
+            .service(handlers::fill_invoice_exchange_rate)
             .service(handlers::account_login)
             .service(handlers::account_logout)
             .service(handlers::get_entrepreneur)
             .service(handlers::get_contact)
+            .service(handlers::get_bank_account)
             .service(handlers::get_invoice)
             .service(handlers::get_invoice_with_rows)
             .service(handlers::list_entrepreneurs)
             .service(handlers::list_contacts)
+            .service(handlers::list_bank_accounts)
             .service(handlers::list_invoices)
             .service(handlers::list_invoice_rows)
             .service(handlers::insert_entrepreneur)
             .service(handlers::insert_contact)
+            .service(handlers::insert_bank_account)
             .service(handlers::insert_invoice)
             .service(handlers::copy_invoice)
+            .service(handlers::insert_credit_note)
+            .service(handlers::finalize_proforma)
             .service(handlers::insert_invoice_row)
             .service(handlers::update_entrepreneur)
             .service(handlers::update_contact)
+            .service(handlers::update_bank_account)
             .service(handlers::update_invoice)
             .service(handlers::update_invoice_row)
             .service(handlers::delete_entrepreneur)
             .service(handlers::delete_contact)
+            .service(handlers::delete_bank_account)
             .service(handlers::delete_invoice)
             .service(handlers::delete_invoice_row)
             .service(handlers::get_yearly_stats)
+            .service(handlers::get_numbering_gaps)
+            .service(handlers::list_recurrences)
+            .service(handlers::insert_recurrence)
+            .service(handlers::pause_recurrence)
+            .service(handlers::resume_recurrence)
             .service(handlers::login_salt)
             .service(handlers::status)
             .route("/{filename:.*}", web::get().to(web_ui))